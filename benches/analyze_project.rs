@@ -0,0 +1,64 @@
+//! Serial vs parallel `AnalyzerManager` runs over a synthetic 1000-file tree.
+//!
+//! Run with `cargo bench --bench analyze_project`.
+
+use deeptracking_llamaindex::AnalyzerManager;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const FILES: usize = 1000;
+const ROUNDS: u32 = 5;
+
+/// Writes `FILES` Rust modules spread over 20 directories, each importing
+/// its neighbour and defining a few functions.
+fn synthetic_project(root: &Path) {
+    for i in 0..FILES {
+        let dir = root.join(format!("src/group_{}", i % 20));
+        fs::create_dir_all(&dir).unwrap();
+        let source = format!(
+            "use crate::group_{}::module_{};\n\n\
+             pub fn first_{i}(x: u32) -> u32 {{ if x > 1 {{ x * 2 }} else {{ x }} }}\n\
+             pub fn second_{i}(v: &[u32]) -> u32 {{ v.iter().map(|x| first_{i}(*x)).sum() }}\n\
+             pub struct Item{i} {{ pub value: u32 }}\n",
+            (i + 1) % 20,
+            (i + 1) % FILES,
+        );
+        fs::write(dir.join(format!("module_{}.rs", i)), source).unwrap();
+    }
+}
+
+/// Mean time of a full analysis from an empty state.
+fn bench(root: &Path, parallel: bool) -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..ROUNDS {
+        let _ = fs::remove_file(root.join(".deeptracking-state.json"));
+        let _ = fs::remove_file(root.join(".deeptracking-state.json.bak"));
+        let mut manager = AnalyzerManager::new(root, None).unwrap();
+
+        let start = Instant::now();
+        let result = if parallel {
+            manager.analyze_project_parallel(root)
+        } else {
+            manager.analyze_project(root)
+        };
+        total += start.elapsed();
+        assert!(!result.unwrap().dependencies.is_empty());
+    }
+    total / ROUNDS
+}
+
+fn main() {
+    let dir = tempfile::tempdir().unwrap();
+    synthetic_project(dir.path());
+
+    let serial = bench(dir.path(), false);
+    let parallel = bench(dir.path(), true);
+    println!("{} files", FILES);
+    println!("serial:   {:.2?}", serial);
+    println!("parallel: {:.2?}", parallel);
+    println!(
+        "speedup:  {:.2}x",
+        serial.as_secs_f64() / parallel.as_secs_f64()
+    );
+}
@@ -0,0 +1,65 @@
+//! md5 vs blake3 on a large file, read in chunks the way
+//! `AnalyzerManager` hashes files for change detection.
+//!
+//! Run with `cargo bench --bench file_hash`.
+
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const FILE_SIZE: usize = 256 * 1024 * 1024;
+const ROUNDS: u32 = 3;
+
+fn hash_md5(path: &Path) -> String {
+    let mut context = md5::Context::new();
+    read_chunks(path, |chunk| context.consume(chunk));
+    format!("{:x}", context.compute())
+}
+
+fn hash_blake3(path: &Path) -> String {
+    let mut hasher = blake3::Hasher::new();
+    read_chunks(path, |chunk| {
+        hasher.update(chunk);
+    });
+    hasher.finalize().to_hex().to_string()
+}
+
+fn read_chunks(path: &Path, mut consume: impl FnMut(&[u8])) {
+    let mut file = File::open(path).unwrap();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).unwrap();
+        if read == 0 {
+            break;
+        }
+        consume(&buffer[..read]);
+    }
+}
+
+/// Mean time to hash `path`, after one warm-up run to fill the page cache.
+fn bench(path: &Path, hash: fn(&Path) -> String) -> Duration {
+    hash(path);
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        assert!(!hash(path).is_empty());
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("large.bin");
+    let mut data = vec![0u8; FILE_SIZE];
+    StdRng::seed_from_u64(7).fill_bytes(&mut data);
+    File::create(&path).unwrap().write_all(&data).unwrap();
+
+    let md5 = bench(&path, hash_md5);
+    let blake3 = bench(&path, hash_blake3);
+    println!("{} MiB file", FILE_SIZE / (1024 * 1024));
+    println!("md5:     {:.2?}", md5);
+    println!("blake3:  {:.2?}", blake3);
+    println!("speedup: {:.2}x", md5.as_secs_f64() / blake3.as_secs_f64());
+}
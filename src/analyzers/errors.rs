@@ -1,3 +1,5 @@
+use std::io;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -9,3 +11,49 @@ pub enum TrackerError {
     #[error("Graph error: {0}")]
     Graph(String),
 }
+
+#[derive(Error, Debug)]
+pub enum AnalyzerError {
+    #[error("Failed to read {}: {source}", .path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to parse {}: {message}", .path.display())]
+    Parse { path: PathBuf, message: String },
+    #[error("File not found: {}", .0.display())]
+    NotFound(PathBuf),
+    #[error("Analyzer lock poisoned: {0}")]
+    LockPoisoned(String),
+    #[error("Analysis cancelled")]
+    Cancelled,
+}
+
+impl AnalyzerError {
+    /// Wraps an IO error on `path`, reporting missing files as `NotFound`.
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        if source.kind() == io::ErrorKind::NotFound {
+            AnalyzerError::NotFound(path.to_path_buf())
+        } else {
+            AnalyzerError::Io {
+                path: path.to_path_buf(),
+                source,
+            }
+        }
+    }
+
+    pub fn parse(path: &Path, message: impl Into<String>) -> Self {
+        AnalyzerError::Parse {
+            path: path.to_path_buf(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Lets code that still returns `Result<_, String>` use `?` on analyzer calls.
+impl From<AnalyzerError> for String {
+    fn from(error: AnalyzerError) -> Self {
+        error.to_string()
+    }
+}
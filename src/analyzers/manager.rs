@@ -1,17 +1,45 @@
-use crate::analyzers::{CodeAnalyzer, Dependency, PythonAnalyzer, RustAnalyzer};
+use crate::analyzers::{
+    deduplicate_dependencies, resolver, AnalyzerError, CodeAnalyzer, Dependency, DependencyType,
+    FunctionComplexity, PythonAnalyzer, RustAnalyzer,
+};
+use crate::progress::{AnalysisProgress, ProgressReporter};
 use chrono::{DateTime, Utc};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 use walkdir::WalkDir;
 
+/// Tool-specific ignore file at the project root, for files git should
+/// track but analysis should skip.
+const IGNORE_FILE: &str = ".deeptrackingignore";
+
+/// Leading bytes checked for NUL when deciding whether a file is binary.
+const BINARY_SNIFF_LEN: u64 = 8192;
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct ProjectState {
     #[serde(default)]
     pub last_analysis: DateTime<Utc>,
     #[serde(default)]
     pub analyzed_files: HashMap<PathBuf, FileState>,
+    #[serde(default)]
+    pub cached_structure: Option<CachedStructure>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedStructure {
+    pub file_set_hash: String,
+    pub structure: ProjectStructure,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,12 +47,52 @@ pub struct FileState {
     pub last_modified: DateTime<Utc>,
     pub dependencies: Vec<Dependency>,
     pub hash: String,
+    /// Algorithm `hash` was computed with. States saved before it was
+    /// recorded used md5.
+    #[serde(default = "legacy_hash_algorithm")]
+    pub hash_algorithm: HashAlgorithm,
+}
+
+/// Content hash used to detect changed files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Md5,
+    Blake3,
+}
+
+fn legacy_hash_algorithm() -> HashAlgorithm {
+    HashAlgorithm::Md5
 }
 
 #[derive(Debug)]
 pub struct AnalysisResult {
     pub dependencies: Vec<Dependency>,
     pub project_structure: ProjectStructure,
+    /// Files whose analysis exceeded `AnalyzerConfig::analysis_timeout`.
+    pub timed_out: Vec<PathBuf>,
+    /// Directories left unexplored because they sit at
+    /// `AnalyzerConfig::max_depth`.
+    pub depth_limited: Vec<PathBuf>,
+}
+
+/// What an analysis pass did with one collected file.
+enum FileOutcome {
+    Unsupported,
+    Unchanged,
+    Analyzed(Vec<Dependency>),
+    TimedOut,
+    /// Too large or binary, see `AnalyzerManager::skip_reason`.
+    Skipped,
+    /// Not looked at because the run was cancelled.
+    Cancelled,
+}
+
+/// Which source files have tests, keyed by project-relative path.
+#[derive(Debug, Default, Serialize)]
+pub struct TestCoverage {
+    pub tested: BTreeMap<PathBuf, Vec<PathBuf>>,
+    pub untested: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,36 +117,179 @@ pub struct FileMetadata {
     pub size: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalyzerConfig {
+    pub ignore_patterns: Vec<String>,
+    /// Follow symlinked directories during the walk. Cycles are broken by
+    /// skipping directories that were already visited.
+    pub follow_symlinks: bool,
+    /// Deepest directory level the walk descends to, `None` for no limit.
+    #[serde(default = "default_max_depth")]
+    pub max_depth: Option<usize>,
+    /// Files larger than this, in bytes, are skipped rather than analyzed.
+    pub max_file_size: u64,
+    /// Deadline for analyzing a single file, `None` to wait indefinitely.
+    /// Each file is analyzed on its own thread, and a thread that misses the
+    /// deadline can't be stopped: it keeps running, and holding its memory,
+    /// until the analyzer returns.
+    #[serde(default = "default_analysis_timeout")]
+    pub analysis_timeout: Option<Duration>,
+    /// Timed out threads allowed to keep running at once. While this many
+    /// are, further files are reported as timed out without being analyzed.
+    #[serde(default = "default_max_stalled_workers")]
+    pub max_stalled_workers: usize,
+}
+
+fn default_analysis_timeout() -> Option<Duration> {
+    Some(Duration::from_secs(30))
+}
+
+fn default_max_stalled_workers() -> usize {
+    4
+}
+
+fn default_max_depth() -> Option<usize> {
+    Some(64)
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            ignore_patterns: vec![
+                "target".to_string(),
+                "node_modules".to_string(),
+                ".git".to_string(),
+                "__pycache__".to_string(),
+                ".deeptracking-state.json".to_string(),
+                ".deeptracking-state.json.tmp".to_string(),
+                ".deeptracking-state.json.bak".to_string(),
+                ".deeptracking-summaries.json".to_string(),
+                ".deeptracking-summaries.json.tmp".to_string(),
+            ],
+            follow_symlinks: false,
+            max_depth: default_max_depth(),
+            max_file_size: 10 * 1024 * 1024,
+            analysis_timeout: default_analysis_timeout(),
+            max_stalled_workers: default_max_stalled_workers(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct AnalyzerManager {
-    analyzers: Vec<Box<dyn CodeAnalyzer>>,
+    analyzers: Vec<Arc<dyn CodeAnalyzer>>,
     project_state: ProjectState,
     state_file: PathBuf,
+    /// Whether the state file on disk is known to parse, and so is worth
+    /// keeping as the backup on the next save.
+    state_file_valid: bool,
+    config: AnalyzerConfig,
+    /// Rules from the root `.gitignore` and `.deeptrackingignore`.
+    ignore_rules: Gitignore,
+    /// Paths the last `analyze_paths` run was limited to, `None` after a
+    /// whole-project analysis.
+    scope: Option<Vec<PathBuf>>,
+    progress: ProgressReporter,
+    cancel: CancellationToken,
+    /// Files and directories this tool writes into the project, such as
+    /// reports. They are left out of the file-set hash so writing them keeps the cached
+    /// structure valid.
+    output_paths: HashSet<PathBuf>,
+    /// Analysis threads still running after missing their deadline.
+    stalled_workers: Arc<AtomicUsize>,
 }
 
 impl AnalyzerManager {
-    pub fn new(project_root: &Path) -> Result<Self, String> {
+    pub fn new(project_root: &Path, config: Option<AnalyzerConfig>) -> Result<Self, String> {
         let state_file = project_root.join(".deeptracking-state.json");
-        let project_state = if state_file.exists() {
-            serde_json::from_reader(fs::File::open(&state_file).map_err(|e| e.to_string())?)
-                .unwrap_or_default()
-        } else {
-            ProjectState {
-                last_analysis: Utc::now(),
-                analyzed_files: HashMap::new(),
-            }
-        };
+        let (project_state, state_file_valid) = Self::load_state(&state_file);
+        let project_state = project_state.unwrap_or_else(|| ProjectState {
+            last_analysis: Utc::now(),
+            analyzed_files: HashMap::new(),
+            cached_structure: None,
+        });
+        let ignore_rules = Self::load_ignore_rules(project_root)?;
 
         Ok(Self {
             analyzers: vec![
-                Box::new(RustAnalyzer::new()),
-                Box::new(PythonAnalyzer::new()),
+                Arc::new(RustAnalyzer::new()),
+                Arc::new(PythonAnalyzer::new().with_project_root(project_root)),
             ],
             project_state,
             state_file,
+            state_file_valid,
+            config: config.unwrap_or_default(),
+            ignore_rules,
+            scope: None,
+            progress: ProgressReporter::default(),
+            cancel: CancellationToken::new(),
+            output_paths: HashSet::new(),
+            stalled_workers: Arc::new(AtomicUsize::new(0)),
         })
     }
 
+    /// Once `cancel` is cancelled, runs stop before the next file, save the
+    /// state of the files analyzed so far and return
+    /// `AnalyzerError::Cancelled`.
+    pub fn set_cancellation(&mut self, cancel: CancellationToken) {
+        self.cancel = cancel;
+    }
+
+    /// Reports walking and analysis progress of every later run to
+    /// `progress`.
+    pub fn set_progress(&mut self, progress: ProgressReporter) {
+        self.progress = progress;
+    }
+
+    /// Marks `path`, a file or a directory of files, as written by this
+    /// tool, so creating or rewriting it doesn't invalidate the cached
+    /// structure. It need not exist yet.
+    pub fn add_output_path(&mut self, path: &Path) {
+        self.output_paths.insert(Self::output_key(path));
+    }
+
+    /// `path` with its directory resolved, so relative and absolute spellings
+    /// of an output compare equal.
+    fn output_key(path: &Path) -> PathBuf {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        match (fs::canonicalize(parent), path.file_name()) {
+            (Ok(parent), Some(name)) => parent.join(name),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    fn is_output(&self, path: &Path) -> bool {
+        if self.output_paths.is_empty() {
+            return false;
+        }
+        let key = Self::output_key(path);
+        self.output_paths
+            .iter()
+            .any(|output| key.starts_with(output))
+    }
+
+    /// Reads `.gitignore` then `.deeptrackingignore` from the project root.
+    /// Later rules win, so `.deeptrackingignore` can both add exclusions and
+    /// re-include gitignored files with `!`.
+    fn load_ignore_rules(project_root: &Path) -> Result<Gitignore, String> {
+        let mut builder = GitignoreBuilder::new(project_root);
+        for name in [".gitignore", IGNORE_FILE] {
+            let path = project_root.join(name);
+            if !path.is_file() {
+                continue;
+            }
+            if let Some(e) = builder.add(&path) {
+                return Err(format!("Invalid ignore file {}: {}", path.display(), e));
+            }
+        }
+        builder
+            .build()
+            .map_err(|e| format!("Invalid ignore rules in {}: {}", project_root.display(), e))
+    }
+
     pub fn root_path(&self) -> PathBuf {
         self.state_file
             .parent()
@@ -87,76 +298,618 @@ impl AnalyzerManager {
     }
 
     pub fn analyze_project(&mut self, root_path: &Path) -> Result<AnalysisResult, String> {
+        self.scope = None;
+        self.run_analysis(root_path, false)
+    }
+
+    /// Like `analyze_project`, but changed files are analyzed on the rayon
+    /// pool. Results are merged in file order, so the outcome and the saved
+    /// state match a serial run.
+    pub fn analyze_project_parallel(&mut self, root_path: &Path) -> Result<AnalysisResult, String> {
+        self.scope = None;
+        self.run_analysis(root_path, true)
+    }
+
+    /// Analyzes only the given files and directories, expanding directories
+    /// with the usual ignore rules. Relative paths are taken from the project
+    /// root. Files outside the scope keep their saved state, and
+    /// `dependencies` reports only the scoped files until the next
+    /// whole-project analysis. Dependencies on files outside the scope are
+    /// still recorded as targets.
+    pub fn analyze_paths(&mut self, paths: &[PathBuf]) -> Result<AnalysisResult, String> {
+        let root_path = self.root_path();
+        let scope = self.resolve_scope(paths)?;
+
+        self.scope = Some(scope);
+        self.run_analysis(&root_path, false)
+    }
+
+    /// Project paths for `paths`, rejecting any that don't exist or that
+    /// leave the project root once `..` and symlinks are resolved.
+    fn resolve_scope(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+        let root_path = self.root_path();
+        let canonical_root = fs::canonicalize(&root_path)
+            .map_err(|e| format!("Cannot resolve {}: {}", root_path.display(), e))?;
+        paths
+            .iter()
+            .map(|path| {
+                let joined = root_path.join(path);
+                // `..` and symlinks only show up in the canonical form
+                let canonical = fs::canonicalize(&joined)
+                    .map_err(|e| format!("{} does not exist: {}", joined.display(), e))?;
+                let relative = canonical.strip_prefix(&canonical_root).map_err(|_| {
+                    format!(
+                        "{} is outside the project root {}",
+                        joined.display(),
+                        root_path.display()
+                    )
+                })?;
+                Ok(root_path.join(relative))
+            })
+            .collect()
+    }
+
+    fn in_scope(&self, path: &Path) -> bool {
+        match &self.scope {
+            Some(scope) => scope.iter().any(|root| path.starts_with(root)),
+            None => true,
+        }
+    }
+
+    fn run_analysis(&mut self, root_path: &Path, parallel: bool) -> Result<AnalysisResult, String> {
         let mut all_dependencies = Vec::new();
         let mut current_files = HashSet::new();
         let mut entries_by_path: HashMap<PathBuf, FileEntry> = HashMap::new();
+        let mut timed_out = Vec::new();
+        let mut depth_limited = Vec::new();
+        let mut cancelled = false;
 
         // First collect all files that need analysis
-        let files_to_analyze: Vec<_> = WalkDir::new(root_path)
-            .into_iter()
-            .filter_entry(|e| !self.is_ignored(e.path()))
-            .filter_map(|e| e.ok())
-            .filter(|entry| entry.path().is_file())
-            .map(|entry| entry.path().to_path_buf())
-            .collect();
+        let files_to_analyze = match &self.scope {
+            Some(scope) => self.collect_scoped_files(scope, &mut depth_limited),
+            None => self.collect_files(root_path, &mut depth_limited),
+        };
+
+        // The cached tree stays valid as long as no file was added, removed or
+        // modified. It always describes the whole project, so scoped runs
+        // neither use nor replace it.
+        let file_set_hash = match self.scope {
+            Some(_) => None,
+            None => Some(self.calculate_file_set_hash(root_path, &files_to_analyze)?),
+        };
+        let structure_is_cached = match (&self.project_state.cached_structure, &file_set_hash) {
+            (Some(cached), Some(hash)) => &cached.file_set_hash == hash,
+            _ => false,
+        };
 
-        // Now process each file
-        for path in files_to_analyze {
+        // Analysis only reads the state, so it can run on many files at once
+        let outcomes: Vec<FileOutcome> = if parallel {
+            files_to_analyze
+                .par_iter()
+                .map(|path| self.analyze_if_changed(path))
+                .collect::<Result<_, _>>()?
+        } else {
+            files_to_analyze
+                .iter()
+                .map(|path| self.analyze_if_changed(path))
+                .collect::<Result<_, _>>()?
+        };
+
+        // Now record each file's results
+        for (path, outcome) in files_to_analyze.into_iter().zip(outcomes) {
             current_files.insert(path.clone());
             let relative_path = path
                 .strip_prefix(root_path)
                 .map_err(|e| e.to_string())?
                 .to_path_buf();
 
-            if let Some(analyzer) = self.get_analyzer_for_file(&path) {
-                // Analyze file if needed
-                if self.needs_analysis(&path)? {
-                    let deps = analyzer.analyze(&path)?;
-                    self.update_file_state(&path, &deps)?;
-                    all_dependencies.extend(deps.clone());
-
-                    // Create file entry with metadata
-                    if let Ok(metadata) = fs::metadata(&path) {
-                        entries_by_path.insert(
-                            relative_path.clone(),
-                            FileEntry {
-                                path: relative_path,
-                                file_type: path
-                                    .extension()
-                                    .and_then(|ext| ext.to_str())
-                                    .map(|ext| ext.to_string())
-                                    .unwrap_or_else(|| "unknown".to_string()),
-                                children: Vec::new(),
-                                metadata: Some(FileMetadata {
-                                    last_modified: DateTime::from(metadata.modified().unwrap()),
-                                    language: Some(self.determine_language(&path)),
-                                    dependencies: deps
-                                        .iter()
-                                        .map(|d| d.target.to_string_lossy().to_string())
-                                        .collect(),
-                                    size: metadata.len(),
-                                }),
-                            },
-                        );
-                    }
+            match outcome {
+                FileOutcome::Unsupported => continue,
+                FileOutcome::Skipped => {
+                    self.project_state.analyzed_files.remove(&path);
+                    continue;
+                }
+                FileOutcome::Unchanged => {}
+                FileOutcome::Analyzed(deps) => {
+                    let deps = self.update_file_state(&path, deps)?;
+                    all_dependencies.extend(deps);
+                }
+                FileOutcome::TimedOut => timed_out.push(path.clone()),
+                // Left with its previous state, so the next run picks it up
+                FileOutcome::Cancelled => {
+                    cancelled = true;
+                    continue;
+                }
+            }
+
+            // Create file entry with metadata
+            if !structure_is_cached {
+                if let Some(entry) = self.create_file_entry(&path, &relative_path) {
+                    entries_by_path.insert(relative_path, entry);
                 }
             }
         }
 
-        // Update state and clean up deleted files
-        self.project_state
-            .analyzed_files
-            .retain(|path, _| current_files.contains(path));
+        // Update state and clean up deleted files, leaving files outside the
+        // scope as they were
+        let scope = &self.scope;
+        self.project_state.analyzed_files.retain(|path, _| {
+            current_files.contains(path)
+                || scope.as_ref().map_or(false, |scope| {
+                    !scope.iter().any(|root| path.starts_with(root))
+                })
+        });
+
+        if cancelled {
+            self.save_state()?;
+            return Err(AnalyzerError::Cancelled.into());
+        }
+
+        let project_structure = match &self.project_state.cached_structure {
+            Some(cached) if structure_is_cached => cached.structure.clone(),
+            _ => {
+                add_directory_entries(&mut entries_by_path);
+                let structure = ProjectStructure {
+                    root: root_path.to_string_lossy().into_owned(),
+                    files: self.build_directory_tree(PathBuf::new(), &entries_by_path)?,
+                };
+                if let Some(file_set_hash) = file_set_hash {
+                    self.project_state.cached_structure = Some(CachedStructure {
+                        file_set_hash,
+                        structure: structure.clone(),
+                    });
+                }
+                structure
+            }
+        };
+
+        // Persist so the next run only re-analyzes files that changed
+        self.save_state()?;
+        self.progress.report(AnalysisProgress::Done);
 
         Ok(AnalysisResult {
             dependencies: all_dependencies,
-            project_structure: ProjectStructure {
-                root: root_path.to_string_lossy().into_owned(),
-                files: self.build_directory_tree(PathBuf::new(), &entries_by_path)?,
-            },
+            project_structure,
+            timed_out,
+            depth_limited,
+        })
+    }
+
+    /// Dependencies of every analyzed file, including ones that were
+    /// unchanged since the last run.
+    pub fn dependencies(&self) -> Vec<Dependency> {
+        self.project_state
+            .analyzed_files
+            .iter()
+            .filter(|(path, _)| self.in_scope(path))
+            .map(|(_, state)| state)
+            .flat_map(|state| state.dependencies.iter().cloned())
+            .collect()
+    }
+
+    /// Like `dependencies`, but with imports that resolve to a project file
+    /// pointing at that file so the graph links files to each other.
+    pub fn resolved_dependencies(&self) -> Vec<Dependency> {
+        let root_path = self.root_path();
+        self.dependencies()
+            .into_iter()
+            .map(|mut dep| {
+                if let Some(file) = resolver::resolve_import(&root_path, &dep) {
+                    dep.target = root_path.join(file);
+                }
+                dep
+            })
+            .collect()
+    }
+
+    pub fn project_state(&self) -> &ProjectState {
+        &self.project_state
+    }
+
+    pub fn analyzed_files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.project_state.analyzed_files.keys()
+    }
+
+    /// Drops state entries for files that no longer exist on disk and
+    /// persists the result, returning the removed paths.
+    pub fn prune_missing_files(&mut self) -> Result<Vec<PathBuf>, String> {
+        let missing: Vec<PathBuf> = self
+            .project_state
+            .analyzed_files
+            .keys()
+            .filter(|path| !path.exists())
+            .cloned()
+            .collect();
+
+        for path in &missing {
+            self.project_state.analyzed_files.remove(path);
+        }
+        if !missing.is_empty() {
+            self.save_state()?;
+        }
+
+        Ok(missing)
+    }
+
+    /// Maps analyzed source files to the tests that exercise them. A test
+    /// covers a source when it imports it or shares its name (`test_foo.py`
+    /// and `tests/foo.rs` both cover `foo`); Rust files with a `#[cfg(test)]`
+    /// module cover themselves.
+    pub fn test_coverage(&self) -> TestCoverage {
+        let root_path = self.root_path();
+        let relative = |path: &Path| path.strip_prefix(&root_path).unwrap_or(path).to_path_buf();
+        let crate_name = rust_crate_name(&root_path);
+
+        let (tests, sources): (Vec<&PathBuf>, Vec<&PathBuf>) = self
+            .project_state
+            .analyzed_files
+            .keys()
+            .partition(|path| is_test_file(&relative(path)));
+
+        let mut tested: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+        let mut cover = |source: PathBuf, test: PathBuf| {
+            let tests = tested.entry(source).or_default();
+            if !tests.contains(&test) {
+                tests.push(test);
+            }
+        };
+
+        for source in &sources {
+            let is_rust = source.extension().and_then(|ext| ext.to_str()) == Some("rs");
+            if is_rust
+                && fs::read_to_string(source)
+                    .map(|content| content.contains("#[cfg(test)]"))
+                    .unwrap_or(false)
+            {
+                cover(relative(source), relative(source));
+            }
+        }
+
+        for test in &tests {
+            let test_relative = relative(test);
+
+            if let Some(name) = tested_module_name(&test_relative) {
+                for source in &sources {
+                    let stem = match source.file_stem().and_then(|stem| stem.to_str()) {
+                        Some("mod") | Some("__init__") => source
+                            .parent()
+                            .and_then(|dir| dir.file_name())
+                            .and_then(|name| name.to_str()),
+                        stem => stem,
+                    };
+                    if stem == Some(name.as_str()) && source.extension() == test.extension() {
+                        cover(relative(source), test_relative.clone());
+                    }
+                }
+            }
+
+            let dependencies = match self.project_state.analyzed_files.get(*test) {
+                Some(state) => &state.dependencies,
+                None => continue,
+            };
+            for dep in dependencies {
+                let mut dep = dep.clone();
+                // Integration tests import the crate by name; resolve from its root
+                // instead. Paths into std, other crates or `super` stay as they are.
+                if dep.source.extension().and_then(|ext| ext.to_str()) == Some("rs") {
+                    let target = dep.target.to_string_lossy().into_owned();
+                    if let Some((first, rest)) = target.split_once("::") {
+                        if Some(first) == crate_name.as_deref() {
+                            dep.target = PathBuf::from(format!("crate::{}", rest));
+                            dep.source = root_path.join("src").join("lib.rs");
+                        }
+                    }
+                }
+                if let Some(source) = resolver::resolve_import(&root_path, &dep) {
+                    if !is_test_file(&source) {
+                        cover(source, test_relative.clone());
+                    }
+                }
+            }
+        }
+
+        let mut untested: Vec<PathBuf> = sources
+            .iter()
+            .map(|source| relative(source))
+            .filter(|source| !tested.contains_key(source))
+            .collect();
+        untested.sort();
+
+        TestCoverage { tested, untested }
+    }
+
+    /// Computes the cyclomatic complexity of every function in the project,
+    /// most complex first.
+    pub fn analyze_complexity(&self, root_path: &Path) -> Result<Vec<FunctionComplexity>, String> {
+        self.complexity_of(self.collect_files(root_path, &mut Vec::new()))
+    }
+
+    /// Like `analyze_complexity`, limited to the given files and directories
+    /// as in `analyze_paths`.
+    pub fn analyze_paths_complexity(
+        &self,
+        paths: &[PathBuf],
+    ) -> Result<Vec<FunctionComplexity>, String> {
+        let scope = self.resolve_scope(paths)?;
+        self.complexity_of(self.collect_scoped_files(&scope, &mut Vec::new()))
+    }
+
+    fn complexity_of(&self, files: Vec<PathBuf>) -> Result<Vec<FunctionComplexity>, String> {
+        let mut complexities = Vec::new();
+        for path in files {
+            if let Some(analyzer) = self.get_analyzer_for_file(&path) {
+                if self.skip_reason(&path).is_some() {
+                    continue;
+                }
+                complexities.extend(analyzer.analyze_complexity(&path)?);
+            }
+        }
+
+        complexities.sort_by(|a, b| b.complexity.cmp(&a.complexity));
+        Ok(complexities)
+    }
+
+    /// Cyclomatic complexity of the functions in a single file.
+    pub fn file_complexity(&self, path: &Path) -> Result<Vec<FunctionComplexity>, String> {
+        match self.get_analyzer_for_file(path) {
+            Some(analyzer) => Ok(analyzer.analyze_complexity(path)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Reanalyzes a single file and updates the project state, returning its
+    /// fresh dependencies so callers can patch their graph. A deleted file is
+    /// dropped from the state and yields no dependencies.
+    pub fn analyze_file(&mut self, path: &Path) -> Result<Vec<Dependency>, String> {
+        if !path.exists() {
+            self.project_state.analyzed_files.remove(path);
+            return Ok(Vec::new());
+        }
+
+        let analyzer = self
+            .get_analyzer_for_file(path)
+            .ok_or_else(|| format!("No analyzer available for {}", path.display()))?;
+        if let Some(reason) = self.skip_reason(path) {
+            log::warn!("Skipping {}: {}", path.display(), reason);
+            self.project_state.analyzed_files.remove(path);
+            return Ok(Vec::new());
+        }
+        let deps = self.analyze_with_timeout(analyzer, path)?.ok_or_else(|| {
+            format!(
+                "Analysis of {} timed out after {:?}",
+                path.display(),
+                self.config.analysis_timeout.unwrap_or_default()
+            )
+        })?;
+        self.update_file_state(path, deps)
+    }
+
+    /// Analyzes `path` unless the run was cancelled, the file is unsupported
+    /// or skipped, or it is unchanged since the last run.
+    fn analyze_if_changed(&self, path: &Path) -> Result<FileOutcome, String> {
+        if self.cancel.is_cancelled() {
+            return Ok(FileOutcome::Cancelled);
+        }
+        let analyzer = match self.get_analyzer_for_file(path) {
+            Some(analyzer) => analyzer,
+            None => return Ok(FileOutcome::Unsupported),
+        };
+        // Checked before hashing, so an oversized file is never read in full
+        if let Some(reason) = self.skip_reason(path) {
+            log::warn!("Skipping {}: {}", path.display(), reason);
+            return Ok(FileOutcome::Skipped);
+        }
+        if !self.needs_analysis(path)? {
+            return Ok(FileOutcome::Unchanged);
+        }
+
+        self.progress.report(AnalysisProgress::Analyzing {
+            path: path.to_path_buf(),
+        });
+        Ok(match self.analyze_with_timeout(analyzer, path)? {
+            Some(deps) => FileOutcome::Analyzed(deps),
+            None => FileOutcome::TimedOut,
         })
     }
 
+    /// Runs the analyzer on a worker thread so a pathological file can't stall
+    /// the run, returning `None` when it misses the deadline. A timed out
+    /// worker can't be cancelled and is left to finish in the background, so
+    /// once `max_stalled_workers` are, files are not started at all.
+    fn analyze_with_timeout(
+        &self,
+        analyzer: Arc<dyn CodeAnalyzer>,
+        path: &Path,
+    ) -> Result<Option<Vec<Dependency>>, String> {
+        let timeout = match self.config.analysis_timeout {
+            Some(timeout) => timeout,
+            None => return Ok(Some(analyzer.analyze(path)?)),
+        };
+
+        if self.stalled_workers.load(Ordering::SeqCst) >= self.config.max_stalled_workers {
+            log::warn!(
+                "Not analyzing {}: {} timed out analyses are still running",
+                path.display(),
+                self.config.max_stalled_workers
+            );
+            return Ok(None);
+        }
+
+        // Set by whichever of the worker finishing and the deadline passing
+        // comes first, so the worker knows whether it was counted as stalled
+        let settled = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        let worker_path = path.to_path_buf();
+        let worker_settled = settled.clone();
+        let stalled_workers = self.stalled_workers.clone();
+        thread::spawn(move || {
+            // A panic still settles, and drops `tx` to report it
+            let result = panic::catch_unwind(AssertUnwindSafe(|| analyzer.analyze(&worker_path)));
+            if let Ok(result) = result {
+                let _ = tx.send(result);
+            }
+            if worker_settled.swap(true, Ordering::SeqCst) {
+                stalled_workers.fetch_sub(1, Ordering::SeqCst);
+            }
+        });
+
+        match rx.recv_timeout(timeout) {
+            Ok(result) => Ok(Some(result?)),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                // Counted before settling, so the worker never decrements first
+                self.stalled_workers.fetch_add(1, Ordering::SeqCst);
+                if settled.swap(true, Ordering::SeqCst) {
+                    self.stalled_workers.fetch_sub(1, Ordering::SeqCst);
+                }
+                Ok(None)
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err(format!("Analyzer panicked on {}", path.display()))
+            }
+        }
+    }
+
+    /// Files under each scoped path, without duplicates when paths overlap.
+    fn collect_scoped_files(
+        &self,
+        scope: &[PathBuf],
+        depth_limited: &mut Vec<PathBuf>,
+    ) -> Vec<PathBuf> {
+        let mut seen = HashSet::new();
+        let mut files = Vec::new();
+        for path in scope {
+            for file in self.collect_files(path, depth_limited) {
+                if seen.insert(file.clone()) {
+                    files.push(file);
+                }
+            }
+        }
+        files
+    }
+
+    /// Walks `root_path` for files to analyze. Directories at the depth limit
+    /// are not entered and are added to `depth_limited`; directories reached
+    /// a second time through symlinks are skipped.
+    fn collect_files(&self, root_path: &Path, depth_limited: &mut Vec<PathBuf>) -> Vec<PathBuf> {
+        let mut walker = WalkDir::new(root_path).follow_links(self.config.follow_symlinks);
+        if let Some(max_depth) = self.config.max_depth {
+            walker = walker.max_depth(max_depth);
+        }
+
+        let mut visited_dirs = HashSet::new();
+        let mut files = Vec::new();
+        let walk = walker.into_iter().filter_entry(|e| {
+            if self.is_ignored(e.path()) {
+                return false;
+            }
+            if e.file_type().is_dir() {
+                let real_path =
+                    fs::canonicalize(e.path()).unwrap_or_else(|_| e.path().to_path_buf());
+                return visited_dirs.insert(real_path);
+            }
+            true
+        });
+
+        for entry in walk {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    if e.loop_ancestor().is_some() {
+                        log::warn!("Skipping symlink loop: {}", e);
+                    }
+                    continue;
+                }
+            };
+
+            if entry.file_type().is_dir() {
+                if Some(entry.depth()) == self.config.max_depth {
+                    log::warn!(
+                        "Not descending into {}: max depth {} reached",
+                        entry.path().display(),
+                        entry.depth()
+                    );
+                    depth_limited.push(entry.path().to_path_buf());
+                }
+                continue;
+            }
+
+            if entry.path().is_file() {
+                files.push(entry.path().to_path_buf());
+                self.progress
+                    .report(AnalysisProgress::Walking { count: files.len() });
+            }
+        }
+        files
+    }
+
+    pub fn create_file_entry(&self, path: &Path, relative_path: &Path) -> Option<FileEntry> {
+        let metadata = fs::metadata(path).ok()?;
+        let root_path = self.root_path();
+        let mut dependencies: Vec<String> = Vec::new();
+        if let Some(state) = self.project_state.analyzed_files.get(path) {
+            for dep in &state.dependencies {
+                if dep.dependency_type != DependencyType::Import {
+                    continue;
+                }
+
+                let dependency = match resolver::resolve_import(&root_path, dep) {
+                    Some(file) => file.to_string_lossy().into_owned(),
+                    None => format!("unresolved:{}", dep.target.to_string_lossy()),
+                };
+                if !dependencies.contains(&dependency) {
+                    dependencies.push(dependency);
+                }
+            }
+        }
+
+        Some(FileEntry {
+            path: relative_path.to_path_buf(),
+            file_type: path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| ext.to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+            children: Vec::new(),
+            metadata: Some(FileMetadata {
+                last_modified: DateTime::from(metadata.modified().ok()?),
+                language: Some(self.determine_language(path)),
+                dependencies,
+                size: metadata.len(),
+            }),
+        })
+    }
+
+    fn calculate_file_set_hash(
+        &self,
+        root_path: &Path,
+        files: &[PathBuf],
+    ) -> Result<String, String> {
+        let mut entries = Vec::with_capacity(files.len());
+        for path in files {
+            if self.is_output(path) {
+                continue;
+            }
+            let metadata = fs::metadata(path).map_err(|e| e.to_string())?;
+            let modified: DateTime<Utc> =
+                DateTime::from(metadata.modified().map_err(|e| e.to_string())?);
+            let relative_path = path.strip_prefix(root_path).unwrap_or(path);
+            entries.push(format!(
+                "{}:{}:{}",
+                relative_path.to_string_lossy(),
+                modified.timestamp_nanos_opt().unwrap_or_default(),
+                metadata.len()
+            ));
+        }
+        entries.sort();
+
+        let mut context = md5::Context::new();
+        for entry in &entries {
+            context.consume(entry.as_bytes());
+            context.consume(b"\n");
+        }
+        Ok(format!("{:x}", context.compute()))
+    }
+
     fn build_directory_tree(
         &self,
         current_path: PathBuf,
@@ -194,6 +947,10 @@ impl AnalyzerManager {
         let modified: DateTime<Utc> = DateTime::from(modified);
 
         if let Some(state) = self.project_state.analyzed_files.get(path) {
+            // A hash from another algorithm can't be compared
+            if state.hash_algorithm != HashAlgorithm::Blake3 {
+                return Ok(true);
+            }
             let file_hash = self.calculate_file_hash(path)?;
             Ok(modified > state.last_modified || file_hash != state.hash)
         } else {
@@ -201,7 +958,14 @@ impl AnalyzerManager {
         }
     }
 
-    fn update_file_state(&mut self, path: &Path, deps: &[Dependency]) -> Result<(), String> {
+    /// Records the file's dependencies, collapsing duplicates first, and
+    /// returns the deduplicated list.
+    fn update_file_state(
+        &mut self,
+        path: &Path,
+        deps: Vec<Dependency>,
+    ) -> Result<Vec<Dependency>, String> {
+        let deps = deduplicate_dependencies(deps);
         let metadata = fs::metadata(path).map_err(|e| e.to_string())?;
         let modified = metadata.modified().map_err(|e| e.to_string())?;
         let modified: DateTime<Utc> = DateTime::from(modified);
@@ -210,24 +974,56 @@ impl AnalyzerManager {
             path.to_path_buf(),
             FileState {
                 last_modified: modified,
-                dependencies: deps.to_vec(),
+                dependencies: deps.clone(),
                 hash: self.calculate_file_hash(path)?,
+                hash_algorithm: HashAlgorithm::Blake3,
             },
         );
-        Ok(())
+        Ok(deps)
     }
 
     fn calculate_file_hash(&self, path: &Path) -> Result<String, String> {
-        let content = fs::read(path).map_err(|e| e.to_string())?;
-        Ok(format!("{:x}", md5::compute(&content)))
+        let mut file = fs::File::open(path).map_err(|e| e.to_string())?;
+        let mut hasher = blake3::Hasher::new();
+        let mut buffer = [0u8; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer).map_err(|e| e.to_string())?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(hasher.finalize().to_hex().to_string())
     }
 
-    fn get_analyzer_for_file(&self, path: &Path) -> Option<&dyn CodeAnalyzer> {
+    /// Why `path` shouldn't be handed to an analyzer: it is over
+    /// `max_file_size`, or a NUL byte near the start suggests binary content.
+    /// Only the metadata and the first few kilobytes are read.
+    fn skip_reason(&self, path: &Path) -> Option<String> {
+        let size = fs::metadata(path).ok()?.len();
+        if size > self.config.max_file_size {
+            return Some(format!(
+                "{} bytes exceeds the {} byte limit",
+                size, self.config.max_file_size
+            ));
+        }
+
+        let mut head = Vec::new();
+        fs::File::open(path)
+            .ok()?
+            .take(BINARY_SNIFF_LEN)
+            .read_to_end(&mut head)
+            .ok()?;
+        head.contains(&0)
+            .then(|| "looks like a binary file".to_string())
+    }
+
+    fn get_analyzer_for_file(&self, path: &Path) -> Option<Arc<dyn CodeAnalyzer>> {
         let extension = path.extension()?.to_str()?;
         self.analyzers
             .iter()
             .find(|analyzer| analyzer.supported_extensions().contains(&extension))
-            .map(|analyzer| analyzer.as_ref())
+            .cloned()
     }
 
     fn determine_language(&self, path: &Path) -> String {
@@ -242,30 +1038,768 @@ impl AnalyzerManager {
             .to_string()
     }
 
-    fn is_ignored(&self, path: &Path) -> bool {
-        let ignored_patterns = [
-            "target",
-            "node_modules",
-            ".git",
-            "__pycache__",
-            ".deeptracking-state.json",
-        ];
-
-        path.components().any(|c| {
+    /// Built-in patterns match any path component and always apply; ignore
+    /// file rules only apply to paths under the project root.
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let built_in = path.components().any(|c| {
             if let Some(s) = c.as_os_str().to_str() {
-                ignored_patterns.contains(&s)
+                self.config.ignore_patterns.iter().any(|p| p == s)
             } else {
                 false
             }
-        })
+        });
+
+        built_in
+            || (path.starts_with(self.ignore_rules.path())
+                && self
+                    .ignore_rules
+                    .matched_path_or_any_parents(path, path.is_dir())
+                    .is_ignore())
+    }
+
+    /// Loads the persisted state, falling back to the backup of the last good
+    /// state when the main file is missing or corrupted. The flag tells
+    /// whether the main file itself was readable.
+    fn load_state(state_file: &Path) -> (Option<ProjectState>, bool) {
+        let read_state = |path: &Path| -> Option<Result<ProjectState, serde_json::Error>> {
+            let file = fs::File::open(path).ok()?;
+            Some(serde_json::from_reader(BufReader::new(file)))
+        };
+
+        match read_state(state_file) {
+            Some(Ok(state)) => return (Some(state), true),
+            Some(Err(e)) => log::warn!(
+                "{} is corrupted ({}), trying the backup",
+                state_file.display(),
+                e
+            ),
+            None => {}
+        }
+
+        let backup_file = Self::backup_file(state_file);
+        match read_state(&backup_file) {
+            Some(Ok(state)) => (Some(state), false),
+            Some(Err(e)) => {
+                log::warn!(
+                    "{} is corrupted too ({}), every file will be analyzed again",
+                    backup_file.display(),
+                    e
+                );
+                (None, false)
+            }
+            None => (None, false),
+        }
+    }
+
+    fn backup_file(state_file: &Path) -> PathBuf {
+        state_file.with_extension("json.bak")
     }
 
     fn save_state(&mut self) -> Result<(), String> {
         self.project_state.last_analysis = Utc::now();
-        serde_json::to_writer_pretty(
-            fs::File::create(&self.state_file).map_err(|e| e.to_string())?,
-            &self.project_state,
-        )
-        .map_err(|e| e.to_string())
+
+        let state_error = |e: std::io::Error| {
+            let hint = if e.kind() == std::io::ErrorKind::PermissionDenied {
+                " (is the directory read-only?)"
+            } else {
+                ""
+            };
+            format!(
+                "Failed to save analysis state to {}: {}{}",
+                self.state_file.display(),
+                e,
+                hint
+            )
+        };
+
+        // Write to a temp file first so an interrupted write never truncates the state
+        let tmp_file = self.state_file.with_extension("json.tmp");
+        let write_tmp = || -> std::io::Result<()> {
+            let file = fs::File::create(&tmp_file)?;
+            let mut writer = BufWriter::new(file);
+            serde_json::to_writer_pretty(&mut writer, &self.project_state)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()
+        };
+        if let Err(e) = write_tmp() {
+            let _ = fs::remove_file(&tmp_file);
+            return Err(state_error(e));
+        }
+
+        // Keep the previous good state around for recovery. A corrupted
+        // state file is replaced without overwriting the backup it fell
+        // back to.
+        if self.state_file_valid && self.state_file.exists() {
+            fs::rename(&self.state_file, Self::backup_file(&self.state_file))
+                .map_err(state_error)?;
+        }
+
+        fs::rename(&tmp_file, &self.state_file).map_err(state_error)?;
+        self.state_file_valid = true;
+        Ok(())
+    }
+}
+
+/// Adds a directory entry for every ancestor of the file entries so
+/// `build_directory_tree` can nest files under their folders.
+fn add_directory_entries(entries: &mut HashMap<PathBuf, FileEntry>) {
+    let directories: HashSet<PathBuf> = entries
+        .keys()
+        .flat_map(|path| path.ancestors().skip(1))
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .collect();
+
+    for dir in directories {
+        entries.entry(dir.clone()).or_insert_with(|| FileEntry {
+            path: dir,
+            file_type: "directory".to_string(),
+            children: Vec::new(),
+            metadata: None,
+        });
+    }
+}
+
+fn is_test_file(relative_path: &Path) -> bool {
+    if relative_path
+        .components()
+        .any(|component| component.as_os_str() == "tests")
+    {
+        return true;
+    }
+
+    let stem = relative_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("");
+    stem.starts_with("test_") || stem.ends_with("_test") || stem.ends_with("_tests")
+}
+
+/// Name the project's crate goes by in paths: the `[lib]` name from
+/// Cargo.toml, else the package name with dashes as underscores.
+fn rust_crate_name(root_path: &Path) -> Option<String> {
+    let manifest = fs::read_to_string(root_path.join("Cargo.toml")).ok()?;
+    let mut section = "";
+    let mut package_name = None;
+    for line in manifest.lines().map(str::trim) {
+        if line.starts_with('[') {
+            section = line;
+            continue;
+        }
+        let value = line
+            .strip_prefix("name")
+            .map(str::trim_start)
+            .and_then(|rest| rest.strip_prefix('='))
+            .map(|value| value.trim().trim_matches('"').replace('-', "_"));
+        match (section, value) {
+            ("[lib]", Some(name)) => return Some(name),
+            ("[package]", Some(name)) => package_name = Some(name),
+            _ => {}
+        }
+    }
+    package_name
+}
+
+/// Name of the module a test file targets by convention, if any.
+fn tested_module_name(relative_path: &Path) -> Option<String> {
+    let stem = relative_path.file_stem()?.to_str()?;
+    let name = stem
+        .strip_prefix("test_")
+        .or_else(|| stem.strip_suffix("_tests"))
+        .or_else(|| stem.strip_suffix("_test"))
+        .unwrap_or(stem);
+
+    match name {
+        "" | "mod" | "__init__" | "conftest" => None,
+        name => Some(name.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A temporary project containing `files`, given as (relative path,
+    /// content) pairs.
+    fn project(files: &[(&str, &str)]) -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn analyze_paths_rejects_paths_outside_the_root() {
+        let dir = project(&[
+            ("outside.rs", "fn main() {}\n"),
+            ("project/src/lib.rs", "pub fn helper() {}\n"),
+        ]);
+        let root = dir.path().join("project");
+        let mut manager = AnalyzerManager::new(&root, None).unwrap();
+
+        for path in ["../outside.rs", "src/../../outside.rs"] {
+            let err = manager.analyze_paths(&[PathBuf::from(path)]).unwrap_err();
+            assert!(
+                err.contains("outside the project root"),
+                "{}: {}",
+                path,
+                err
+            );
+        }
+        assert!(manager
+            .analyze_paths(&[PathBuf::from("src/../src/lib.rs")])
+            .is_ok());
+    }
+
+    #[test]
+    fn test_coverage_ignores_other_crates_paths() {
+        let dir = project(&[
+            (
+                "Cargo.toml",
+                "[package]\nname = \"demo-app\"\nversion = \"0.1.0\"\n",
+            ),
+            ("src/lib.rs", "pub mod parser;\npub mod render;\n"),
+            ("src/parser.rs", "pub fn parse() {}\n"),
+            ("src/render.rs", "pub fn draw() {}\n"),
+            (
+                "tests/integration.rs",
+                "use demo_app::parser::parse;\nuse other::render::draw;\nuse std::render::Frame;\n",
+            ),
+        ]);
+        let mut manager = AnalyzerManager::new(dir.path(), None).unwrap();
+        manager.analyze_project(dir.path()).unwrap();
+
+        let coverage = manager.test_coverage();
+        assert!(coverage.tested.contains_key(Path::new("src/parser.rs")));
+        assert!(!coverage.tested.contains_key(Path::new("src/render.rs")));
+        assert!(coverage.untested.contains(&PathBuf::from("src/render.rs")));
+    }
+
+    #[test]
+    fn file_metadata_resolves_module_dependencies_to_files() {
+        let dir = project(&[
+            ("src/lib.rs", "mod foo;\nuse serde::Serialize;\n"),
+            ("src/foo.rs", "pub fn foo() {}\n"),
+        ]);
+        let mut manager = AnalyzerManager::new(dir.path(), None).unwrap();
+        manager.analyze_project(dir.path()).unwrap();
+
+        let lib = manager
+            .analyzed_files()
+            .find(|path| path.ends_with("src/lib.rs"))
+            .unwrap()
+            .clone();
+        let entry = manager
+            .create_file_entry(&lib, Path::new("src/lib.rs"))
+            .unwrap();
+        assert_eq!(
+            entry.metadata.unwrap().dependencies,
+            vec![
+                "src/foo.rs".to_string(),
+                "unresolved:serde::Serialize".to_string()
+            ]
+        );
+    }
+
+    /// Takes far longer than any test deadline to analyze `.slow` files.
+    #[derive(Debug)]
+    struct SlowAnalyzer;
+
+    impl CodeAnalyzer for SlowAnalyzer {
+        fn analyze(&self, _path: &Path) -> Result<Vec<Dependency>, AnalyzerError> {
+            std::thread::sleep(Duration::from_secs(5));
+            Ok(Vec::new())
+        }
+
+        fn supported_extensions(&self) -> Vec<&'static str> {
+            vec!["slow"]
+        }
+    }
+
+    #[test]
+    fn slow_files_time_out_without_stopping_the_run() {
+        let dir = project(&[
+            ("src/lib.rs", "mod foo;\n"),
+            ("src/foo.rs", "pub fn foo() {}\n"),
+            ("data/blob.slow", "spin\n"),
+        ]);
+        let config = AnalyzerConfig {
+            analysis_timeout: Some(Duration::from_millis(50)),
+            ..AnalyzerConfig::default()
+        };
+        let mut manager = AnalyzerManager::new(dir.path(), Some(config)).unwrap();
+        manager.analyzers.push(Arc::new(SlowAnalyzer));
+
+        let result = manager.analyze_project(dir.path()).unwrap();
+        assert_eq!(result.timed_out.len(), 1);
+        assert!(result.timed_out[0].ends_with("data/blob.slow"));
+        assert!(result
+            .dependencies
+            .iter()
+            .any(|dep| dep.target == Path::new("foo")));
+    }
+
+    #[test]
+    fn stalled_workers_are_capped() {
+        logged_warnings();
+        let dir = project(&[
+            ("data/a.slow", "spin\n"),
+            ("data/b.slow", "spin\n"),
+            ("data/c.slow", "spin\n"),
+        ]);
+        let config = AnalyzerConfig {
+            analysis_timeout: Some(Duration::from_millis(50)),
+            max_stalled_workers: 1,
+            ..AnalyzerConfig::default()
+        };
+        let mut manager = AnalyzerManager::new(dir.path(), Some(config)).unwrap();
+        manager.analyzers.push(Arc::new(SlowAnalyzer));
+
+        let result = manager.analyze_project(dir.path()).unwrap();
+        assert_eq!(result.timed_out.len(), 3);
+        // Only the first file got a thread, which is still running
+        assert_eq!(manager.stalled_workers.load(Ordering::SeqCst), 1);
+        let skipped = logged_warnings()
+            .iter()
+            .filter(|warning| {
+                warning.starts_with(&format!("Not analyzing {}", dir.path().display()))
+            })
+            .count();
+        assert_eq!(skipped, 2);
+    }
+
+    #[test]
+    fn repeated_calls_collapse_into_one_dependency() {
+        let dir = project(&[("app.py", "def run():\n    foo()\n    foo()\n    foo()\n")]);
+        let mut manager = AnalyzerManager::new(dir.path(), None).unwrap();
+
+        let deps = manager.analyze_file(&dir.path().join("app.py")).unwrap();
+        let calls: Vec<&Dependency> = deps
+            .iter()
+            .filter(|dep| dep.dependency_type == DependencyType::FunctionCall)
+            .collect();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].target, Path::new("function:foo"));
+        assert_eq!(calls[0].metadata.line_numbers.len(), 3);
+    }
+
+    #[test]
+    fn saved_state_lets_a_new_run_skip_unchanged_files() {
+        let dir = project(&[
+            ("src/lib.rs", "mod foo;\n"),
+            ("src/foo.rs", "pub fn foo() {}\n"),
+        ]);
+        let lib = dir.path().join("src/lib.rs");
+        let mut manager = AnalyzerManager::new(dir.path(), None).unwrap();
+        assert!(manager.needs_analysis(&lib).unwrap());
+        let first = manager.analyze_project(dir.path()).unwrap();
+        assert!(dir.path().join(".deeptracking-state.json").is_file());
+
+        let mut manager = AnalyzerManager::new(dir.path(), None).unwrap();
+        assert!(!manager.needs_analysis(&lib).unwrap());
+        manager.analyze_project(dir.path()).unwrap();
+        assert_eq!(manager.dependencies().len(), first.dependencies.len());
+    }
+
+    #[test]
+    fn unwritable_state_file_is_reported() {
+        let dir = project(&[("src/lib.rs", "pub fn helper() {}\n"), ("blocker", "")]);
+        let mut manager = AnalyzerManager::new(dir.path(), None).unwrap();
+        manager.state_file = dir.path().join("blocker/.deeptracking-state.json");
+
+        let err = manager.analyze_project(dir.path()).unwrap_err();
+        assert!(err.contains("Failed to save analysis state"), "{}", err);
+    }
+
+    #[test]
+    fn project_structure_nests_files_under_their_folders() {
+        let dir = project(&[("src/foo/bar.rs", "pub fn bar() {}\n"), ("README.md", "")]);
+        let mut manager = AnalyzerManager::new(dir.path(), None).unwrap();
+        let files = manager
+            .analyze_project(dir.path())
+            .unwrap()
+            .project_structure
+            .files;
+
+        let src = files
+            .iter()
+            .find(|entry| entry.path == Path::new("src"))
+            .unwrap();
+        assert_eq!(src.file_type, "directory");
+        assert!(src.metadata.is_none());
+        let foo = &src.children[0];
+        assert_eq!(
+            (foo.path.as_path(), foo.file_type.as_str()),
+            (Path::new("src/foo"), "directory")
+        );
+        let bar = &foo.children[0];
+        assert_eq!(bar.path, Path::new("src/foo/bar.rs"));
+        assert_eq!(bar.file_type, "rs");
+        assert!(bar.metadata.is_some());
+    }
+
+    /// Analyzes `.rs` files like the Rust analyzer, counting the calls.
+    #[derive(Debug, Default)]
+    struct CountingAnalyzer {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl CodeAnalyzer for CountingAnalyzer {
+        fn analyze(&self, path: &Path) -> Result<Vec<Dependency>, AnalyzerError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            RustAnalyzer::new().analyze(path)
+        }
+
+        fn supported_extensions(&self) -> Vec<&'static str> {
+            vec!["rs"]
+        }
+    }
+
+    #[test]
+    fn unchanged_project_reuses_the_cached_structure() {
+        let dir = project(&[("src/lib.rs", "pub fn helper() {}\n")]);
+        let mut manager = AnalyzerManager::new(dir.path(), None).unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        manager.analyzers.insert(
+            0,
+            Arc::new(CountingAnalyzer {
+                calls: calls.clone(),
+            }),
+        );
+        manager.add_output_path(&dir.path().join("reports"));
+        let built = manager.analyze_project(dir.path()).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Mark the cached tree so a rebuild would replace the marker
+        manager
+            .project_state
+            .cached_structure
+            .as_mut()
+            .unwrap()
+            .structure
+            .root = "cached".to_string();
+        let reused = manager.analyze_project(dir.path()).unwrap();
+        assert_eq!(reused.project_structure.root, "cached");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Outputs of earlier runs don't count as project changes
+        fs::write(dir.path().join(".deeptracking-summaries.json"), "{}").unwrap();
+        fs::create_dir_all(dir.path().join("reports")).unwrap();
+        fs::write(dir.path().join("reports/report_1.md"), "# Report\n").unwrap();
+        let reused = manager.analyze_project(dir.path()).unwrap();
+        assert_eq!(reused.project_structure.root, "cached");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        fs::write(dir.path().join("src/extra.rs"), "pub fn extra() {}\n").unwrap();
+        let rebuilt = manager.analyze_project(dir.path()).unwrap();
+        assert_eq!(rebuilt.project_structure.root, built.project_structure.root);
+        let src = rebuilt
+            .project_structure
+            .files
+            .iter()
+            .find(|entry| entry.path == Path::new("src"))
+            .unwrap();
+        assert_eq!(src.children.len(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn legacy_md5_state_is_reanalyzed_once() {
+        let source = "pub fn helper() {}\n";
+        let dir = project(&[("src/lib.rs", source)]);
+        let lib = dir.path().join("src/lib.rs");
+        let state_file = dir.path().join(".deeptracking-state.json");
+        // Saved before the algorithm was recorded, with a matching md5 and a
+        // modification time no edit could be newer than
+        let legacy = serde_json::json!({
+            "analyzed_files": {
+                lib.to_string_lossy(): {
+                    "last_modified": "2999-01-01T00:00:00Z",
+                    "dependencies": [],
+                    "hash": format!("{:x}", md5::compute(source)),
+                }
+            }
+        });
+        fs::write(&state_file, legacy.to_string()).unwrap();
+
+        let mut manager = AnalyzerManager::new(dir.path(), None).unwrap();
+        assert_eq!(
+            manager.project_state.analyzed_files[&lib].hash_algorithm,
+            HashAlgorithm::Md5
+        );
+        let calls = Arc::new(AtomicUsize::new(0));
+        manager.analyzers.insert(
+            0,
+            Arc::new(CountingAnalyzer {
+                calls: calls.clone(),
+            }),
+        );
+
+        manager.analyze_project(dir.path()).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let saved: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&state_file).unwrap()).unwrap();
+        let saved = &saved["analyzed_files"][&*lib.to_string_lossy()];
+        assert_eq!(saved["hash_algorithm"], "blake3");
+        assert_eq!(
+            saved["hash"],
+            blake3::hash(source.as_bytes()).to_hex().as_str()
+        );
+
+        manager.analyze_project(dir.path()).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn parallel_and_serial_runs_agree() {
+        let dir = project(&[
+            (
+                "src/lib.rs",
+                "mod parser;\nmod render;\nuse crate::parser::parse;\n",
+            ),
+            (
+                "src/parser.rs",
+                "use std::fs;\npub fn parse() { helper(); }\nfn helper() {}\n",
+            ),
+            (
+                "src/render/mod.rs",
+                "use crate::parser;\npub fn draw() { parser::parse(); }\n",
+            ),
+            ("scripts/build.py", "import os\nfrom pathlib import Path\n"),
+            ("README.md", "# Demo\n"),
+        ]);
+        let state_file = dir.path().join(".deeptracking-state.json");
+
+        let serial = AnalyzerManager::new(dir.path(), None)
+            .unwrap()
+            .analyze_project(dir.path())
+            .unwrap();
+        fs::remove_file(&state_file).unwrap();
+        let parallel = AnalyzerManager::new(dir.path(), None)
+            .unwrap()
+            .analyze_project_parallel(dir.path())
+            .unwrap();
+
+        assert!(!serial.dependencies.is_empty());
+        assert_eq!(
+            serde_json::to_value(&serial.dependencies).unwrap(),
+            serde_json::to_value(&parallel.dependencies).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(&serial.project_structure).unwrap(),
+            serde_json::to_value(&parallel.project_structure).unwrap()
+        );
+    }
+
+    #[test]
+    fn truncated_state_is_recovered_from_the_backup() {
+        let dir = project(&[("src/lib.rs", "pub fn helper() {}\n")]);
+        let lib = dir.path().join("src/lib.rs");
+        let mut manager = AnalyzerManager::new(dir.path(), None).unwrap();
+        manager.analyze_project(dir.path()).unwrap();
+        // The second save moves the first one into the backup
+        manager.analyze_project(dir.path()).unwrap();
+
+        let state_file = dir.path().join(".deeptracking-state.json");
+        let saved = fs::read(&state_file).unwrap();
+        fs::write(&state_file, &saved[..saved.len() / 2]).unwrap();
+
+        let manager = AnalyzerManager::new(dir.path(), None).unwrap();
+        assert!(manager.project_state.analyzed_files.contains_key(&lib));
+        assert!(!manager.needs_analysis(&lib).unwrap());
+    }
+
+    #[test]
+    fn deeptrackingignore_skips_files_git_still_tracks() {
+        let dir = project(&[
+            (".gitignore", "target/\n"),
+            (
+                ".deeptrackingignore",
+                "tests/fixtures/**\n!tests/fixtures/keep.py\n",
+            ),
+            ("app.py", "import os\n"),
+            ("tests/fixtures/generated.py", "import json\n"),
+            ("tests/fixtures/keep.py", "import sys\n"),
+        ]);
+        let generated = dir.path().join("tests/fixtures/generated.py");
+        let mut manager = AnalyzerManager::new(dir.path(), None).unwrap();
+        assert!(manager.is_ignored(&generated));
+
+        // .gitignore alone leaves the fixture tracked
+        let gitignore = Gitignore::new(dir.path().join(".gitignore")).0;
+        assert!(!gitignore.matched(&generated, false).is_ignore());
+
+        let sources: HashSet<PathBuf> = manager
+            .analyze_project(dir.path())
+            .unwrap()
+            .dependencies
+            .into_iter()
+            .map(|dep| dep.source)
+            .collect();
+        assert!(sources.iter().any(|source| source.ends_with("app.py")));
+        assert!(sources
+            .iter()
+            .any(|source| source.ends_with("tests/fixtures/keep.py")));
+        assert!(!sources
+            .iter()
+            .any(|source| source.ends_with("tests/fixtures/generated.py")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_to_an_ancestor_does_not_loop() {
+        let dir = project(&[
+            ("src/lib.rs", "mod foo;\n"),
+            ("src/foo.rs", "pub fn foo() {}\n"),
+        ]);
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("src/root")).unwrap();
+
+        for follow_symlinks in [false, true] {
+            let config = AnalyzerConfig {
+                follow_symlinks,
+                ..AnalyzerConfig::default()
+            };
+            let manager = AnalyzerManager::new(dir.path(), Some(config)).unwrap();
+            let files = manager.collect_files(dir.path(), &mut Vec::new());
+            assert_eq!(files.len(), 2, "follow_symlinks: {}", follow_symlinks);
+        }
+    }
+
+    #[test]
+    fn directories_at_max_depth_are_reported() {
+        let dir = project(&[
+            ("a/shallow.py", "import os\n"),
+            ("a/b/c/deep.py", "import sys\n"),
+        ]);
+        let config = AnalyzerConfig {
+            max_depth: Some(2),
+            ..AnalyzerConfig::default()
+        };
+        let mut manager = AnalyzerManager::new(dir.path(), Some(config)).unwrap();
+
+        let result = manager.analyze_project(dir.path()).unwrap();
+        assert_eq!(result.depth_limited, vec![dir.path().join("a/b")]);
+        assert!(result
+            .dependencies
+            .iter()
+            .all(|dep| !dep.source.ends_with("a/b/c/deep.py")));
+    }
+
+    #[test]
+    fn oversized_and_binary_files_are_skipped_without_failing() {
+        let large = format!("import os\n{}", "# padding\n".repeat(100));
+        let dir = project(&[
+            ("app.py", "import sys\n"),
+            ("large.py", large.as_str()),
+            ("binary.py", "import json\n\0\u{1}\u{2}"),
+        ]);
+        let config = AnalyzerConfig {
+            max_file_size: 512,
+            ..AnalyzerConfig::default()
+        };
+        let mut manager = AnalyzerManager::new(dir.path(), Some(config)).unwrap();
+        let large = dir.path().join("large.py");
+        let binary = dir.path().join("binary.py");
+        assert!(manager.skip_reason(&large).unwrap().contains("byte limit"));
+        assert_eq!(
+            manager.skip_reason(&binary).as_deref(),
+            Some("looks like a binary file")
+        );
+        assert_eq!(manager.skip_reason(&dir.path().join("app.py")), None);
+
+        let sources: Vec<PathBuf> = manager
+            .analyze_project(dir.path())
+            .unwrap()
+            .dependencies
+            .into_iter()
+            .map(|dep| dep.source)
+            .collect();
+        assert!(sources.iter().any(|source| source.ends_with("app.py")));
+        assert!(sources
+            .iter()
+            .all(|source| !source.ends_with("large.py") && !source.ends_with("binary.py")));
+        assert!(manager.analyze_file(&binary).unwrap().is_empty());
+    }
+
+    /// Warnings logged so far by any test. Installs the capturing logger on
+    /// first use, so call it before the code under test runs.
+    fn logged_warnings() -> Vec<String> {
+        use std::sync::{Mutex, Once};
+
+        static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        static INSTALL: Once = Once::new();
+
+        struct CapturingLogger;
+        impl log::Log for CapturingLogger {
+            fn enabled(&self, metadata: &log::Metadata) -> bool {
+                metadata.level() <= log::Level::Warn
+            }
+            fn log(&self, record: &log::Record) {
+                if self.enabled(record.metadata()) {
+                    WARNINGS.lock().unwrap().push(record.args().to_string());
+                }
+            }
+            fn flush(&self) {}
+        }
+
+        INSTALL.call_once(|| {
+            if log::set_logger(&CapturingLogger).is_ok() {
+                log::set_max_level(log::LevelFilter::Warn);
+            }
+        });
+        WARNINGS.lock().unwrap().clone()
+    }
+
+    const GOOD_STATE: &str = r#"{"analyzed_files": {"src/lib.rs": {"last_modified": "2024-01-01T00:00:00Z", "dependencies": [], "hash": "abc"}}}"#;
+
+    /// A project whose state file is corrupted and whose backup is good.
+    fn project_with_corrupted_state() -> (TempDir, PathBuf) {
+        let dir = project(&[("src/lib.rs", "pub fn helper() {}\n")]);
+        let state_file = dir.path().join(".deeptracking-state.json");
+        fs::write(AnalyzerManager::backup_file(&state_file), GOOD_STATE).unwrap();
+        fs::write(&state_file, "{\"analyzed_files\": {").unwrap();
+        (dir, state_file)
+    }
+
+    #[test]
+    fn corrupted_state_falls_back_to_the_backup() {
+        logged_warnings();
+        let (dir, state_file) = project_with_corrupted_state();
+
+        let manager = AnalyzerManager::new(dir.path(), None).unwrap();
+        assert!(manager
+            .project_state
+            .analyzed_files
+            .contains_key(Path::new("src/lib.rs")));
+        assert!(!manager.state_file_valid);
+
+        let expected = format!("{} is corrupted", state_file.display());
+        assert!(logged_warnings()
+            .iter()
+            .any(|warning| warning.starts_with(&expected)));
+    }
+
+    #[test]
+    fn saving_over_a_corrupted_state_keeps_the_good_backup() {
+        let (dir, state_file) = project_with_corrupted_state();
+        let backup_file = AnalyzerManager::backup_file(&state_file);
+
+        let mut manager = AnalyzerManager::new(dir.path(), None).unwrap();
+        manager.save_state().unwrap();
+        assert_eq!(fs::read_to_string(&backup_file).unwrap(), GOOD_STATE);
+        let (saved, valid) = AnalyzerManager::load_state(&state_file);
+        assert!(valid);
+        assert!(saved
+            .unwrap()
+            .analyzed_files
+            .contains_key(Path::new("src/lib.rs")));
+
+        // Once the state is valid again, saving rotates it into the backup
+        manager.save_state().unwrap();
+        assert_ne!(fs::read_to_string(&backup_file).unwrap(), GOOD_STATE);
     }
 }
@@ -1,11 +1,16 @@
+mod errors;
 pub mod manager;
 mod python;
+mod resolver;
 mod rust;
+mod rust_ast;
 
+pub use errors::{AnalyzerError, TrackerError};
 pub use python::PythonAnalyzer;
 pub use rust::RustAnalyzer;
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
 
@@ -25,25 +30,95 @@ pub enum DependencyType {
     Usage,
     FunctionDefinition,
     TypeUsage,
+    /// A struct, enum, trait, type alias or class declared by the source.
+    TypeDefinition,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+/// Equality and hashing ignore `context`, which holds free-form analyzer
+/// details rather than identity.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DependencyMetadata {
     pub line_number: Option<usize>,
+    /// Every line the dependency occurs on once duplicates are collapsed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub line_numbers: Vec<usize>,
     pub description: Option<String>,
     pub relationships: Option<Vec<String>>,
     pub context: Option<serde_json::Value>,
 }
 
+impl PartialEq for DependencyMetadata {
+    fn eq(&self, other: &Self) -> bool {
+        self.line_number == other.line_number
+            && self.line_numbers == other.line_numbers
+            && self.description == other.description
+            && self.relationships == other.relationships
+    }
+}
+
+impl Eq for DependencyMetadata {}
+
 impl Hash for DependencyMetadata {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.line_number.hash(state);
+        self.line_numbers.hash(state);
         self.description.hash(state);
         self.relationships.hash(state);
     }
 }
 
+/// Collapses dependencies sharing source, target and type into one, keeping
+/// the first occurrence and recording every line in `line_numbers`.
+pub fn deduplicate_dependencies(deps: Vec<Dependency>) -> Vec<Dependency> {
+    let mut positions: HashMap<(PathBuf, PathBuf, DependencyType), usize> = HashMap::new();
+    let mut unique: Vec<Dependency> = Vec::new();
+
+    for dep in deps {
+        let key = (
+            dep.source.clone(),
+            dep.target.clone(),
+            dep.dependency_type.clone(),
+        );
+        let lines: Vec<usize> = dep
+            .metadata
+            .line_number
+            .into_iter()
+            .chain(dep.metadata.line_numbers.iter().copied())
+            .collect();
+
+        match positions.get(&key) {
+            Some(&index) => unique[index].metadata.line_numbers.extend(lines),
+            None => {
+                positions.insert(key, unique.len());
+                let mut dep = dep;
+                dep.metadata.line_numbers = lines;
+                unique.push(dep);
+            }
+        }
+    }
+
+    for dep in &mut unique {
+        dep.metadata.line_numbers.sort_unstable();
+        dep.metadata.line_numbers.dedup();
+    }
+
+    unique
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionComplexity {
+    pub file: PathBuf,
+    pub function: String,
+    pub complexity: usize,
+    pub line_range: (usize, usize),
+}
+
 pub trait CodeAnalyzer: Send + Sync + std::fmt::Debug {
-    fn analyze(&self, path: &Path) -> Result<Vec<Dependency>, String>;
+    fn analyze(&self, path: &Path) -> Result<Vec<Dependency>, AnalyzerError>;
     fn supported_extensions(&self) -> Vec<&'static str>;
+
+    /// Cyclomatic complexity of every function in the file.
+    fn analyze_complexity(&self, _path: &Path) -> Result<Vec<FunctionComplexity>, AnalyzerError> {
+        Ok(Vec::new())
+    }
 }
@@ -1,4 +1,7 @@
-use super::{CodeAnalyzer, Dependency, DependencyMetadata, DependencyType};
+use super::{
+    resolver, AnalyzerError, CodeAnalyzer, Dependency, DependencyMetadata, DependencyType,
+    FunctionComplexity,
+};
 use lazy_static::lazy_static;
 use regex::Regex;
 use serde_json::json;
@@ -11,18 +14,27 @@ pub struct PythonAnalyzer {
     function_pattern: Regex,
     import_pattern: Regex,
     from_import_pattern: Regex,
+    call_pattern: Regex,
+    project_root: Option<PathBuf>,
 }
 
+const PYTHON_KEYWORDS: &[&str] = &[
+    "if", "elif", "else", "while", "for", "in", "not", "and", "or", "is", "return", "yield",
+    "with", "assert", "del", "except", "raise", "lambda", "await", "def", "class", "import",
+    "from", "pass", "global", "nonlocal",
+];
+
 impl PythonAnalyzer {
     pub fn new() -> Self {
         lazy_static! {
             static ref CLASS_RE: Regex =
-                Regex::new(r"^class\s+(\w+)(?:\s*\([^)]*\))?\s*:").unwrap();
+                Regex::new(r"^class\s+(\w+)(?:\s*\(([^)]*)\))?\s*:").unwrap();
             static ref FUNC_RE: Regex =
                 Regex::new(r"^def\s+(\w+)\s*\([^)]*\)\s*(?:->.*)?:").unwrap();
             static ref IMPORT_RE: Regex = Regex::new(r"^import\s+([\w,\s]+)").unwrap();
             static ref FROM_IMPORT_RE: Regex =
                 Regex::new(r"^from\s+([\w.]+)\s+import\s+([\w,\s]+)").unwrap();
+            static ref CALL_RE: Regex = Regex::new(r"([A-Za-z_][\w.]*)\s*\(").unwrap();
         }
 
         PythonAnalyzer {
@@ -30,9 +42,18 @@ impl PythonAnalyzer {
             function_pattern: FUNC_RE.clone(),
             import_pattern: IMPORT_RE.clone(),
             from_import_pattern: FROM_IMPORT_RE.clone(),
+            call_pattern: CALL_RE.clone(),
+            project_root: None,
         }
     }
 
+    /// Absolute imports resolve against directories up to `root`, never
+    /// above it.
+    pub fn with_project_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.project_root = Some(root.into());
+        self
+    }
+
     fn analyze_imports(&self, content: &str, path: &Path) -> Vec<Dependency> {
         let mut dependencies = Vec::new();
 
@@ -49,6 +70,7 @@ impl PythonAnalyzer {
                         dependency_type: DependencyType::Import,
                         metadata: DependencyMetadata {
                             line_number: Some(line_num),
+                            line_numbers: Vec::new(),
                             description: Some(format!("Import: {}", import)),
                             context: Some(json!({
                                 "import_type": "direct",
@@ -67,10 +89,16 @@ impl PythonAnalyzer {
                 for import in imports {
                     dependencies.push(Dependency {
                         source: path.to_path_buf(),
-                        target: PathBuf::from(format!("{}.{}", module, import)),
+                        // `from . import x` names `.x`, not `..x`
+                        target: PathBuf::from(if module.ends_with('.') {
+                            format!("{}{}", module, import)
+                        } else {
+                            format!("{}.{}", module, import)
+                        }),
                         dependency_type: DependencyType::Import,
                         metadata: DependencyMetadata {
                             line_number: Some(line_num),
+                            line_numbers: Vec::new(),
                             description: Some(format!("From {} import {}", module, import)),
                             context: Some(json!({
                                 "import_type": "from",
@@ -91,20 +119,139 @@ impl PythonAnalyzer {
         dependencies
     }
 
+    fn parse_base_classes(bases: &str) -> Vec<String> {
+        bases
+            .split(',')
+            .map(str::trim)
+            // Skip keyword arguments such as `metaclass=ABCMeta`
+            .filter(|base| !base.is_empty() && !base.contains('='))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// `content` with comments removed and string literals emptied, keeping
+    /// every line break so lines still line up with the original. Triple
+    /// quoted strings may span lines.
+    fn strip_comments_and_strings(content: &str) -> String {
+        let chars: Vec<char> = content.chars().collect();
+        let mut code = String::with_capacity(content.len());
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            if c == '#' {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            if c != '"' && c != '\'' {
+                code.push(c);
+                i += 1;
+                continue;
+            }
+
+            let quote_len = if chars[i..].starts_with(&[c, c, c]) {
+                3
+            } else {
+                1
+            };
+            let delimiter = &chars[i..i + quote_len];
+            i += quote_len;
+            code.push(c);
+            while i < chars.len() {
+                if chars[i..].starts_with(delimiter) {
+                    i += quote_len;
+                    break;
+                }
+                match chars[i] {
+                    '\\' => {
+                        if chars.get(i + 1) == Some(&'\n') {
+                            code.push('\n');
+                        }
+                        i += 2;
+                    }
+                    // An unterminated single quoted string ends with its line
+                    '\n' if quote_len == 1 => break,
+                    '\n' => {
+                        code.push('\n');
+                        i += 1;
+                    }
+                    _ => i += 1,
+                }
+            }
+            code.push(c);
+        }
+
+        code
+    }
+
+    /// Calls made on `line`, found in `code`, the same line with comments
+    /// and string literals stripped.
+    fn analyze_calls(
+        &self,
+        line: &str,
+        code: &str,
+        line_num: usize,
+        caller: &str,
+        current_class: Option<&str>,
+    ) -> Vec<Dependency> {
+        self.call_pattern
+            .captures_iter(code)
+            .map(|cap| cap[1].to_string())
+            .filter(|callee| !PYTHON_KEYWORDS.contains(&callee.as_str()))
+            .map(|callee| {
+                // Resolve `self.method()` to the method on the enclosing class
+                let callee = match (callee.strip_prefix("self."), current_class) {
+                    (Some(method), Some(class_name)) => format!("{}::{}", class_name, method),
+                    _ => callee,
+                };
+
+                Dependency {
+                    source: PathBuf::from(format!("function:{}", caller)),
+                    target: PathBuf::from(format!("function:{}", callee)),
+                    dependency_type: DependencyType::FunctionCall,
+                    metadata: DependencyMetadata {
+                        line_number: Some(line_num),
+                        line_numbers: Vec::new(),
+                        description: Some(format!("Function call: {} -> {}", caller, callee)),
+                        context: Some(json!({
+                            "type": "call",
+                            "caller": caller,
+                            "callee": callee,
+                            "line_content": line,
+                        })),
+                        relationships: Some(vec![format!("Called by function: {}", caller)]),
+                    },
+                }
+            })
+            .collect()
+    }
+
     fn analyze_classes_and_functions(&self, content: &str, path: &Path) -> Vec<Dependency> {
         let mut dependencies = Vec::new();
         let mut current_class: Option<String> = None;
+        let mut current_function: Option<(String, usize)> = None;
         let mut indent_level = 0;
+        let code = Self::strip_comments_and_strings(content);
 
-        for (line_num, line) in content.lines().enumerate() {
+        for (line_num, (line, code_line)) in content.lines().zip(code.lines()).enumerate() {
             let spaces = line.chars().take_while(|c| c.is_whitespace()).count();
             let line = line.trim();
 
+            // Blank lines and comments don't affect scope
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
             // Track indentation
             if spaces == 0 {
                 current_class = None;
                 indent_level = 0;
             }
+            if matches!(current_function, Some((_, func_indent)) if spaces <= func_indent) {
+                current_function = None;
+            }
 
             // Class definition
             if let Some(cap) = self.class_pattern.captures(line) {
@@ -112,21 +259,52 @@ impl PythonAnalyzer {
                 current_class = Some(class_name.to_string());
                 indent_level = spaces;
 
+                let bases = cap
+                    .get(2)
+                    .map(|m| Self::parse_base_classes(m.as_str()))
+                    .unwrap_or_default();
+
                 dependencies.push(Dependency {
                     source: path.to_path_buf(),
                     target: PathBuf::from(format!("class:{}", class_name)),
-                    dependency_type: DependencyType::Inheritance,
+                    dependency_type: DependencyType::TypeDefinition,
                     metadata: DependencyMetadata {
                         line_number: Some(line_num),
+                        line_numbers: Vec::new(),
                         description: Some(format!("Class definition: {}", class_name)),
                         context: Some(json!({
                             "type": "class",
                             "name": class_name,
+                            "bases": bases,
                             "indent_level": indent_level
                         })),
                         relationships: Some(vec![format!("Defines class {}", class_name)]),
                     },
                 });
+
+                for base in &bases {
+                    dependencies.push(Dependency {
+                        source: PathBuf::from(format!("class:{}", class_name)),
+                        target: PathBuf::from(format!("class:{}", base)),
+                        dependency_type: DependencyType::Inheritance,
+                        metadata: DependencyMetadata {
+                            line_number: Some(line_num),
+                            line_numbers: Vec::new(),
+                            description: Some(format!("{} inherits from {}", class_name, base)),
+                            context: Some(json!({
+                                "type": "inheritance",
+                                "class": class_name,
+                                "base": base,
+                                "file": path.to_string_lossy(),
+                            })),
+                            relationships: Some(vec![format!(
+                                "Class {} extends {}",
+                                class_name, base
+                            )]),
+                        },
+                    });
+                }
+                continue;
             }
 
             // Function definition
@@ -141,9 +319,10 @@ impl PythonAnalyzer {
                 dependencies.push(Dependency {
                     source: path.to_path_buf(),
                     target: PathBuf::from(format!("function:{}", qualified_name)),
-                    dependency_type: DependencyType::FunctionCall,
+                    dependency_type: DependencyType::FunctionDefinition,
                     metadata: DependencyMetadata {
                         line_number: Some(line_num),
+                        line_numbers: Vec::new(),
                         description: Some(format!("Function definition: {}", qualified_name)),
                         context: Some(json!({
                             "type": "function",
@@ -159,6 +338,20 @@ impl PythonAnalyzer {
                         }]),
                     },
                 });
+
+                current_function = Some((qualified_name, spaces));
+                continue;
+            }
+
+            // Function calls inside a function body
+            if let Some((caller, _)) = &current_function {
+                dependencies.extend(self.analyze_calls(
+                    line,
+                    code_line,
+                    line_num,
+                    caller,
+                    current_class.as_deref(),
+                ));
             }
         }
 
@@ -166,18 +359,293 @@ impl PythonAnalyzer {
     }
 }
 
+impl PythonAnalyzer {
+    fn function_complexities(&self, content: &str, path: &Path) -> Vec<FunctionComplexity> {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut results = Vec::new();
+        let mut current_class: Option<(String, usize)> = None;
+
+        for (start, raw_line) in lines.iter().enumerate() {
+            let spaces = raw_line.chars().take_while(|c| c.is_whitespace()).count();
+            let line = raw_line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if matches!(current_class, Some((_, class_indent)) if spaces <= class_indent) {
+                current_class = None;
+            }
+            if let Some(cap) = self.class_pattern.captures(line) {
+                current_class = Some((cap[1].to_string(), spaces));
+                continue;
+            }
+
+            let func_name = match self.function_pattern.captures(line) {
+                Some(cap) => cap[1].to_string(),
+                None => continue,
+            };
+            let function = match &current_class {
+                Some((class_name, _)) => format!("{}::{}", class_name, func_name),
+                None => func_name,
+            };
+
+            // The body ends before the next line indented at or above the def
+            let mut end = start;
+            for (offset, body_line) in lines[start + 1..].iter().enumerate() {
+                let body = body_line.trim();
+                if body.is_empty() || body.starts_with('#') {
+                    continue;
+                }
+                if body_line.chars().take_while(|c| c.is_whitespace()).count() <= spaces {
+                    break;
+                }
+                end = start + 1 + offset;
+            }
+
+            let branches: usize = lines[start..=end]
+                .iter()
+                .map(|l| Self::count_branch_points(l))
+                .sum();
+            results.push(FunctionComplexity {
+                file: path.to_path_buf(),
+                function,
+                complexity: 1 + branches,
+                line_range: (start, end),
+            });
+        }
+
+        results
+    }
+
+    fn count_branch_points(line: &str) -> usize {
+        let code = line.split('#').next().unwrap_or("");
+        code.split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|token| {
+                matches!(
+                    *token,
+                    "if" | "elif" | "for" | "while" | "except" | "and" | "or" | "case"
+                )
+            })
+            .count()
+    }
+}
+
 impl CodeAnalyzer for PythonAnalyzer {
-    fn analyze(&self, path: &Path) -> Result<Vec<Dependency>, String> {
-        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    fn analyze(&self, path: &Path) -> Result<Vec<Dependency>, AnalyzerError> {
+        let content = fs::read_to_string(path).map_err(|e| AnalyzerError::io(path, e))?;
         let mut dependencies = Vec::new();
 
         dependencies.extend(self.analyze_imports(&content, path));
         dependencies.extend(self.analyze_classes_and_functions(&content, path));
 
+        resolver::resolve_python_imports(&mut dependencies, self.project_root.as_deref());
         Ok(dependencies)
     }
 
     fn supported_extensions(&self) -> Vec<&'static str> {
         vec!["py"]
     }
+
+    fn analyze_complexity(&self, path: &Path) -> Result<Vec<FunctionComplexity>, AnalyzerError> {
+        let content = fs::read_to_string(path).map_err(|e| AnalyzerError::io(path, e))?;
+        Ok(self.function_complexities(&content, path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(deps: &[Dependency], dependency_type: DependencyType) -> Vec<String> {
+        deps.iter()
+            .filter(|dep| dep.dependency_type == dependency_type)
+            .map(|dep| dep.target.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn calls(source: &str) -> Vec<String> {
+        let deps = PythonAnalyzer::new().analyze_classes_and_functions(source, Path::new("m.py"));
+        targets(&deps, DependencyType::FunctionCall)
+    }
+
+    #[test]
+    fn complexity_counts_branch_points_in_the_function_body() {
+        let source = "def grade(score):\n    if score > 90 and score <= 100:\n        return 'A'\n    elif score > 80:\n        return 'B'\n    for _ in range(3):\n        pass\n    return 'C'\n\ndef plain():\n    return 1\n";
+        let complexities = PythonAnalyzer::new().function_complexities(source, Path::new("m.py"));
+
+        let summary: Vec<(&str, usize, (usize, usize))> = complexities
+            .iter()
+            .map(|c| (c.function.as_str(), c.complexity, c.line_range))
+            .collect();
+        // if, and, elif and for
+        assert_eq!(summary, vec![("grade", 5, (0, 7)), ("plain", 1, (9, 10))]);
+    }
+
+    /// (source, target) of each edge of `dependency_type` found in `source`.
+    fn edges(source: &str, dependency_type: DependencyType) -> Vec<(String, String)> {
+        PythonAnalyzer::new()
+            .analyze_classes_and_functions(source, Path::new("m.py"))
+            .into_iter()
+            .filter(|dep| dep.dependency_type == dependency_type)
+            .map(|dep| {
+                (
+                    dep.source.to_string_lossy().into_owned(),
+                    dep.target.to_string_lossy().into_owned(),
+                )
+            })
+            .collect()
+    }
+
+    fn edge(source: &str, target: &str) -> (String, String) {
+        (source.to_string(), target.to_string())
+    }
+
+    #[test]
+    fn inheritance_edges_point_at_each_base_class() {
+        let source = "class B(A, mod.C, metaclass=Meta):\n    pass\n";
+        assert_eq!(
+            edges(source, DependencyType::Inheritance),
+            vec![edge("class:B", "class:A"), edge("class:B", "class:mod.C")]
+        );
+        // The declaration itself is a separate edge from the file
+        assert_eq!(
+            edges(source, DependencyType::TypeDefinition),
+            vec![edge("m.py", "class:B")]
+        );
+    }
+
+    #[test]
+    fn single_inheritance_has_one_edge() {
+        let source = "class Foo(Bar):\n    pass\n";
+        assert_eq!(
+            edges(source, DependencyType::Inheritance),
+            vec![edge("class:Foo", "class:Bar")]
+        );
+        assert_eq!(
+            edges(source, DependencyType::TypeDefinition),
+            vec![edge("m.py", "class:Foo")]
+        );
+    }
+
+    #[test]
+    fn class_without_bases_has_no_inheritance_edge() {
+        for source in ["class Foo:\n    pass\n", "class Foo():\n    pass\n"] {
+            assert!(edges(source, DependencyType::Inheritance).is_empty());
+            assert_eq!(
+                edges(source, DependencyType::TypeDefinition),
+                vec![edge("m.py", "class:Foo")]
+            );
+        }
+    }
+
+    #[test]
+    fn calls_in_methods_are_attributed_to_the_method() {
+        let source = "import os\n\nclass Parser:\n    def parse(self, text):\n        tokens = split(text)\n        return self.build(tokens)\n\n    def build(self, tokens):\n        return Node(tokens)\n";
+        let deps = PythonAnalyzer::new().analyze_classes_and_functions(source, Path::new("m.py"));
+        let calls: Vec<(String, String, Option<usize>)> = deps
+            .iter()
+            .filter(|dep| dep.dependency_type == DependencyType::FunctionCall)
+            .map(|dep| {
+                (
+                    dep.source.to_string_lossy().into_owned(),
+                    dep.target.to_string_lossy().into_owned(),
+                    dep.metadata.line_number,
+                )
+            })
+            .collect();
+        let call = |caller: &str, callee: &str, line: usize| {
+            (caller.to_string(), callee.to_string(), Some(line))
+        };
+        assert_eq!(
+            calls,
+            vec![
+                call("function:Parser::parse", "function:split", 4),
+                call("function:Parser::parse", "function:Parser::build", 5),
+                call("function:Parser::build", "function:Node", 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn calls_inside_strings_are_ignored() {
+        let source = "def run():\n    log(\"retry(later)\")\n    s = 'parse(x)'\n    \"\"\"\n    helper(1)\n    \"\"\"\n    done()\n";
+        assert_eq!(calls(source), vec!["function:log", "function:done"]);
+    }
+
+    #[test]
+    fn hash_inside_a_string_does_not_start_a_comment() {
+        let source = "def run():\n    fmt(\"#\", value(1))  # skip(2)\n";
+        assert_eq!(calls(source), vec!["function:fmt", "function:value"]);
+    }
+
+    #[test]
+    fn keywords_followed_by_parentheses_are_not_calls() {
+        let source = "def run(x):\n    if(x):\n        return(check(x))\n    while(x):\n        x = step(x)\n";
+        assert_eq!(calls(source), vec!["function:check", "function:step"]);
+    }
+
+    /// Import targets of `path`, analyzed within `root`, with whether each
+    /// was tagged external.
+    fn resolved_imports(root: &Path, path: &Path) -> Vec<(PathBuf, bool)> {
+        PythonAnalyzer::new()
+            .with_project_root(root)
+            .analyze(path)
+            .unwrap()
+            .into_iter()
+            .filter(|dep| dep.dependency_type == DependencyType::Import)
+            .map(|dep| {
+                let context = dep.metadata.context.as_ref().unwrap();
+                let external = context["external"].as_bool().unwrap();
+                (dep.target, external)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn imports_resolve_to_project_files_and_tag_external_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in ["app/__init__.py", "app/models.py", "app/helpers.py"] {
+            std::fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        let views = root.join("app/views.py");
+        std::fs::write(
+            &views,
+            "from app.models import User\nfrom . import helpers\nimport os\n",
+        )
+        .unwrap();
+
+        let imports = resolved_imports(root, &views);
+        assert_eq!(
+            imports,
+            vec![
+                (root.join("app/models.py"), false),
+                (root.join("app/helpers.py"), false),
+                (PathBuf::from("os"), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn modules_above_the_project_root_stay_external() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("project");
+        std::fs::create_dir_all(root.join("app")).unwrap();
+        // Same name as the import, but outside the project
+        std::fs::write(dir.path().join("utils.py"), "").unwrap();
+        let main = root.join("app/main.py");
+        std::fs::write(&main, "import utils\n").unwrap();
+
+        assert_eq!(
+            resolved_imports(&root, &main),
+            vec![(PathBuf::from("utils"), true)]
+        );
+
+        std::fs::write(root.join("utils.py"), "").unwrap();
+        assert_eq!(
+            resolved_imports(&root, &main),
+            vec![(root.join("utils.py"), false)]
+        );
+    }
 }
@@ -0,0 +1,209 @@
+use crate::analyzers::{Dependency, DependencyType};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+/// Resolves an import dependency to the file it refers to, relative to
+/// `project_root`. Returns `None` for non-imports, external crates and
+/// packages, and anything else that doesn't map onto a file in the project.
+pub(crate) fn resolve_import(project_root: &Path, dep: &Dependency) -> Option<PathBuf> {
+    if dep.dependency_type != DependencyType::Import {
+        return None;
+    }
+
+    // Analyzers that resolve their own imports already point at the file
+    if dep.target.is_file() {
+        return dep
+            .target
+            .strip_prefix(project_root)
+            .map(Path::to_path_buf)
+            .ok();
+    }
+
+    let target = dep.target.to_string_lossy();
+    let resolved = match dep.source.extension().and_then(|ext| ext.to_str()) {
+        Some("rs") => {
+            let kind = dep
+                .metadata
+                .context
+                .as_ref()
+                .and_then(|context| context.get("type"))
+                .and_then(|kind| kind.as_str());
+            resolve_rust(&dep.source, &target, kind == Some("mod"))
+        }
+        Some("py") => resolve_python(project_root, &dep.source, &target),
+        _ => None,
+    }?;
+
+    resolved
+        .strip_prefix(project_root)
+        .map(Path::to_path_buf)
+        .ok()
+}
+
+/// Points Rust imports at the file they name, keeping the module path in
+/// `context.symbol`. Imports of external crates are left symbolic.
+pub(crate) fn resolve_rust_imports(deps: &mut [Dependency]) {
+    for dep in deps
+        .iter_mut()
+        .filter(|dep| dep.dependency_type == DependencyType::Import)
+    {
+        let context = dep.metadata.context.get_or_insert_with(|| json!({}));
+        let is_mod = context.get("type").and_then(|kind| kind.as_str()) == Some("mod");
+        let symbol = dep.target.to_string_lossy().into_owned();
+
+        if let Some(file) = resolve_rust(&dep.source, &symbol, is_mod) {
+            if let Some(context) = context.as_object_mut() {
+                context.insert("symbol".to_string(), json!(symbol));
+            }
+            dep.target = file;
+        }
+    }
+}
+
+fn resolve_rust(source: &Path, target: &str, is_mod: bool) -> Option<PathBuf> {
+    let module_dir = rust_module_dir(source)?;
+
+    if is_mod {
+        return first_file(&[
+            module_dir.join(format!("{}.rs", target)),
+            module_dir.join(target).join("mod.rs"),
+        ]);
+    }
+
+    // Groups and globs import from the module named by the prefix
+    let path = target
+        .split("::{")
+        .next()
+        .unwrap_or(target)
+        .trim_end_matches("::*");
+    let segments: Vec<&str> = path.split("::").filter(|s| !s.is_empty()).collect();
+
+    let (base, rest) = match segments.first() {
+        Some(&"crate") => (crate_root(source)?, &segments[1..]),
+        Some(&"self") => (module_dir, &segments[1..]),
+        Some(&"super") => {
+            let supers = segments.iter().take_while(|s| **s == "super").count();
+            let mut base = module_dir;
+            for _ in 0..supers {
+                base = base.parent()?.to_path_buf();
+            }
+            (base, &segments[supers..])
+        }
+        _ => (module_dir, &segments[..]),
+    };
+
+    // The longest prefix that maps onto a file wins, the rest names items in it
+    for len in (1..=rest.len()).rev() {
+        let mut dir = base.clone();
+        for segment in &rest[..len - 1] {
+            dir.push(segment);
+        }
+        let last = rest[len - 1];
+        if let Some(file) = first_file(&[
+            dir.join(format!("{}.rs", last)),
+            dir.join(last).join("mod.rs"),
+        ]) {
+            return Some(file);
+        }
+    }
+
+    None
+}
+
+/// Directory holding the child modules declared by `source`.
+fn rust_module_dir(source: &Path) -> Option<PathBuf> {
+    let dir = source.parent()?;
+    match source.file_stem()?.to_str()? {
+        "mod" | "lib" | "main" => Some(dir.to_path_buf()),
+        stem => Some(dir.join(stem)),
+    }
+}
+
+fn crate_root(source: &Path) -> Option<PathBuf> {
+    source
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join("lib.rs").is_file() || dir.join("main.rs").is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Points Python imports at the project file they name, keeping the dotted
+/// name in `context.symbol`. Absolute imports are looked up from every
+/// ancestor of the importing file (and its `src/`) up to `project_root`,
+/// like entries on `sys.path`; those that match nothing are third-party or
+/// stdlib and get `context.external = true`. Without a root only the
+/// importing file's directory is searched. Nothing outside the root resolves.
+pub(crate) fn resolve_python_imports(deps: &mut [Dependency], project_root: Option<&Path>) {
+    for dep in deps
+        .iter_mut()
+        .filter(|dep| dep.dependency_type == DependencyType::Import)
+    {
+        let symbol = dep.target.to_string_lossy().into_owned();
+        let is_relative = symbol.starts_with('.');
+        let Some(root) = project_root.or_else(|| dep.source.parent()) else {
+            continue;
+        };
+
+        let resolved = if is_relative {
+            dep.source
+                .parent()
+                .and_then(|dir| resolve_python(dir, &dep.source, &symbol))
+        } else {
+            dep.source
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| dir.starts_with(root))
+                .find_map(|dir| {
+                    resolve_python(dir, &dep.source, &symbol)
+                        .or_else(|| resolve_python(&dir.join("src"), &dep.source, &symbol))
+                })
+        }
+        .filter(|file| file.starts_with(root));
+
+        let context = dep.metadata.context.get_or_insert_with(|| json!({}));
+        if let Some(context) = context.as_object_mut() {
+            context.insert("symbol".to_string(), json!(symbol));
+            context.insert(
+                "external".to_string(),
+                json!(resolved.is_none() && !is_relative),
+            );
+        }
+        if let Some(file) = resolved {
+            dep.target = file;
+        }
+    }
+}
+
+fn resolve_python(project_root: &Path, source: &Path, target: &str) -> Option<PathBuf> {
+    let dots = target.chars().take_while(|c| *c == '.').count();
+    let mut base = if dots > 0 {
+        source.parent()?.to_path_buf()
+    } else {
+        project_root.to_path_buf()
+    };
+    for _ in 1..dots {
+        base = base.parent()?.to_path_buf();
+    }
+
+    let segments: Vec<&str> = target[dots..]
+        .split('.')
+        .filter(|s| !s.is_empty())
+        .collect();
+    if segments.is_empty() {
+        return first_file(&[base.join("__init__.py")]);
+    }
+
+    // `from pkg.mod import name` may name a module or an item in one
+    for len in (1..=segments.len()).rev() {
+        let module = base.join(segments[..len].join("/"));
+        if let Some(file) = first_file(&[module.with_extension("py"), module.join("__init__.py")]) {
+            return Some(file);
+        }
+    }
+
+    None
+}
+
+fn first_file(candidates: &[PathBuf]) -> Option<PathBuf> {
+    candidates.iter().find(|path| path.is_file()).cloned()
+}
@@ -1,4 +1,7 @@
-use crate::analyzers::{CodeAnalyzer, Dependency, DependencyMetadata, DependencyType};
+use crate::analyzers::{
+    resolver, rust_ast, AnalyzerError, CodeAnalyzer, Dependency, DependencyMetadata,
+    DependencyType, FunctionComplexity,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::{HashMap, HashSet};
@@ -13,6 +16,8 @@ pub struct RustAnalyzer {
     modules: HashMap<String, ModuleInfo>,
     // Track current analysis state
     current_scope: Vec<String>,
+    // Walk the syn AST instead of matching lines
+    use_ast: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -35,6 +40,15 @@ struct ParameterInfo {
     is_mutable: bool,
 }
 
+/// A line that opens a named block: an inline `mod`, a `trait` or an `impl`.
+#[derive(Debug, Clone)]
+struct ScopeHeader {
+    kind: &'static str,
+    name: String,
+    trait_name: Option<String>,
+    supertraits: Vec<String>,
+}
+
 #[derive(Debug, Clone)]
 struct ModuleInfo {
     name: String,
@@ -50,14 +64,28 @@ impl RustAnalyzer {
             functions: HashMap::new(),
             modules: HashMap::new(),
             current_scope: Vec::new(),
+            use_ast: false,
+        }
+    }
+
+    /// Creates an analyzer backed by a full `syn` parse. It is slower than the
+    /// line heuristics but understands grouped imports, trait impls, nested
+    /// modules and closures.
+    pub fn with_ast_parsing() -> Self {
+        Self {
+            use_ast: true,
+            ..Self::new()
         }
     }
 
     fn analyze_file(&mut self, content: &str, path: &Path) -> Result<Vec<Dependency>, String> {
         let mut dependencies = Vec::new();
-        let mut current_block_depth = 0;
+        let mut current_block_depth = 0usize;
         let mut in_function = false;
+        let mut function_depth = 0usize;
         let mut current_function: Option<String> = None;
+        // Open scopes with the block depth they started at
+        let mut scopes: Vec<(ScopeHeader, usize)> = Vec::new();
 
         let lines: Vec<_> = content.lines().collect();
         let total_lines = lines.len();
@@ -66,11 +94,12 @@ impl RustAnalyzer {
             let line = line.trim();
 
             // Track block depth
-            current_block_depth += line.matches('{').count();
-            current_block_depth -= line.matches('}').count();
+            let depth_before = current_block_depth;
+            current_block_depth = (current_block_depth + line.matches('{').count())
+                .saturating_sub(line.matches('}').count());
 
             // Track function endings
-            if current_block_depth == 0 && in_function {
+            if in_function && current_block_depth <= function_depth {
                 if let Some(func_name) = current_function.take() {
                     if let Some(func_info) = self.functions.get_mut(&func_name) {
                         func_info.block_end = Some(line_num);
@@ -84,9 +113,14 @@ impl RustAnalyzer {
                 dependencies.push(dep);
             }
 
+            if let Some(dep) = self.analyze_type_definition(line, line_num, path) {
+                dependencies.push(dep);
+            }
+
             if let Some(mut deps) = self.analyze_function_definition(line, line_num, path)? {
                 if !in_function {
                     in_function = true;
+                    function_depth = depth_before;
                     current_function = deps.first().map(|d| {
                         d.metadata
                             .description
@@ -94,6 +128,14 @@ impl RustAnalyzer {
                             .map(|desc| desc.replace("Function definition: ", ""))
                             .unwrap_or_default()
                     });
+
+                    let impl_block = scopes
+                        .last()
+                        .filter(|(header, _)| header.kind == "impl")
+                        .map(|(header, _)| header);
+                    if let (Some(header), Some(method)) = (impl_block, current_function.as_ref()) {
+                        deps.push(self.method_dependency(method, header, line_num, path));
+                    }
                 }
                 dependencies.append(&mut deps);
             }
@@ -104,12 +146,23 @@ impl RustAnalyzer {
                 dependencies.append(&mut deps);
             }
 
-            // Handle closing blocks and scope
-            if line.contains('}') && current_block_depth == 0 {
-                if !self.current_scope.is_empty() {
-                    self.current_scope.pop();
+            // Enter `mod`, `trait` and `impl` blocks
+            if !in_function && line.contains('{') {
+                if let Some(header) = Self::scope_header(line) {
+                    dependencies.extend(self.inheritance_dependencies(&header, line_num, path));
+                    self.current_scope.push(header.name.clone());
+                    scopes.push((header, depth_before));
                 }
             }
+
+            // Leave every scope whose block closed on this line
+            while scopes
+                .last()
+                .map_or(false, |(_, depth)| current_block_depth <= *depth)
+            {
+                scopes.pop();
+                self.current_scope.pop();
+            }
         }
 
         Ok(dependencies)
@@ -121,7 +174,9 @@ impl RustAnalyzer {
         line_num: usize,
         path: &Path,
     ) -> Result<Option<Dependency>, String> {
-        if line.starts_with("use ") || line.starts_with("mod ") {
+        // `mod name { ... }` is an inline module, not a file import
+        let inline_mod = line.starts_with("mod ") && line.contains('{');
+        if line.starts_with("use ") || (line.starts_with("mod ") && !inline_mod) {
             let parts: Vec<&str> = line.split_whitespace().collect();
             if parts.len() >= 2 {
                 let import_path = parts[1].trim_end_matches(';');
@@ -131,6 +186,7 @@ impl RustAnalyzer {
                     dependency_type: DependencyType::Import,
                     metadata: DependencyMetadata {
                         line_number: Some(line_num),
+                        line_numbers: Vec::new(),
                         description: Some(format!("Import: {}", import_path)),
                         context: Some(json!({
                             "type": if line.starts_with("use ") { "use" } else { "mod" },
@@ -148,6 +204,249 @@ impl RustAnalyzer {
         Ok(None)
     }
 
+    /// Struct, enum, union, trait and type alias definitions, recorded like
+    /// the AST analyzer does.
+    fn analyze_type_definition(
+        &self,
+        line: &str,
+        line_num: usize,
+        path: &Path,
+    ) -> Option<Dependency> {
+        let is_public = line.starts_with("pub");
+        let rest = if line.starts_with("pub(") {
+            line.split_once(") ")?.1
+        } else {
+            line.strip_prefix("pub ").unwrap_or(line)
+        };
+
+        let (kind, rest) = ["struct", "enum", "union", "trait", "type"]
+            .iter()
+            .find_map(|kind| {
+                rest.strip_prefix(kind)
+                    .and_then(|rest| rest.strip_prefix(' '))
+                    .map(|rest| (*kind, rest))
+            })?;
+        let name: String = rest
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .collect();
+        if name.is_empty() {
+            return None;
+        }
+
+        let full_path = self
+            .current_scope
+            .iter()
+            .chain(std::iter::once(&name))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("::");
+
+        Some(Dependency {
+            source: path.to_path_buf(),
+            target: PathBuf::from(&full_path),
+            dependency_type: DependencyType::TypeDefinition,
+            metadata: DependencyMetadata {
+                line_number: Some(line_num),
+                line_numbers: Vec::new(),
+                description: Some(format!("Type definition: {}", full_path)),
+                context: Some(json!({
+                    "kind": kind,
+                    "is_public": is_public,
+                    "scope": self.current_scope.join("::"),
+                })),
+                relationships: Some(vec![format!("Defines {} {}", kind, full_path)]),
+            },
+        })
+    }
+
+    /// Parses `mod name {`, `trait Name` and `impl<..> [Trait for] Type`
+    /// headers. Impl blocks are named after the implementing type.
+    fn scope_header(line: &str) -> Option<ScopeHeader> {
+        let rest = if line.starts_with("pub(") {
+            line.split_once(") ")?.1
+        } else {
+            line.strip_prefix("pub ").unwrap_or(line)
+        };
+        let rest = rest.strip_prefix("unsafe ").unwrap_or(rest);
+        let ident = |text: &str| -> String {
+            text.chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect()
+        };
+
+        let mut supertraits = Vec::new();
+        let (kind, name, trait_name) = if let Some(rest) = rest.strip_prefix("mod ") {
+            ("mod", ident(rest), None)
+        } else if let Some(rest) = rest.strip_prefix("trait ") {
+            let name = ident(rest);
+            let bounds = Self::skip_generics(rest[name.len()..].trim_start())
+                .and_then(|rest| rest.strip_prefix(':'));
+            if let Some(bounds) = bounds {
+                let bounds = bounds.split(" where ").next().unwrap_or(bounds);
+                supertraits = bounds
+                    .split('{')
+                    .next()
+                    .unwrap_or(bounds)
+                    .split('+')
+                    .map(|bound| bound.trim().trim_start_matches('?').replace(' ', ""))
+                    .filter(|bound| !bound.is_empty() && !bound.starts_with('\''))
+                    .collect();
+            }
+            ("trait", name, None)
+        } else if rest.starts_with("impl ") || rest.starts_with("impl<") {
+            let rest = Self::skip_generics(rest[4..].trim_start())?;
+            let header = rest
+                .split(" where ")
+                .next()
+                .unwrap_or(rest)
+                .split('{')
+                .next()
+                .unwrap_or(rest)
+                .trim();
+            let (trait_name, self_type) = match header.split_once(" for ") {
+                Some((trait_name, self_type)) => (Some(trait_name.trim()), self_type),
+                None => (None, header),
+            };
+
+            // Reduce `&'a path::Foo<T>` to `Foo`, as the AST analyzer does
+            let mut self_type = self_type.trim().trim_start_matches('&');
+            if self_type.starts_with('\'') {
+                self_type = self_type.split_once(' ').map_or("", |(_, ty)| ty);
+            }
+            let self_type = self_type.trim_start_matches("mut ");
+            let base = self_type
+                .split('<')
+                .next()
+                .unwrap_or(self_type)
+                .rsplit("::")
+                .next()
+                .unwrap_or(self_type);
+
+            (
+                "impl",
+                ident(base),
+                trait_name.map(|name| name.replace(' ', "")),
+            )
+        } else {
+            return None;
+        };
+
+        if name.is_empty() {
+            return None;
+        }
+        Some(ScopeHeader {
+            kind,
+            name,
+            trait_name,
+            supertraits,
+        })
+    }
+
+    /// Returns the text after a leading `<...>` generics list, if any.
+    fn skip_generics(text: &str) -> Option<&str> {
+        if !text.starts_with('<') {
+            return Some(text);
+        }
+
+        let mut depth = 0usize;
+        let mut previous = ' ';
+        for (i, c) in text.char_indices() {
+            match c {
+                '<' => depth += 1,
+                // `->` inside `Fn() -> T` bounds does not close a bracket
+                '>' if previous != '-' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(text[i + 1..].trim_start());
+                    }
+                }
+                _ => {}
+            }
+            previous = c;
+        }
+        None
+    }
+
+    /// Inheritance edges for `impl Trait for Type` (type to trait) and
+    /// `trait Name: Super` (trait to supertrait), matching the AST analyzer.
+    fn inheritance_dependencies(
+        &self,
+        header: &ScopeHeader,
+        line_num: usize,
+        path: &Path,
+    ) -> Vec<Dependency> {
+        let source = self
+            .current_scope
+            .iter()
+            .chain(std::iter::once(&header.name))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("::");
+
+        let (kind, parents) = match &header.trait_name {
+            Some(trait_name) => ("trait_impl", std::slice::from_ref(trait_name)),
+            None => ("supertrait", header.supertraits.as_slice()),
+        };
+
+        parents
+            .iter()
+            .map(|parent| Dependency {
+                source: PathBuf::from(&source),
+                target: PathBuf::from(parent),
+                dependency_type: DependencyType::Inheritance,
+                metadata: DependencyMetadata {
+                    line_number: Some(line_num),
+                    line_numbers: Vec::new(),
+                    description: Some(match kind {
+                        "trait_impl" => format!("{} implements {}", header.name, parent),
+                        _ => format!("{} extends {}", header.name, parent),
+                    }),
+                    context: Some(json!({
+                        "type": kind,
+                        "self_type": header.name,
+                        "trait": parent,
+                        "file": path.to_string_lossy(),
+                    })),
+                    relationships: Some(vec![match kind {
+                        "trait_impl" => {
+                            format!("Type {} implements trait {}", header.name, parent)
+                        }
+                        _ => format!("Trait {} requires trait {}", header.name, parent),
+                    }]),
+                },
+            })
+            .collect()
+    }
+
+    /// Links a method to the type whose `impl` block defines it.
+    fn method_dependency(
+        &self,
+        method: &str,
+        header: &ScopeHeader,
+        line_num: usize,
+        path: &Path,
+    ) -> Dependency {
+        let type_path = self.current_scope.join("::");
+        Dependency {
+            source: PathBuf::from(method),
+            target: PathBuf::from(&type_path),
+            dependency_type: DependencyType::Usage,
+            metadata: DependencyMetadata {
+                line_number: Some(line_num),
+                line_numbers: Vec::new(),
+                description: Some(format!("Method {} of {}", method, type_path)),
+                context: Some(json!({
+                    "type": "impl",
+                    "self_type": header.name,
+                    "trait": header.trait_name,
+                    "file": path.to_string_lossy(),
+                })),
+                relationships: Some(vec![format!("Method of type: {}", type_path)]),
+            },
+        }
+    }
+
     fn analyze_function_definition(
         &mut self,
         line: &str,
@@ -172,6 +471,7 @@ impl RustAnalyzer {
                 dependency_type: DependencyType::FunctionDefinition,
                 metadata: DependencyMetadata {
                     line_number: Some(line_num),
+                    line_numbers: Vec::new(),
                     description: Some(format!("Function definition: {}", full_path)),
                     context: Some(json!({
                         "is_public": func_info.is_public,
@@ -195,6 +495,7 @@ impl RustAnalyzer {
                         dependency_type: DependencyType::TypeUsage,
                         metadata: DependencyMetadata {
                             line_number: Some(line_num),
+                            line_numbers: Vec::new(),
                             description: Some(format!(
                                 "Parameter {} uses type {}",
                                 param.name, param_type
@@ -231,6 +532,7 @@ impl RustAnalyzer {
                     dependency_type: DependencyType::FunctionCall,
                     metadata: DependencyMetadata {
                         line_number: Some(line_num),
+                        line_numbers: Vec::new(),
                         description: Some(format!("Function call: {}", call)),
                         context: Some(json!({
                             "caller": current_function,
@@ -361,14 +663,175 @@ impl RustAnalyzer {
     }
 }
 
+impl RustAnalyzer {
+    fn function_complexities(&self, content: &str, path: &Path) -> Vec<FunctionComplexity> {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut results = Vec::new();
+
+        for (start, line) in lines.iter().enumerate() {
+            let line = Self::strip_comment(line).trim();
+            let name = match line.split("fn ").nth(1) {
+                Some(rest) if line.contains('(') => rest.split('(').next().unwrap_or("").trim(),
+                _ => continue,
+            };
+            if name.is_empty() {
+                continue;
+            }
+
+            // Find the closing brace of the function body
+            let mut depth = 0usize;
+            let mut opened = false;
+            let mut end = None;
+            for (offset, body_line) in lines[start..].iter().enumerate() {
+                let body_line = Self::strip_comment(body_line);
+                for c in body_line.chars() {
+                    match c {
+                        '{' => {
+                            depth += 1;
+                            opened = true;
+                        }
+                        '}' => depth = depth.saturating_sub(1),
+                        _ => {}
+                    }
+                }
+                if opened && depth == 0 {
+                    end = Some(start + offset);
+                    break;
+                }
+                // Declarations without a body, e.g. trait methods
+                if !opened && body_line.trim_end().ends_with(';') {
+                    break;
+                }
+            }
+
+            if let Some(end) = end {
+                let body: Vec<&str> = lines[start..=end]
+                    .iter()
+                    .map(|l| Self::strip_comment(l))
+                    .collect();
+                let branches = Self::count_branch_points(&body.join("\n"));
+                results.push(FunctionComplexity {
+                    file: path.to_path_buf(),
+                    function: name.to_string(),
+                    complexity: 1 + branches,
+                    line_range: (start, end),
+                });
+            }
+        }
+
+        results
+    }
+
+    fn strip_comment(line: &str) -> &str {
+        line.split("//").next().unwrap_or("")
+    }
+
+    /// Branch points in a function body with comments already stripped.
+    /// Counted over the whole body, so arms on their own lines are matched
+    /// up with their `match`.
+    fn count_branch_points(body: &str) -> usize {
+        let keywords = body
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|token| matches!(*token, "if" | "while" | "for"))
+            .count();
+        let matches = body
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|token| *token == "match")
+            .count();
+        // Each match arm is a branch, minus the default path of the match itself
+        let arms = body.matches("=>").count();
+
+        keywords + body.matches("&&").count() + body.matches("||").count() + arms
+            - matches.min(arms)
+    }
+}
+
 impl CodeAnalyzer for RustAnalyzer {
-    fn analyze(&self, path: &Path) -> Result<Vec<Dependency>, String> {
-        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
-        let mut analyzer = RustAnalyzer::new();
-        analyzer.analyze_file(&content, path)
+    fn analyze(&self, path: &Path) -> Result<Vec<Dependency>, AnalyzerError> {
+        let content = fs::read_to_string(path).map_err(|e| AnalyzerError::io(path, e))?;
+        let mut dependencies = if self.use_ast {
+            rust_ast::analyze_source(&content, path)
+        } else {
+            RustAnalyzer::new().analyze_file(&content, path)
+        }
+        .map_err(|message| AnalyzerError::parse(path, message))?;
+
+        resolver::resolve_rust_imports(&mut dependencies);
+        Ok(dependencies)
     }
 
     fn supported_extensions(&self) -> Vec<&'static str> {
         vec!["rs"]
     }
+
+    fn analyze_complexity(&self, path: &Path) -> Result<Vec<FunctionComplexity>, AnalyzerError> {
+        let content = fs::read_to_string(path).map_err(|e| AnalyzerError::io(path, e))?;
+        Ok(self.function_complexities(&content, path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complexity_counts_each_branch_point_once() {
+        let source = "fn classify(n: i32) -> &'static str {\n    // if only this were a for loop\n    if n < 0 && n != -1 {\n        return \"negative\";\n    }\n    match n {\n        0 => \"zero\",\n        1 => \"one\",\n        _ => \"many\",\n    }\n}\n";
+        let complexities =
+            RustAnalyzer::new().function_complexities(source, Path::new("src/lib.rs"));
+
+        assert_eq!(complexities.len(), 1);
+        assert_eq!(complexities[0].function, "classify");
+        assert_eq!(complexities[0].line_range, (0, 10));
+        // if, &&, and two extra match arms
+        assert_eq!(complexities[0].complexity, 5);
+    }
+
+    /// (method, type, trait) for every method linked to its impl block.
+    fn methods(source: &str) -> Vec<(String, String, Option<String>)> {
+        RustAnalyzer::new()
+            .analyze_file(source, Path::new("src/lib.rs"))
+            .unwrap()
+            .into_iter()
+            .filter(|dep| {
+                dep.metadata
+                    .context
+                    .as_ref()
+                    .map_or(false, |context| context["type"] == "impl")
+            })
+            .map(|dep| {
+                let context = dep.metadata.context.unwrap();
+                (
+                    dep.source.to_string_lossy().into_owned(),
+                    dep.target.to_string_lossy().into_owned(),
+                    context["trait"].as_str().map(str::to_string),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn inherent_impl_methods_belong_to_their_type() {
+        let source = "pub struct Counter {\n    count: u32,\n}\n\nimpl Counter {\n    pub fn new() -> Self {\n        Counter { count: 0 }\n    }\n\n    fn bump(&mut self) {\n        self.count += 1;\n    }\n}\n\nfn free() {}\n";
+        assert_eq!(
+            methods(source),
+            vec![
+                ("Counter::new".to_string(), "Counter".to_string(), None),
+                ("Counter::bump".to_string(), "Counter".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn trait_impl_methods_record_the_trait() {
+        let source = "mod display {\n    impl<'a> fmt::Display for &'a Wrapper<T> {\n        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {\n            write!(f, \"wrapped\")\n        }\n    }\n}\n";
+        assert_eq!(
+            methods(source),
+            vec![(
+                "display::Wrapper::fmt".to_string(),
+                "display::Wrapper".to_string(),
+                Some("fmt::Display".to_string()),
+            )]
+        );
+    }
 }
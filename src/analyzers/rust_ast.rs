@@ -0,0 +1,468 @@
+use crate::analyzers::{Dependency, DependencyMetadata, DependencyType};
+use quote::ToTokens;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+
+/// Analyzes a Rust source file by walking its `syn` AST instead of matching lines.
+pub(crate) fn analyze_source(content: &str, path: &Path) -> Result<Vec<Dependency>, String> {
+    let file = syn::parse_file(content)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+    let mut visitor = AstVisitor {
+        path,
+        scope: Vec::new(),
+        current_function: None,
+        dependencies: Vec::new(),
+    };
+    visitor.visit_file(&file);
+
+    Ok(visitor.dependencies)
+}
+
+struct AstVisitor<'a> {
+    path: &'a Path,
+    scope: Vec<String>,
+    current_function: Option<String>,
+    dependencies: Vec<Dependency>,
+}
+
+impl<'a> AstVisitor<'a> {
+    fn scoped_name(&self, name: &str) -> String {
+        self.scope
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(name))
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+
+    fn line_of<T: Spanned>(node: &T) -> usize {
+        // Spans are 1-based, the line analyzer reports 0-based line numbers
+        node.span().start().line.saturating_sub(1)
+    }
+
+    fn add_import(
+        &mut self,
+        import_path: String,
+        alias: Option<String>,
+        kind: &str,
+        line: usize,
+        is_public: bool,
+    ) {
+        self.dependencies.push(Dependency {
+            source: self.path.to_path_buf(),
+            target: PathBuf::from(&import_path),
+            dependency_type: DependencyType::Import,
+            metadata: DependencyMetadata {
+                line_number: Some(line),
+                line_numbers: Vec::new(),
+                description: Some(format!("Import: {}", import_path)),
+                context: Some(json!({
+                    "type": kind,
+                    "alias": alias,
+                    "scope": self.scope.join("::"),
+                    "is_public": is_public,
+                })),
+                relationships: Some(vec![format!(
+                    "Imported at scope: {}",
+                    self.scope.join("::")
+                )]),
+            },
+        });
+    }
+
+    fn add_function(
+        &mut self,
+        sig: &syn::Signature,
+        attrs: &[syn::Attribute],
+        is_public: bool,
+    ) -> String {
+        let full_path = self.scoped_name(&sig.ident.to_string());
+        let line = Self::line_of(&sig.ident);
+        let attributes: Vec<String> = attrs
+            .iter()
+            .map(|attr| attr.path().to_token_stream().to_string())
+            .collect();
+        let parameters: Vec<_> = sig
+            .inputs
+            .iter()
+            .filter_map(|input| match input {
+                syn::FnArg::Typed(pat_type) => Some((
+                    pat_type.pat.to_token_stream().to_string(),
+                    Self::base_type_name(&pat_type.ty),
+                )),
+                syn::FnArg::Receiver(_) => None,
+            })
+            .collect();
+
+        self.dependencies.push(Dependency {
+            source: self.path.to_path_buf(),
+            target: PathBuf::from(&full_path),
+            dependency_type: DependencyType::FunctionDefinition,
+            metadata: DependencyMetadata {
+                line_number: Some(line),
+                line_numbers: Vec::new(),
+                description: Some(format!("Function definition: {}", full_path)),
+                context: Some(json!({
+                    "is_public": is_public,
+                    "signature": sig.to_token_stream().to_string(),
+                    "parameters": parameters
+                        .iter()
+                        .map(|(name, ty)| json!({ "name": name, "param_type": ty }))
+                        .collect::<Vec<_>>(),
+                    "return_type": match &sig.output {
+                        syn::ReturnType::Type(_, ty) => Some(ty.to_token_stream().to_string()),
+                        syn::ReturnType::Default => None,
+                    },
+                    "attributes": attributes,
+                    "is_async": sig.asyncness.is_some(),
+                    "scope": self.scope.join("::"),
+                })),
+                relationships: Some(vec![
+                    format!("Defined in scope: {}", self.scope.join("::")),
+                    format!("Parameters: {}", parameters.len()),
+                ]),
+            },
+        });
+
+        for (name, param_type) in parameters {
+            if let Some(param_type) = param_type {
+                self.dependencies.push(Dependency {
+                    source: PathBuf::from(&full_path),
+                    target: PathBuf::from(&param_type),
+                    dependency_type: DependencyType::TypeUsage,
+                    metadata: DependencyMetadata {
+                        line_number: Some(line),
+                        line_numbers: Vec::new(),
+                        description: Some(format!("Parameter {} uses type {}", name, param_type)),
+                        context: Some(json!({
+                            "parameter": name,
+                            "function": full_path,
+                        })),
+                        relationships: None,
+                    },
+                });
+            }
+        }
+
+        full_path
+    }
+
+    fn add_type(&mut self, ident: &syn::Ident, kind: &str, is_public: bool) {
+        let full_path = self.scoped_name(&ident.to_string());
+        self.dependencies.push(Dependency {
+            source: self.path.to_path_buf(),
+            target: PathBuf::from(&full_path),
+            dependency_type: DependencyType::TypeDefinition,
+            metadata: DependencyMetadata {
+                line_number: Some(Self::line_of(ident)),
+                line_numbers: Vec::new(),
+                description: Some(format!("Type definition: {}", full_path)),
+                context: Some(json!({
+                    "kind": kind,
+                    "is_public": is_public,
+                    "scope": self.scope.join("::"),
+                })),
+                relationships: Some(vec![format!("Defines {} {}", kind, full_path)]),
+            },
+        });
+    }
+
+    fn add_call(&mut self, callee: String, line: usize) {
+        self.dependencies.push(Dependency {
+            source: self.path.to_path_buf(),
+            target: PathBuf::from(&callee),
+            dependency_type: DependencyType::FunctionCall,
+            metadata: DependencyMetadata {
+                line_number: Some(line),
+                line_numbers: Vec::new(),
+                description: Some(format!("Function call: {}", callee)),
+                context: Some(json!({
+                    "caller": self.current_function,
+                    "scope": self.scope.join("::"),
+                })),
+                relationships: self
+                    .current_function
+                    .as_ref()
+                    .map(|caller| vec![format!("Called by function: {}", caller)]),
+            },
+        });
+    }
+
+    /// Strips references and generics so `&mut Vec<Foo>` maps to `Vec`.
+    fn base_type_name(ty: &syn::Type) -> Option<String> {
+        match ty {
+            syn::Type::Reference(reference) => Self::base_type_name(&reference.elem),
+            syn::Type::Path(type_path) => type_path
+                .path
+                .segments
+                .last()
+                .map(|segment| segment.ident.to_string()),
+            _ => None,
+        }
+    }
+
+    fn expand_use_tree(prefix: &str, tree: &syn::UseTree, out: &mut Vec<(String, Option<String>)>) {
+        let join = |name: &str| {
+            if prefix.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}::{}", prefix, name)
+            }
+        };
+
+        match tree {
+            syn::UseTree::Path(use_path) => {
+                Self::expand_use_tree(&join(&use_path.ident.to_string()), &use_path.tree, out)
+            }
+            syn::UseTree::Name(use_name) => out.push((join(&use_name.ident.to_string()), None)),
+            syn::UseTree::Rename(rename) => out.push((
+                join(&rename.ident.to_string()),
+                Some(rename.rename.to_string()),
+            )),
+            syn::UseTree::Glob(_) => out.push((join("*"), None)),
+            syn::UseTree::Group(group) => {
+                for item in &group.items {
+                    Self::expand_use_tree(prefix, item, out);
+                }
+            }
+        }
+    }
+}
+
+impl<'a, 'ast> Visit<'ast> for AstVisitor<'a> {
+    fn visit_item_use(&mut self, node: &'ast syn::ItemUse) {
+        let mut imports = Vec::new();
+        Self::expand_use_tree("", &node.tree, &mut imports);
+
+        let is_public = matches!(node.vis, syn::Visibility::Public(_));
+        let line = Self::line_of(node);
+        for (import_path, alias) in imports {
+            self.add_import(import_path, alias, "use", line, is_public);
+        }
+    }
+
+    fn visit_item_mod(&mut self, node: &'ast syn::ItemMod) {
+        if node.content.is_none() {
+            self.add_import(
+                node.ident.to_string(),
+                None,
+                "mod",
+                Self::line_of(&node.ident),
+                matches!(node.vis, syn::Visibility::Public(_)),
+            );
+        }
+
+        self.scope.push(node.ident.to_string());
+        visit::visit_item_mod(self, node);
+        self.scope.pop();
+    }
+
+    fn visit_item_fn(&mut self, node: &'ast syn::ItemFn) {
+        let is_public = matches!(node.vis, syn::Visibility::Public(_));
+        let full_path = self.add_function(&node.sig, &node.attrs, is_public);
+
+        let previous = self.current_function.replace(full_path);
+        visit::visit_item_fn(self, node);
+        self.current_function = previous;
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast syn::ImplItemFn) {
+        let is_public = matches!(node.vis, syn::Visibility::Public(_));
+        let full_path = self.add_function(&node.sig, &node.attrs, is_public);
+
+        let previous = self.current_function.replace(full_path);
+        visit::visit_impl_item_fn(self, node);
+        self.current_function = previous;
+    }
+
+    fn visit_item_impl(&mut self, node: &'ast syn::ItemImpl) {
+        let self_type = match Self::base_type_name(&node.self_ty) {
+            Some(name) => name,
+            None => node.self_ty.to_token_stream().to_string(),
+        };
+
+        if let Some((_, trait_path, _)) = &node.trait_ {
+            let trait_name = trait_path.to_token_stream().to_string().replace(' ', "");
+            self.dependencies.push(Dependency {
+                source: PathBuf::from(self.scoped_name(&self_type)),
+                target: PathBuf::from(&trait_name),
+                dependency_type: DependencyType::Inheritance,
+                metadata: DependencyMetadata {
+                    line_number: Some(Self::line_of(node)),
+                    line_numbers: Vec::new(),
+                    description: Some(format!("{} implements {}", self_type, trait_name)),
+                    context: Some(json!({
+                        "type": "trait_impl",
+                        "self_type": self_type,
+                        "trait": trait_name,
+                        "file": self.path.to_string_lossy(),
+                    })),
+                    relationships: Some(vec![format!(
+                        "Type {} implements trait {}",
+                        self_type, trait_name
+                    )]),
+                },
+            });
+        }
+
+        self.scope.push(self_type);
+        visit::visit_item_impl(self, node);
+        self.scope.pop();
+    }
+
+    fn visit_item_struct(&mut self, node: &'ast syn::ItemStruct) {
+        self.add_type(
+            &node.ident,
+            "struct",
+            matches!(node.vis, syn::Visibility::Public(_)),
+        );
+        visit::visit_item_struct(self, node);
+    }
+
+    fn visit_item_enum(&mut self, node: &'ast syn::ItemEnum) {
+        self.add_type(
+            &node.ident,
+            "enum",
+            matches!(node.vis, syn::Visibility::Public(_)),
+        );
+        visit::visit_item_enum(self, node);
+    }
+
+    fn visit_item_trait(&mut self, node: &'ast syn::ItemTrait) {
+        self.add_type(
+            &node.ident,
+            "trait",
+            matches!(node.vis, syn::Visibility::Public(_)),
+        );
+
+        let trait_name = node.ident.to_string();
+        for bound in &node.supertraits {
+            let syn::TypeParamBound::Trait(bound) = bound else {
+                continue;
+            };
+            let supertrait = bound.path.to_token_stream().to_string().replace(' ', "");
+            self.dependencies.push(Dependency {
+                source: PathBuf::from(self.scoped_name(&trait_name)),
+                target: PathBuf::from(&supertrait),
+                dependency_type: DependencyType::Inheritance,
+                metadata: DependencyMetadata {
+                    line_number: Some(Self::line_of(&node.ident)),
+                    line_numbers: Vec::new(),
+                    description: Some(format!("{} extends {}", trait_name, supertrait)),
+                    context: Some(json!({
+                        "type": "supertrait",
+                        "self_type": trait_name,
+                        "trait": supertrait,
+                        "file": self.path.to_string_lossy(),
+                    })),
+                    relationships: Some(vec![format!(
+                        "Trait {} requires trait {}",
+                        trait_name, supertrait
+                    )]),
+                },
+            });
+        }
+
+        self.scope.push(node.ident.to_string());
+        visit::visit_item_trait(self, node);
+        self.scope.pop();
+    }
+
+    fn visit_item_type(&mut self, node: &'ast syn::ItemType) {
+        self.add_type(
+            &node.ident,
+            "type",
+            matches!(node.vis, syn::Visibility::Public(_)),
+        );
+        visit::visit_item_type(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        if let syn::Expr::Path(expr_path) = node.func.as_ref() {
+            let callee = expr_path
+                .path
+                .to_token_stream()
+                .to_string()
+                .replace(' ', "");
+            self.add_call(callee, Self::line_of(node));
+        }
+        visit::visit_expr_call(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        self.add_call(node.method.to_string(), Self::line_of(&node.method));
+        visit::visit_expr_method_call(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges(source: &str, dependency_type: DependencyType) -> Vec<(String, String)> {
+        analyze_source(source, Path::new("src/lib.rs"))
+            .unwrap()
+            .into_iter()
+            .filter(|dep| dep.dependency_type == dependency_type)
+            .map(|dep| {
+                (
+                    dep.source.to_string_lossy().into_owned(),
+                    dep.target.to_string_lossy().into_owned(),
+                )
+            })
+            .collect()
+    }
+
+    fn targets(source: &str, dependency_type: DependencyType) -> Vec<String> {
+        edges(source, dependency_type)
+            .into_iter()
+            .map(|(_, target)| target)
+            .collect()
+    }
+
+    #[test]
+    fn grouped_imports_expand_to_one_edge_each() {
+        assert_eq!(
+            targets("use std::{fmt, io};\n", DependencyType::Import),
+            vec!["std::fmt", "std::io"]
+        );
+
+        let source = "use std::{fmt, io::{Read, Write}};\nuse crate::graph::Edge as GraphEdge;\n";
+        assert_eq!(
+            targets(source, DependencyType::Import),
+            vec![
+                "std::fmt",
+                "std::io::Read",
+                "std::io::Write",
+                "crate::graph::Edge"
+            ]
+        );
+
+        let deps = analyze_source(source, Path::new("src/lib.rs")).unwrap();
+        let alias = deps
+            .iter()
+            .find(|dep| dep.target == Path::new("crate::graph::Edge"))
+            .and_then(|dep| dep.metadata.context.as_ref())
+            .map(|context| context["alias"].clone());
+        assert_eq!(alias, Some(json!("GraphEdge")));
+    }
+
+    #[test]
+    fn trait_impls_and_type_definitions_are_recorded() {
+        let source = "mod shapes {\n    pub struct Square;\n    impl std::fmt::Display for Square {\n        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { write!(f, \"square\") }\n    }\n}\n";
+        assert_eq!(
+            edges(source, DependencyType::Inheritance),
+            vec![(
+                "shapes::Square".to_string(),
+                "std::fmt::Display".to_string()
+            )]
+        );
+        assert_eq!(
+            targets(source, DependencyType::TypeDefinition),
+            vec!["shapes::Square"]
+        );
+    }
+}
@@ -2,6 +2,7 @@ use deeptracking_llamaindex::cli::CLI;
 use std::process;
 
 fn main() {
+    #[cfg(feature = "python")]
     pyo3::prepare_freethreaded_python();
 
     if let Err(e) = CLI::run() {
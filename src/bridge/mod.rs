@@ -1,11 +1,21 @@
 use crate::analyzers::manager::{AnalyzerManager, FileEntry, ProjectStructure};
-use crate::graph::DependencyGraph;
+use crate::analyzers::{AnalyzerError, DependencyType};
+use crate::graph::{DependencyGraph, DependencyMetrics, GraphError};
+use crate::progress::{AnalysisProgress, ProgressReporter};
+use crate::query::{co_changed_files, Recommender, RelatedFile};
 use pyo3::prelude::*;
 use pyo3::types::{PyDict, PyList};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// File the dependency graph is persisted to inside an index's `persist_dir`.
+const GRAPH_FILE: &str = "dependency_graph.json";
+
+/// Commits of history searched for files changed alongside another.
+const CO_CHANGE_COMMITS: usize = 200;
 
 #[derive(Debug)]
 pub struct LlamaIndexBridge {
@@ -13,6 +23,27 @@ pub struct LlamaIndexBridge {
     graph: Arc<RwLock<DependencyGraph>>,
     index: Option<PyObject>,
     storage_context: Option<PyObject>,
+    config: BridgeConfig,
+    project_structure: Option<ProjectStructure>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BridgeConfig {
+    pub chunk_size: usize,
+    pub chunk_overlap: usize,
+    pub similarity_top_k: usize,
+    pub response_mode: String,
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: 1024,
+            chunk_overlap: 128,
+            similarity_top_k: 5,
+            response_mode: "tree_summarize".to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,6 +52,10 @@ pub struct QueryResult {
     pub source_nodes: Vec<SourceNode>,
     pub project_context: ProjectContext,
     pub project_structure: ProjectStructure,
+    pub query_embedding: Option<Vec<f32>>,
+    /// The LlamaIndex response the result was built from.
+    #[serde(skip)]
+    pub raw_response: Option<PyObject>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,6 +63,7 @@ pub struct SourceNode {
     pub file_path: PathBuf,
     pub content: String,
     pub metadata: NodeMetadata,
+    pub embedding: Option<Vec<f32>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -54,25 +90,100 @@ pub struct DependencyPath {
 impl LlamaIndexBridge {
     pub fn new(root_path: PathBuf) -> Result<Self, String> {
         Ok(Self {
-            analyzer: AnalyzerManager::new(&root_path)?,
+            analyzer: AnalyzerManager::new(&root_path, None)?,
             graph: Arc::new(RwLock::new(DependencyGraph::new())),
             index: None,
             storage_context: None,
+            config: BridgeConfig::default(),
+            project_structure: None,
         })
     }
 
-    pub fn initialize(&mut self, py: Python<'_>) -> PyResult<()> {
-        // Analyze project and build dependency graph
-        let analysis_result = self
-            .analyzer
-            .analyze_project(&self.analyzer.root_path())
-            .map_err(BridgeError::AnalyzerError)?;
+    /// Read access to the graph. A poisoned lock means another thread
+    /// panicked mid-update, so it is reported rather than read from.
+    fn read_graph(&self) -> Result<RwLockReadGuard<'_, DependencyGraph>, GraphError> {
+        self.graph
+            .read()
+            .map_err(|e| GraphError::LockPoisoned(e.to_string()))
+    }
 
+    fn write_graph(&self) -> Result<RwLockWriteGuard<'_, DependencyGraph>, GraphError> {
         self.graph
             .write()
-            .map_err(|e| BridgeError::GraphError(e.to_string()))?
+            .map_err(|e| GraphError::LockPoisoned(e.to_string()))
+    }
+
+    pub fn save_graph(&self, persist_dir: &Path) -> Result<(), BridgeError> {
+        fs::create_dir_all(persist_dir).map_err(|e| BridgeError::GraphError(e.to_string()))?;
+        self.read_graph()?
+            .save(&persist_dir.join(GRAPH_FILE))
+            .map_err(BridgeError::from)
+    }
+
+    /// Restores the graph saved next to an index. Indexes persisted before the
+    /// graph was saved alongside them keep the current graph.
+    pub fn load_graph(&mut self, persist_dir: &Path) -> Result<(), BridgeError> {
+        let graph_file = persist_dir.join(GRAPH_FILE);
+        if !graph_file.exists() {
+            return Ok(());
+        }
+
+        let graph = DependencyGraph::load(&graph_file)?;
+        *self.write_graph()? = graph;
+        Ok(())
+    }
+
+    pub fn configure(&mut self, config: BridgeConfig) -> Result<(), BridgeError> {
+        if config.chunk_overlap >= config.chunk_size {
+            return Err(BridgeError::ConfigError(format!(
+                "chunk_overlap ({}) must be smaller than chunk_size ({})",
+                config.chunk_overlap, config.chunk_size
+            )));
+        }
+        if config.similarity_top_k < 1 {
+            return Err(BridgeError::ConfigError(
+                "similarity_top_k must be at least 1".to_string(),
+            ));
+        }
+
+        self.config = config;
+        Ok(())
+    }
+
+    /// Analyzes the project and builds the LlamaIndex index over it. When
+    /// given, `on_progress` is called with a dict per analysis event; the
+    /// first exception it raises is returned once analysis finishes.
+    pub fn initialize(&mut self, py: Python<'_>, on_progress: Option<PyObject>) -> PyResult<()> {
+        let callback_error: Arc<Mutex<Option<PyErr>>> = Arc::new(Mutex::new(None));
+        if let Some(on_progress) = on_progress {
+            let callback_error = Arc::clone(&callback_error);
+            self.analyzer
+                .set_progress(ProgressReporter::new(move |event| {
+                    Python::with_gil(|py| {
+                        let result = progress_to_py(py, &event)
+                            .and_then(|event| on_progress.call1(py, (event,)));
+                        if let (Err(e), Ok(mut error)) = (result, callback_error.lock()) {
+                            error.get_or_insert(e);
+                        }
+                    })
+                }));
+        }
+
+        // Analyze project and build dependency graph
+        let analysis_result = self.analyzer.analyze_project(&self.analyzer.root_path());
+        self.analyzer.set_progress(ProgressReporter::default());
+        if let Some(e) = callback_error
+            .lock()
+            .ok()
+            .and_then(|mut error| error.take())
+        {
+            return Err(e);
+        }
+        let analysis_result = analysis_result.map_err(BridgeError::AnalyzerError)?;
+
+        self.write_graph()?
             .add_dependencies(analysis_result.dependencies)
-            .map_err(BridgeError::GraphError)?;
+            .map_err(BridgeError::from)?;
 
         // Initialize LlamaIndex components
         let llama_index = py.import("llama_index")?;
@@ -83,8 +194,8 @@ impl LlamaIndexBridge {
             .call_method0("from_defaults")?;
 
         let service_context_dict = PyDict::new(py);
-        service_context_dict.set_item("chunk_size", 1024)?;
-        service_context_dict.set_item("chunk_overlap", 128)?;
+        service_context_dict.set_item("chunk_size", self.config.chunk_size)?;
+        service_context_dict.set_item("chunk_overlap", self.config.chunk_overlap)?;
 
         // Create service context
         let service_context = llama_index
@@ -105,6 +216,7 @@ impl LlamaIndexBridge {
 
         self.index = Some(index.into());
         self.storage_context = Some(storage_context.into());
+        self.project_structure = Some(analysis_result.project_structure);
 
         Ok(())
     }
@@ -117,7 +229,14 @@ impl LlamaIndexBridge {
         let llama_index = py.import("llama_index")?;
         let documents = PyList::empty(py);
 
-        for file_entry in &project_structure.files {
+        // Walk the tree depth-first, keeping the files in display order
+        let mut pending: Vec<&FileEntry> = project_structure.files.iter().rev().collect();
+        while let Some(file_entry) = pending.pop() {
+            if file_entry.file_type == "directory" {
+                pending.extend(file_entry.children.iter().rev());
+                continue;
+            }
+
             let metadata = self.create_metadata(py, file_entry)?;
 
             if let Ok(content) = std::fs::read_to_string(&file_entry.path) {
@@ -143,29 +262,34 @@ impl LlamaIndexBridge {
             metadata.set_item("size", file_metadata.size)?;
 
             // Add graph-based relationships
-            if let Ok(graph) = self.graph.read() {
-                if let Ok(relationships) = graph.get_direct_relationships(&file_entry.path) {
-                    metadata.set_item("relationships", relationships)?;
-                }
-            }
+            let relationships = self
+                .read_graph()?
+                .get_direct_relationships(&file_entry.path)?;
+            metadata.set_item("relationships", relationships)?;
         }
 
         Ok(metadata.into())
     }
 
-    pub fn query(&self, py: Python<'_>, query: String) -> PyResult<QueryResult> {
+    pub fn query(
+        &self,
+        py: Python<'_>,
+        query: String,
+        include_embeddings: bool,
+    ) -> PyResult<QueryResult> {
         let index = self.index.as_ref().ok_or_else(|| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Index not initialized")
         })?;
 
+        // Embed the raw query before it gets enhanced with relationship hints
+        let query_embedding = self.embed_query(py, index, &query, include_embeddings)?;
+
         // Enhance query with relationship context
         let enhanced_query = self
             .enhance_query(query)
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e))?;
 
-        let query_dict = PyDict::new(py);
-        query_dict.set_item("similarity_top_k", 5)?;
-        query_dict.set_item("response_mode", "tree_summarize")?;
+        let query_dict = self.query_engine_kwargs(py)?;
 
         // Create query engine
         let query_engine = index.call_method1(py, "as_query_engine", (query_dict,))?;
@@ -175,8 +299,126 @@ impl LlamaIndexBridge {
 
         // Process results
         let response_text = response.getattr(py, "response")?.extract::<String>(py)?;
+        self.build_query_result(
+            py,
+            response_text,
+            response,
+            include_embeddings,
+            query_embedding,
+        )
+    }
+
+    /// Like `query`, but hands each chunk of the response to `on_chunk` as
+    /// LlamaIndex produces it. Falls back to a single chunk with the full
+    /// response when the installed LlamaIndex can't stream.
+    pub fn query_stream(
+        &self,
+        py: Python<'_>,
+        query: String,
+        on_chunk: PyObject,
+        include_embeddings: bool,
+    ) -> PyResult<QueryResult> {
+        let index = self.index.as_ref().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Index not initialized")
+        })?;
+
+        let query_embedding = self.embed_query(py, index, &query, include_embeddings)?;
+        let enhanced_query = self
+            .enhance_query(query.clone())
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e))?;
+
+        let query_dict = self.query_engine_kwargs(py)?;
+        query_dict.set_item("streaming", true)?;
+
+        let query_engine = match index.call_method1(py, "as_query_engine", (query_dict,)) {
+            Ok(engine) => engine,
+            Err(e) if e.is_instance_of::<pyo3::exceptions::PyTypeError>(py) => {
+                return self.query_without_streaming(py, query, on_chunk, include_embeddings);
+            }
+            Err(e) => return Err(e),
+        };
+
+        let response = query_engine.call_method1(py, "query", (enhanced_query,))?;
+        let response_gen = match response.getattr(py, "response_gen") {
+            Ok(response_gen) if !response_gen.is_none(py) => response_gen,
+            _ => {
+                // Older versions ignore `streaming` and return a complete response
+                let response_text = response.getattr(py, "response")?.extract::<String>(py)?;
+                on_chunk.call1(py, (response_text.as_str(),))?;
+                return self.build_query_result(
+                    py,
+                    response_text,
+                    response,
+                    include_embeddings,
+                    query_embedding,
+                );
+            }
+        };
+
+        let mut response_text = String::new();
+        for chunk in response_gen.as_ref(py).iter()? {
+            let chunk = chunk?.extract::<String>()?;
+            on_chunk.call1(py, (chunk.as_str(),))?;
+            response_text.push_str(&chunk);
+        }
+
+        self.build_query_result(
+            py,
+            response_text,
+            response,
+            include_embeddings,
+            query_embedding,
+        )
+    }
+
+    fn query_without_streaming(
+        &self,
+        py: Python<'_>,
+        query: String,
+        on_chunk: PyObject,
+        include_embeddings: bool,
+    ) -> PyResult<QueryResult> {
+        let result = self.query(py, query, include_embeddings)?;
+        on_chunk.call1(py, (result.response.as_str(),))?;
+        Ok(result)
+    }
+
+    fn query_engine_kwargs<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let query_dict = PyDict::new(py);
+        query_dict.set_item("similarity_top_k", self.config.similarity_top_k)?;
+        query_dict.set_item("response_mode", &self.config.response_mode)?;
+        Ok(query_dict)
+    }
+
+    fn embed_query(
+        &self,
+        py: Python<'_>,
+        index: &PyObject,
+        query: &str,
+        include_embeddings: bool,
+    ) -> PyResult<Option<Vec<f32>>> {
+        if !include_embeddings {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            index
+                .getattr(py, "_embed_model")?
+                .call_method1(py, "get_query_embedding", (query,))?
+                .extract::<Vec<f32>>(py)?,
+        ))
+    }
+
+    fn build_query_result(
+        &self,
+        py: Python<'_>,
+        response_text: String,
+        response: PyObject,
+        include_embeddings: bool,
+        query_embedding: Option<Vec<f32>>,
+    ) -> PyResult<QueryResult> {
         let source_nodes = response.getattr(py, "source_nodes")?;
-        let extracted_nodes = self.extract_source_nodes(py, source_nodes)?;
+        let extracted_nodes = self.extract_source_nodes(py, source_nodes, include_embeddings)?;
         let project_context = self
             .build_project_context(&extracted_nodes)
             .map_err(BridgeError::AnalyzerError)?;
@@ -185,10 +427,15 @@ impl LlamaIndexBridge {
             response: response_text,
             source_nodes: extracted_nodes,
             project_context,
-            project_structure: ProjectStructure {
-                root: self.analyzer.root_path().to_string_lossy().to_string(),
-                files: Vec::new(),
-            },
+            project_structure: self
+                .project_structure
+                .clone()
+                .unwrap_or_else(|| ProjectStructure {
+                    root: self.analyzer.root_path().to_string_lossy().to_string(),
+                    files: Vec::new(),
+                }),
+            query_embedding,
+            raw_response: Some(response),
         })
     }
 
@@ -213,7 +460,12 @@ impl LlamaIndexBridge {
         Ok(enhanced)
     }
 
-    fn extract_source_nodes(&self, py: Python<'_>, nodes: PyObject) -> PyResult<Vec<SourceNode>> {
+    fn extract_source_nodes(
+        &self,
+        py: Python<'_>,
+        nodes: PyObject,
+        include_embeddings: bool,
+    ) -> PyResult<Vec<SourceNode>> {
         let nodes_list = nodes.extract::<Vec<PyObject>>(py)?;
         let mut source_nodes = Vec::new();
 
@@ -222,6 +474,11 @@ impl LlamaIndexBridge {
             let metadata = node
                 .getattr(py, "metadata")?
                 .extract::<HashMap<String, PyObject>>(py)?;
+            let embedding = if include_embeddings {
+                Some(self.extract_node_embedding(py, &node, &content)?)
+            } else {
+                None
+            };
 
             source_nodes.push(SourceNode {
                 file_path: PathBuf::from(
@@ -234,12 +491,34 @@ impl LlamaIndexBridge {
                 ),
                 content,
                 metadata: self.extract_node_metadata(py, &metadata)?,
+                embedding,
             });
         }
 
         Ok(source_nodes)
     }
 
+    fn extract_node_embedding(
+        &self,
+        py: Python<'_>,
+        node: &PyObject,
+        content: &str,
+    ) -> PyResult<Vec<f32>> {
+        // Retrieved nodes usually don't carry their vector, so re-embed when missing
+        let stored = node.getattr(py, "node")?.getattr(py, "embedding")?;
+        if !stored.is_none(py) {
+            return stored.extract::<Vec<f32>>(py);
+        }
+
+        let index = self.index.as_ref().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Index not initialized")
+        })?;
+        index
+            .getattr(py, "_embed_model")?
+            .call_method1(py, "get_text_embedding", (content,))?
+            .extract::<Vec<f32>>(py)
+    }
+
     fn extract_node_metadata(
         &self,
         py: Python<'_>,
@@ -270,29 +549,23 @@ impl LlamaIndexBridge {
             dependency_paths: Vec::new(),
         };
 
-        let graph = self
-            .graph
-            .read()
-            .map_err(|e| format!("Failed to acquire read lock: {}", e))?;
+        let graph = self.read_graph()?;
 
         for node in source_nodes {
             // Handle direct relationships
-            if let Ok(relationships) = graph.get_direct_relationships(&node.file_path) {
-                context.file_relationships.insert(
-                    node.file_path.to_string_lossy().to_string(),
-                    relationships.values().flatten().cloned().collect(),
-                );
-            }
+            let relationships = graph.get_direct_relationships(&node.file_path)?;
+            context.file_relationships.insert(
+                node.file_path.to_string_lossy().to_string(),
+                relationships.values().flatten().cloned().collect(),
+            );
 
             // Handle dependency paths
-            if let Ok(dependencies) = graph.get_dependencies(&node.file_path) {
-                for dep_path in dependencies {
-                    context.dependency_paths.push(DependencyPath {
-                        source: node.file_path.to_string_lossy().to_string(),
-                        target: dep_path.clone(),
-                        path: vec![node.file_path.to_string_lossy().to_string(), dep_path],
-                    });
-                }
+            for dep_path in graph.get_dependencies(&node.file_path)? {
+                context.dependency_paths.push(DependencyPath {
+                    source: node.file_path.to_string_lossy().to_string(),
+                    target: dep_path.clone(),
+                    path: vec![node.file_path.to_string_lossy().to_string(), dep_path],
+                });
             }
         }
 
@@ -304,10 +577,7 @@ impl LlamaIndexBridge {
         &self,
         file_path: &Path,
     ) -> Result<HashMap<String, Vec<String>>, String> {
-        let graph = self
-            .graph
-            .read()
-            .map_err(|e| format!("Failed to acquire read lock: {}", e))?;
+        let graph = self.read_graph()?;
         let path_buf = file_path.to_path_buf();
         let mut context = HashMap::new();
 
@@ -315,7 +585,10 @@ impl LlamaIndexBridge {
             context.insert("direct_dependencies".to_string(), deps);
         }
 
-        if let Ok(reverse_deps) = graph.get_callers(&path_buf) {
+        if let Ok(reverse_deps) = graph.get_dependents(
+            &path_buf,
+            &[DependencyType::Import, DependencyType::FunctionCall],
+        ) {
             context.insert(
                 "reverse_dependencies".to_string(),
                 reverse_deps
@@ -334,6 +607,100 @@ impl LlamaIndexBridge {
         Ok(context)
     }
 
+    pub fn get_file_metrics(&self, file_path: &Path) -> Result<DependencyMetrics, String> {
+        let path_buf = self.analyzer.root_path().join(file_path);
+        let mut metrics = self.read_graph()?.calculate_metrics(&path_buf)?;
+
+        // The graph has no notion of control flow, so ask the analyzer
+        metrics.cyclomatic_complexity = self
+            .analyzer
+            .file_complexity(&path_buf)?
+            .iter()
+            .map(|function| function.complexity)
+            .sum();
+
+        Ok(metrics)
+    }
+
+    pub fn get_relationships(
+        &self,
+        file_path: &Path,
+    ) -> Result<HashMap<String, Vec<String>>, String> {
+        let path_buf = self.analyzer.root_path().join(file_path);
+        Ok(self.read_graph()?.get_direct_relationships(&path_buf)?)
+    }
+
+    /// Shortest chain of dependencies leading from `from` to `to`, as
+    /// project-relative paths. `edge_types` limits which edges may be
+    /// followed; any edge is followed when it is `None`.
+    pub fn find_path(
+        &self,
+        from: &Path,
+        to: &Path,
+        edge_types: Option<&[DependencyType]>,
+    ) -> Result<Option<Vec<PathBuf>>, String> {
+        let root = self.analyzer.root_path();
+        let path = self
+            .read_graph()?
+            .shortest_path(&root.join(from), &root.join(to), edge_types);
+
+        Ok(path.map(|nodes| {
+            nodes
+                .into_iter()
+                .map(|node| {
+                    node.strip_prefix(&root)
+                        .map(Path::to_path_buf)
+                        .unwrap_or(node)
+                })
+                .collect()
+        }))
+    }
+
+    /// Files related to `file_path` by graph proximity, commit history and,
+    /// once an index is built, retrieval similarity to the file's content.
+    /// Paths are project-relative.
+    pub fn related_files(
+        &self,
+        py: Python<'_>,
+        file_path: &Path,
+        top_k: usize,
+    ) -> PyResult<Vec<RelatedFile>> {
+        let root = self.analyzer.root_path();
+        let file = root.join(file_path);
+
+        let mut similarities = Vec::new();
+        if let (Some(index), Ok(content)) = (&self.index, fs::read_to_string(&file)) {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("similarity_top_k", top_k)?;
+            let nodes = index
+                .call_method(py, "as_retriever", (), Some(kwargs))?
+                .call_method1(py, "retrieve", (content,))?
+                .extract::<Vec<PyObject>>(py)?;
+
+            for node in nodes {
+                let score = node.getattr(py, "score")?.extract::<Option<f32>>(py)?;
+                let metadata = node
+                    .getattr(py, "node")?
+                    .getattr(py, "metadata")?
+                    .extract::<HashMap<String, PyObject>>(py)?;
+                if let (Some(score), Some(path)) = (score, metadata.get("file_path")) {
+                    similarities.push((root.join(path.extract::<String>(py)?), score));
+                }
+            }
+        }
+
+        let mut related = Recommender::new(&*self.read_graph()?)
+            .with_similarities(similarities)
+            .with_co_changes(co_changed_files(&root, &file, CO_CHANGE_COMMITS))
+            .related_files(&file, top_k)?;
+        for entry in &mut related {
+            if let Ok(relative) = entry.path.strip_prefix(&root) {
+                entry.path = relative.to_path_buf();
+            }
+        }
+        Ok(related)
+    }
+
     fn preprocess_query(&self, query: &str) -> String {
         let mut preprocessed = query.to_string();
 
@@ -351,6 +718,28 @@ impl LlamaIndexBridge {
     }
 }
 
+fn progress_to_py(py: Python<'_>, event: &AnalysisProgress) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    match event {
+        AnalysisProgress::Walking { count } => {
+            dict.set_item("event", "walking")?;
+            dict.set_item("count", count)?;
+        }
+        AnalysisProgress::Analyzing { path } => {
+            dict.set_item("event", "analyzing")?;
+            dict.set_item("path", path.to_string_lossy().into_owned())?;
+        }
+        AnalysisProgress::Indexing { path, done, total } => {
+            dict.set_item("event", "indexing")?;
+            dict.set_item("path", path.to_string_lossy().into_owned())?;
+            dict.set_item("done", done)?;
+            dict.set_item("total", total)?;
+        }
+        AnalysisProgress::Done => dict.set_item("event", "done")?,
+    }
+    Ok(dict.into())
+}
+
 #[pyclass]
 pub struct PyLlamaIndexBridge(LlamaIndexBridge);
 
@@ -363,32 +752,127 @@ impl PyLlamaIndexBridge {
         Ok(PyLlamaIndexBridge(bridge))
     }
 
-    fn initialize(&mut self, py: Python<'_>) -> PyResult<()> {
-        self.0.initialize(py)
+    /// `on_progress` receives `{"event": ...}` dicts: `walking` with
+    /// `count`, `analyzing` with `path`, `indexing` with `path`, `done` and
+    /// `total`, and a final `done`.
+    #[pyo3(signature = (on_progress = None))]
+    fn initialize(&mut self, py: Python<'_>, on_progress: Option<PyObject>) -> PyResult<()> {
+        self.0.initialize(py, on_progress)
     }
 
-    fn query(&self, py: Python<'_>, query: String) -> PyResult<PyObject> {
-        let result = self.0.query(py, query)?;
+    #[pyo3(signature = (
+        chunk_size = 1024,
+        chunk_overlap = 128,
+        similarity_top_k = 5,
+        response_mode = String::from("tree_summarize")
+    ))]
+    fn configure(
+        &mut self,
+        chunk_size: usize,
+        chunk_overlap: usize,
+        similarity_top_k: usize,
+        response_mode: String,
+    ) -> PyResult<()> {
+        self.0.configure(BridgeConfig {
+            chunk_size,
+            chunk_overlap,
+            similarity_top_k,
+            response_mode,
+        })?;
+        Ok(())
+    }
 
-        // Convert result to Python dictionary
-        let result_dict = PyDict::new(py);
-        result_dict.set_item("response", result.response)?;
-        result_dict.set_item(
-            "source_nodes",
-            Self::source_nodes_to_py(py, &result.source_nodes)?,
-        )?;
-        result_dict.set_item(
-            "project_context",
-            Self::project_context_to_py(py, &result.project_context)?,
-        )?;
+    #[pyo3(signature = (query, include_embeddings = false, include_raw_response = false))]
+    fn query(
+        &self,
+        py: Python<'_>,
+        query: String,
+        include_embeddings: bool,
+        include_raw_response: bool,
+    ) -> PyResult<PyObject> {
+        let result = self.0.query(py, query, include_embeddings)?;
+        Self::query_result_to_py(py, &result, include_raw_response)
+    }
 
-        Ok(result_dict.into())
+    #[pyo3(signature = (query, on_chunk, include_embeddings = false))]
+    fn query_stream(
+        &self,
+        py: Python<'_>,
+        query: String,
+        on_chunk: PyObject,
+        include_embeddings: bool,
+    ) -> PyResult<PyObject> {
+        let result = self
+            .0
+            .query_stream(py, query, on_chunk, include_embeddings)?;
+        Self::query_result_to_py(py, &result, false)
+    }
+
+    fn get_file_metrics(&self, py: Python<'_>, path: String) -> PyResult<PyObject> {
+        let metrics = self
+            .0
+            .get_file_metrics(Path::new(&path))
+            .map_err(BridgeError::GraphError)?;
+        Self::metrics_to_py(py, &metrics)
+    }
+
+    fn get_relationships(&self, py: Python<'_>, path: String) -> PyResult<PyObject> {
+        let relationships = self
+            .0
+            .get_relationships(Path::new(&path))
+            .map_err(BridgeError::GraphError)?;
+        Self::relationships_to_py(py, &relationships)
+    }
+
+    /// `edge_types` names the edges to follow, e.g. `["Import"]`.
+    #[pyo3(signature = (from, to, edge_types = None))]
+    fn find_path(
+        &self,
+        from: String,
+        to: String,
+        edge_types: Option<Vec<String>>,
+    ) -> PyResult<Option<Vec<String>>> {
+        let edge_types = edge_types
+            .map(|names| Self::parse_edge_types(&names))
+            .transpose()?;
+        let path = self
+            .0
+            .find_path(Path::new(&from), Path::new(&to), edge_types.as_deref())
+            .map_err(BridgeError::GraphError)?;
+        Ok(path.map(|nodes| {
+            nodes
+                .iter()
+                .map(|node| node.to_string_lossy().into_owned())
+                .collect()
+        }))
+    }
+
+    /// List of `{path, score, reasons}` dicts, most related first.
+    #[pyo3(signature = (path, top_k = 10))]
+    fn related_files(&self, py: Python<'_>, path: String, top_k: usize) -> PyResult<PyObject> {
+        let related = self.0.related_files(py, Path::new(&path), top_k)?;
+        let list = PyList::empty(py);
+        for file in related {
+            let dict = PyDict::new(py);
+            dict.set_item("path", file.path.to_string_lossy().into_owned())?;
+            dict.set_item("score", file.score)?;
+            dict.set_item(
+                "reasons",
+                file.reasons
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+            )?;
+            list.append(dict)?;
+        }
+        Ok(list.into())
     }
 
     fn persist_index(&self, py: Python<'_>, path: String) -> PyResult<()> {
         if let Some(storage_context) = &self.0.storage_context {
-            storage_context.call_method1(py, "persist", (path,))?;
+            storage_context.call_method1(py, "persist", (path.as_str(),))?;
         }
+        self.0.save_graph(Path::new(&path))?;
         Ok(())
     }
 
@@ -396,7 +880,7 @@ impl PyLlamaIndexBridge {
         let llama_index = py.import("llama_index")?;
 
         let storage_dict = PyDict::new(py);
-        storage_dict.set_item("persist_dir", path)?;
+        storage_dict.set_item("persist_dir", path.as_str())?;
 
         // Load storage context
         let storage_context = llama_index
@@ -410,12 +894,50 @@ impl PyLlamaIndexBridge {
 
         self.0.index = Some(index.into_py(py));
         self.0.storage_context = Some(storage_context.into_py(py));
+        self.0.load_graph(Path::new(&path))?;
 
         Ok(())
     }
 }
 
 impl PyLlamaIndexBridge {
+    fn parse_edge_types(names: &[String]) -> Result<Vec<DependencyType>, BridgeError> {
+        names
+            .iter()
+            .map(|name| {
+                serde_json::from_value(serde_json::Value::String(name.clone()))
+                    .map_err(|_| BridgeError::ConfigError(format!("Unknown edge type: {}", name)))
+            })
+            .collect()
+    }
+
+    fn query_result_to_py(
+        py: Python<'_>,
+        result: &QueryResult,
+        include_raw_response: bool,
+    ) -> PyResult<PyObject> {
+        let result_dict = PyDict::new(py);
+        result_dict.set_item("response", &result.response)?;
+        result_dict.set_item(
+            "source_nodes",
+            Self::source_nodes_to_py(py, &result.source_nodes)?,
+        )?;
+        result_dict.set_item(
+            "project_context",
+            Self::project_context_to_py(py, &result.project_context, &result.project_structure)?,
+        )?;
+        if let Some(query_embedding) = &result.query_embedding {
+            result_dict.set_item("query_embedding", query_embedding)?;
+        }
+        if include_raw_response {
+            if let Some(raw_response) = &result.raw_response {
+                result_dict.set_item("raw", raw_response)?;
+            }
+        }
+
+        Ok(result_dict.into())
+    }
+
     fn source_nodes_to_py(py: Python<'_>, nodes: &[SourceNode]) -> PyResult<PyObject> {
         let list = PyList::empty(py);
 
@@ -424,6 +946,9 @@ impl PyLlamaIndexBridge {
             dict.set_item("file_path", node.file_path.to_string_lossy().to_string())?;
             dict.set_item("content", &node.content)?;
             dict.set_item("metadata", Self::metadata_to_py(py, &node.metadata)?)?;
+            if let Some(embedding) = &node.embedding {
+                dict.set_item("embedding", embedding)?;
+            }
             list.append(dict)?;
         }
 
@@ -438,7 +963,32 @@ impl PyLlamaIndexBridge {
         Ok(dict.into())
     }
 
-    fn project_context_to_py(py: Python<'_>, context: &ProjectContext) -> PyResult<PyObject> {
+    fn metrics_to_py(py: Python<'_>, metrics: &DependencyMetrics) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("incoming_dependencies", metrics.incoming_dependencies)?;
+        dict.set_item("outgoing_dependencies", metrics.outgoing_dependencies)?;
+        dict.set_item("coupling_factor", metrics.coupling_factor)?;
+        dict.set_item("cyclomatic_complexity", metrics.cyclomatic_complexity)?;
+        dict.set_item("depth_of_inheritance", metrics.depth_of_inheritance)?;
+        Ok(dict.into())
+    }
+
+    fn relationships_to_py(
+        py: Python<'_>,
+        relationships: &HashMap<String, Vec<String>>,
+    ) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        for (rel_type, targets) in relationships {
+            dict.set_item(rel_type, targets)?;
+        }
+        Ok(dict.into())
+    }
+
+    fn project_context_to_py(
+        py: Python<'_>,
+        context: &ProjectContext,
+        structure: &ProjectStructure,
+    ) -> PyResult<PyObject> {
         let dict = PyDict::new(py);
         dict.set_item("analyzed_files", context.analyzed_files)?;
 
@@ -460,6 +1010,39 @@ impl PyLlamaIndexBridge {
         }
         dict.set_item("dependency_paths", paths)?;
 
+        // Convert project structure
+        let structure_dict = PyDict::new(py);
+        structure_dict.set_item("root", &structure.root)?;
+        let files = PyList::empty(py);
+        for entry in &structure.files {
+            files.append(Self::file_entry_to_py(py, entry)?)?;
+        }
+        structure_dict.set_item("files", files)?;
+        dict.set_item("project_structure", structure_dict)?;
+
+        Ok(dict.into())
+    }
+
+    fn file_entry_to_py(py: Python<'_>, entry: &FileEntry) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("path", entry.path.to_string_lossy().to_string())?;
+        dict.set_item("file_type", &entry.file_type)?;
+
+        if let Some(metadata) = &entry.metadata {
+            let metadata_dict = PyDict::new(py);
+            metadata_dict.set_item("last_modified", metadata.last_modified.to_rfc3339())?;
+            metadata_dict.set_item("language", &metadata.language)?;
+            metadata_dict.set_item("dependencies", &metadata.dependencies)?;
+            metadata_dict.set_item("size", metadata.size)?;
+            dict.set_item("metadata", metadata_dict)?;
+        }
+
+        let children = PyList::empty(py);
+        for child in &entry.children {
+            children.append(Self::file_entry_to_py(py, child)?)?;
+        }
+        dict.set_item("children", children)?;
+
         Ok(dict.into())
     }
 }
@@ -470,6 +1053,7 @@ pub enum BridgeError {
     AnalyzerError(String),
     GraphError(String),
     PyInterfaceError(String),
+    ConfigError(String),
 }
 
 impl From<String> for BridgeError {
@@ -478,6 +1062,30 @@ impl From<String> for BridgeError {
     }
 }
 
+impl From<GraphError> for BridgeError {
+    fn from(error: GraphError) -> Self {
+        BridgeError::GraphError(error.to_string())
+    }
+}
+
+impl From<AnalyzerError> for BridgeError {
+    fn from(error: AnalyzerError) -> Self {
+        BridgeError::AnalyzerError(error.to_string())
+    }
+}
+
+impl From<GraphError> for PyErr {
+    fn from(error: GraphError) -> PyErr {
+        BridgeError::from(error).into()
+    }
+}
+
+impl From<AnalyzerError> for PyErr {
+    fn from(error: AnalyzerError) -> PyErr {
+        BridgeError::from(error).into()
+    }
+}
+
 impl From<BridgeError> for PyErr {
     fn from(error: BridgeError) -> PyErr {
         match error {
@@ -486,6 +1094,214 @@ impl From<BridgeError> for PyErr {
             BridgeError::PyInterfaceError(s) => {
                 PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(s)
             }
+            BridgeError::ConfigError(s) => PyErr::new::<pyo3::exceptions::PyValueError, _>(s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzers::DependencyMetadata;
+    use crate::graph::{Edge, NodeType};
+
+    fn edge(root: &Path, source: &str, target: &str, edge_type: DependencyType) -> Edge {
+        Edge {
+            source: root.join(source),
+            target: root.join(target),
+            edge_type,
+            metadata: DependencyMetadata::default(),
+        }
+    }
+
+    #[test]
+    fn find_path_follows_only_the_requested_edge_types() {
+        let dir = tempfile::tempdir().unwrap();
+        let bridge = LlamaIndexBridge::new(dir.path().to_path_buf()).unwrap();
+        let root = bridge.analyzer.root_path();
+        {
+            let mut graph = bridge.write_graph().unwrap();
+            for file in ["a.py", "b.py", "c.py"] {
+                graph.add_node(&root.join(file), NodeType::File).unwrap();
+            }
+            graph
+                .add_edge(edge(&root, "a.py", "b.py", DependencyType::Import))
+                .unwrap();
+            graph
+                .add_edge(edge(&root, "b.py", "c.py", DependencyType::Import))
+                .unwrap();
+            graph
+                .add_edge(edge(&root, "a.py", "c.py", DependencyType::FunctionCall))
+                .unwrap();
         }
+
+        let path = |edge_types: Option<&[DependencyType]>| {
+            bridge
+                .find_path(Path::new("a.py"), Path::new("c.py"), edge_types)
+                .unwrap()
+        };
+        assert_eq!(
+            path(None),
+            Some(vec![PathBuf::from("a.py"), PathBuf::from("c.py")])
+        );
+        assert_eq!(
+            path(Some(&[DependencyType::Import])),
+            Some(vec![
+                PathBuf::from("a.py"),
+                PathBuf::from("b.py"),
+                PathBuf::from("c.py"),
+            ])
+        );
+        assert_eq!(path(Some(&[DependencyType::Inheritance])), None);
+    }
+
+    #[test]
+    fn reloaded_graph_restores_relationships() {
+        let dir = tempfile::tempdir().unwrap();
+        let persist_dir = dir.path().join("storage");
+        let bridge = LlamaIndexBridge::new(dir.path().to_path_buf()).unwrap();
+        let root = bridge.analyzer.root_path();
+        {
+            let mut graph = bridge.write_graph().unwrap();
+            for file in ["a.py", "b.py"] {
+                graph.add_node(&root.join(file), NodeType::File).unwrap();
+            }
+            graph
+                .add_edge(edge(&root, "a.py", "b.py", DependencyType::Import))
+                .unwrap();
+        }
+        bridge.save_graph(&persist_dir).unwrap();
+
+        let mut reloaded = LlamaIndexBridge::new(dir.path().to_path_buf()).unwrap();
+        assert!(reloaded
+            .get_relationships(Path::new("a.py"))
+            .unwrap()
+            .is_empty());
+        reloaded.load_graph(&persist_dir).unwrap();
+        let relationships = reloaded.get_relationships(Path::new("a.py")).unwrap();
+        assert_eq!(
+            relationships["imports"],
+            vec![root.join("b.py").to_string_lossy().into_owned()]
+        );
+    }
+
+    /// Stands in for a LlamaIndex index whose embed model returns vectors of
+    /// 8 floats and whose query engine answers with a single source node.
+    const FAKE_INDEX: &str = r#"
+class EmbedModel:
+    def get_query_embedding(self, text):
+        return [0.5] * 8
+
+    def get_text_embedding(self, text):
+        return [0.25] * 8
+
+class StoredNode:
+    embedding = None
+
+class RetrievedNode:
+    text = "def helper(): pass"
+    metadata = {"file_path": "src/helper.py"}
+    node = StoredNode()
+
+class Response:
+    response = "answer"
+    source_nodes = [RetrievedNode()]
+
+class QueryEngine:
+    def query(self, query):
+        return Response()
+
+class Index:
+    _embed_model = EmbedModel()
+
+    def as_query_engine(self, kwargs):
+        return QueryEngine()
+"#;
+
+    #[test]
+    fn query_returns_embeddings_only_when_asked() {
+        pyo3::prepare_freethreaded_python();
+        let dir = tempfile::tempdir().unwrap();
+        let mut bridge = LlamaIndexBridge::new(dir.path().to_path_buf()).unwrap();
+
+        Python::with_gil(|py| {
+            let module =
+                PyModule::from_code(py, FAKE_INDEX, "fake_index.py", "fake_index").unwrap();
+            bridge.index = Some(
+                module
+                    .getattr("Index")
+                    .unwrap()
+                    .call0()
+                    .unwrap()
+                    .into_py(py),
+            );
+
+            let with = bridge.query(py, "where is helper".into(), true).unwrap();
+            assert_eq!(with.query_embedding.as_ref().map(Vec::len), Some(8));
+            assert_eq!(
+                with.source_nodes[0].embedding.as_ref().map(Vec::len),
+                Some(8)
+            );
+            let dict = PyLlamaIndexBridge::query_result_to_py(py, &with, false).unwrap();
+            assert!(dict.as_ref(py).contains("query_embedding").unwrap());
+
+            let without = bridge.query(py, "where is helper".into(), false).unwrap();
+            assert!(without.query_embedding.is_none());
+            assert!(without.source_nodes[0].embedding.is_none());
+            let dict = PyLlamaIndexBridge::query_result_to_py(py, &without, false).unwrap();
+            assert!(!dict.as_ref(py).contains("query_embedding").unwrap());
+        });
+    }
+
+    #[test]
+    fn raw_response_is_returned_only_when_asked() {
+        pyo3::prepare_freethreaded_python();
+        let dir = tempfile::tempdir().unwrap();
+        let mut bridge = LlamaIndexBridge::new(dir.path().to_path_buf()).unwrap();
+
+        Python::with_gil(|py| {
+            let module =
+                PyModule::from_code(py, FAKE_INDEX, "fake_index.py", "fake_index").unwrap();
+            bridge.index = Some(
+                module
+                    .getattr("Index")
+                    .unwrap()
+                    .call0()
+                    .unwrap()
+                    .into_py(py),
+            );
+            let result = bridge.query(py, "where is helper".into(), false).unwrap();
+
+            let with = PyLlamaIndexBridge::query_result_to_py(py, &result, true).unwrap();
+            let with: &PyDict = with.downcast(py).unwrap();
+            let raw = with.get_item("raw").unwrap().unwrap();
+            let response_class = module.getattr("Response").unwrap();
+            assert!(raw.is_instance(response_class).unwrap());
+            assert_eq!(
+                with.get_item("response")
+                    .unwrap()
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "answer"
+            );
+
+            let without = PyLlamaIndexBridge::query_result_to_py(py, &result, false).unwrap();
+            assert!(!without.as_ref(py).contains("raw").unwrap());
+        });
+    }
+
+    #[test]
+    fn edge_type_names_parse_like_their_serialized_form() {
+        let parsed = PyLlamaIndexBridge::parse_edge_types(&[
+            "Import".to_string(),
+            "FunctionCall".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(
+            parsed,
+            vec![DependencyType::Import, DependencyType::FunctionCall]
+        );
+        assert!(PyLlamaIndexBridge::parse_edge_types(&["Imports".to_string()]).is_err());
     }
 }
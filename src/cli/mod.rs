@@ -1,20 +1,60 @@
-use crate::analyzers::manager::{AnalyzerManager, FileEntry, ProjectStructure};
-use crate::indexing::Indexer;
-use crate::query::{QueryEngine, QueryResponse, QueryResult};
-use clap::{arg, ArgMatches, Command};
+mod server;
+mod watch;
+
+use crate::analyzers::manager::{AnalysisResult, AnalyzerManager, FileEntry, ProjectStructure};
+use crate::analyzers::DependencyType;
+use crate::graph::{DependencyGraph, Edge};
+use crate::indexing::{CodeQueryFilters, IndexSync, Indexer};
+use crate::llm::load_model;
+use crate::progress::ProgressReporter;
+use crate::query::{
+    co_changed_files, CodeContext, QueryEngine, QueryResponse, QueryResult, QuerySettings,
+    Recommender, RelatedFile, RelevanceScorer,
+};
+use clap::{arg, ArgAction, ArgGroup, ArgMatches, Command};
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
 use rustyline::history::DefaultHistory;
 use rustyline::Editor;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
 use tokio;
+use tokio_util::sync::CancellationToken;
+use watch::ProjectWatcher;
+
+const UNSTABLE_THRESHOLD: f64 = 0.7;
+const ABSTRACT_THRESHOLD: f64 = 0.7;
+const DEFAULT_REPORT_DIR: &str = ".deeptracking/reports";
+const DEFAULT_REPORT_PREFIX: &str = "query-report-";
+const RELATED_FILES_SHOWN: usize = 10;
+/// Commits of history searched for files changed alongside another.
+const CO_CHANGE_COMMITS: usize = 200;
+
+/// What re-analyzing a single file did.
+enum FileChange {
+    Ignored,
+    Removed,
+    Reindexed { dependencies: usize },
+}
 
 pub struct CLI {
     analyzer: AnalyzerManager,
     project_root: PathBuf,
     indexer: Option<Indexer>,
     query_engine: Option<QueryEngine>,
+    graph: DependencyGraph,
+    watcher: Option<ProjectWatcher>,
+    quiet: bool,
+    report_format: String,
+    /// Directory query reports are written to, relative to the project root.
+    report_dir: PathBuf,
+    report_prefix: String,
+    /// Files and directories to analyze instead of the whole project.
+    scope: Vec<PathBuf>,
+    /// Cancelled by Ctrl-C to stop analysis and indexing between files.
+    cancel: CancellationToken,
 }
 
 impl CLI {
@@ -22,11 +62,26 @@ impl CLI {
         let project_root = std::env::current_dir()
             .map_err(|e| format!("Failed to get current directory: {}", e))?;
 
+        let cancel = CancellationToken::new();
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(cancel_on_interrupt(cancel.clone()));
+        }
+        let mut analyzer = AnalyzerManager::new(&project_root, None)?;
+        analyzer.set_cancellation(cancel.clone());
+
         Ok(CLI {
-            analyzer: AnalyzerManager::new(&project_root)?,
+            analyzer,
             project_root,
             indexer: None,
             query_engine: None,
+            graph: DependencyGraph::new(),
+            watcher: None,
+            quiet: false,
+            report_format: "md".to_string(),
+            report_dir: PathBuf::from(DEFAULT_REPORT_DIR),
+            report_prefix: DEFAULT_REPORT_PREFIX.to_string(),
+            scope: Vec::new(),
+            cancel,
         })
     }
 
@@ -38,20 +93,204 @@ impl CLI {
             let matches = Command::new("deeptracking-llamaindex")
                 .version("1.0")
                 .about("Deep code analysis and semantic search tool")
+                .arg(
+                    arg!(-q --quiet "Suppress spinners, colors and decorative output")
+                        .global(true),
+                )
                 .subcommand(
                     Command::new("run")
                         .about("Run analysis and query interface")
+                        .arg(arg!([PATHS] ... "Files or directories to analyze instead of the whole project"))
                         .arg(arg!(-f --force "Force reanalysis of all files"))
-                        .arg(arg!(-m --model <PATH> "Path to LLM model"))
-                        .arg(arg!(-i --index <PATH> "Path to existing index")),
+                        .arg(arg!(-m --model <PATH> "Path to an LLM or a local embedding model directory"))
+                        .arg(
+                            arg!(--"embedding-dim" <N> "Vector length a local embedding model must produce")
+                                .value_parser(clap::value_parser!(usize)),
+                        )
+                        .arg(arg!(-i --index <PATH> "Path to existing index"))
+                        .arg(arg!(-w --watch "Re-index files as they change"))
+                        .arg(
+                            arg!(--"report-format" <FORMAT> "Format of saved query reports")
+                                .value_parser(["md", "json", "both"])
+                                .default_value("md"),
+                        )
+                        .arg(
+                            arg!(--"report-dir" <PATH> "Directory for saved query reports")
+                                .default_value(DEFAULT_REPORT_DIR),
+                        )
+                        .arg(
+                            arg!(--"report-prefix" <PREFIX> "Filename prefix for saved query reports")
+                                .default_value(DEFAULT_REPORT_PREFIX),
+                        )
+                        .arg(arg!(
+                            --"refresh-summaries" "Regenerate cached LLM summaries for every file"
+                        ))
+                        .arg(arg!(-g --graph <PATH> "Path to a saved dependency graph"))
+                        .arg(
+                            arg!(--query <TEXT> "Run a single query and exit")
+                                .conflicts_with("watch"),
+                        )
+                        .arg(
+                            arg!(--file <PATH> "Explain a single file's role and exit")
+                                .conflicts_with_all(["watch", "query"]),
+                        )
+                        .group(ArgGroup::new("single").args(["query", "file"]))
+                        .arg(
+                            arg!(-o --output <PATH> "Write the query report to a file")
+                                .requires("single"),
+                        )
+                        .arg(
+                            arg!(--format <FORMAT> "Report format for --query or --file")
+                                .value_parser(["markdown", "json"])
+                                .default_value("markdown")
+                                .requires("single"),
+                        )
+                        .arg(
+                            arg!(--exclude <GLOB> "Exclude files matching glob from results")
+                                .action(ArgAction::Append),
+                        )
+                        .arg(
+                            arg!(--lang <LANGS> "Only retrieve code in these languages, e.g. rust,python")
+                                .value_delimiter(','),
+                        )
+                        .arg(
+                            arg!(--"path-glob" <GLOB> "Only retrieve files matching glob, e.g. \"src/api/**\"")
+                                .action(ArgAction::Append)
+                                .value_parser(parse_glob),
+                        )
+                        .arg(
+                            arg!(--rel <TYPES> "Only retrieve code with these relationships, e.g. calls,imports")
+                                .value_delimiter(','),
+                        )
+                        .arg(
+                            arg!(--"top-k" <N> "Maximum number of code results per query")
+                                .value_parser(clap::value_parser!(usize)),
+                        )
+                        .arg(
+                            arg!(--"min-similarity" <SCORE> "Minimum similarity (0.0-1.0) for results")
+                                .value_parser(parse_similarity),
+                        )
+                        .arg(arg!(
+                            --"no-relationships" "Skip relationship context when answering"
+                        ))
+                        .arg(arg!(
+                            --rerank "Have the model rescore retrieved code before answering"
+                        ))
+                        .arg(
+                            arg!(--"context-window" <N> "Lines of context around each result")
+                                .value_parser(clap::value_parser!(usize)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("analyze")
+                        .about("Analyze the project without starting the query interface")
+                        .arg(arg!([PATHS] ... "Files or directories to analyze instead of the whole project"))
+                        .arg(arg!(--complexity "Report cyclomatic complexity per function"))
+                        .arg(arg!(--matrix "Emit the dependency adjacency matrix as CSV"))
+                        .arg(arg!(--instability "Report coupling and instability per module"))
+                        .arg(
+                            arg!(--"top-files" <N> "Report the N most central files")
+                                .value_parser(clap::value_parser!(usize)),
+                        )
+                        .arg(
+                            arg!(--sparse "Emit the matrix as row,col,weight triplets")
+                                .requires("matrix"),
+                        )
+                        .arg(
+                            arg!(--format <FORMAT> "Output format for the project report")
+                                .value_parser(["text", "json", "dot"])
+                                .default_value("text"),
+                        )
+                        .arg(arg!(--metrics "Include per-file dependency metrics"))
+                        .arg(arg!(--cycles "Include import cycles between files"))
+                        .arg(arg!(--unresolved "List imports that don't resolve to a project file"))
+                        .arg(arg!(-o --output <PATH> "Write the report to a file")),
+                )
+                .subcommand(
+                    Command::new("graph")
+                        .about("Work with the dependency graph")
+                        .subcommand_required(true)
+                        .subcommand(
+                            Command::new("export")
+                                .about("Export the dependency graph for external tools")
+                                .arg(
+                                    arg!(--format <FORMAT> "Export format")
+                                        .value_parser(["json", "graphml", "dot"])
+                                        .default_value("json"),
+                                )
+                                .arg(arg!(
+                                    -g --graph <PATH> "Export a saved graph instead of analyzing"
+                                ))
+                                .arg(arg!(-o --output <PATH> "Write the export to a file")),
+                        ),
+                )
+                .subcommand(
+                    Command::new("coverage-map")
+                        .about("Map source files to their tests and list untested files")
+                        .arg(arg!(--json "Print the full mapping as JSON")),
+                )
+                .subcommand(
+                    Command::new("validate-index")
+                        .about("Check that the index, graph and state agree with the files on disk")
+                        .arg(arg!(-i --index <PATH> "Path to existing index").required(true))
+                        .arg(arg!(-g --graph <PATH> "Path to a saved dependency graph"))
+                        .arg(arg!(--fix "Remove orphaned entries and index missing files")),
+                )
+                .subcommand(
+                    Command::new("serve")
+                        .about("Answer line-delimited JSON-RPC requests on stdin/stdout")
+                        .arg(arg!(-m --model <PATH> "Path to an LLM or a local embedding model directory"))
+                        .arg(
+                            arg!(--"embedding-dim" <N> "Vector length a local embedding model must produce")
+                                .value_parser(clap::value_parser!(usize)),
+                        )
+                        .arg(arg!(-i --index <PATH> "Path to existing index"))
+                        .arg(arg!(-g --graph <PATH> "Path to a saved dependency graph")),
                 )
                 .get_matches();
 
+            // Escape codes corrupt logs, so color is only used on a terminal
+            let quiet = matches.get_flag("quiet");
+            if quiet || !std::io::stdout().is_terminal() {
+                colored::control::set_override(false);
+            }
+
             match matches.subcommand() {
                 Some(("run", sub_matches)) => {
                     let mut cli = CLI::new()?;
+                    cli.quiet = quiet;
                     cli.handle_run(sub_matches).await
                 }
+                Some(("analyze", sub_matches)) => {
+                    let mut cli = CLI::new()?;
+                    cli.quiet = quiet;
+                    cli.handle_analyze(sub_matches)
+                }
+                Some(("graph", sub_matches)) => {
+                    let mut cli = CLI::new()?;
+                    cli.quiet = quiet;
+                    match sub_matches.subcommand() {
+                        Some(("export", export_matches)) => cli.handle_graph_export(export_matches),
+                        _ => Err("Unknown graph command".to_string()),
+                    }
+                }
+                Some(("coverage-map", sub_matches)) => {
+                    let mut cli = CLI::new()?;
+                    cli.quiet = quiet;
+                    cli.handle_coverage_map(sub_matches)
+                }
+                Some(("validate-index", sub_matches)) => {
+                    let mut cli = CLI::new()?;
+                    cli.quiet = quiet;
+                    cli.handle_validate_index(sub_matches).await
+                }
+                Some(("serve", sub_matches)) => {
+                    let mut cli = CLI::new()?;
+                    // stdout is the protocol channel
+                    cli.quiet = true;
+                    colored::control::set_override(false);
+                    cli.handle_serve(sub_matches).await
+                }
                 _ => {
                     println!(
                         "Use 'deeptracking-llamaindex run' to start the analysis and query interface"
@@ -65,9 +304,47 @@ impl CLI {
     async fn handle_run(&mut self, matches: &ArgMatches) -> Result<(), String> {
         let force_analysis = matches.get_flag("force");
         let model_path = matches.get_one::<String>("model");
+        let embedding_dim = matches.get_one::<usize>("embedding-dim").copied();
         let index_path = matches.get_one::<String>("index");
+        let watch = matches.get_flag("watch");
+        let graph_path = matches.get_one::<String>("graph");
+        let single_query = matches.get_one::<String>("query");
+        let explain_path = matches.get_one::<String>("file");
+        self.scope = scope_paths(matches);
+        if let Some(report_format) = matches.get_one::<String>("report-format") {
+            self.report_format = report_format.clone();
+        }
+        if let Some(report_dir) = matches.get_one::<String>("report-dir") {
+            self.report_dir = PathBuf::from(report_dir);
+        }
+        if let Some(report_prefix) = matches.get_one::<String>("report-prefix") {
+            self.report_prefix = report_prefix.clone();
+        }
+        self.analyzer
+            .add_output_path(&self.project_root.join(&self.report_dir));
+        if let Some(output) = matches.get_one::<String>("output") {
+            self.analyzer.add_output_path(Path::new(output));
+        }
+        let exclude = matches
+            .get_many::<String>("exclude")
+            .unwrap_or_default()
+            .map(|glob| {
+                glob::Pattern::new(glob)
+                    .map_err(|e| format!("Invalid exclude pattern '{}': {}", glob, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let settings = query_settings(matches, exclude);
+
+        // Single queries feed pipelines, so stdout carries only the report
+        if single_query.is_some() || explain_path.is_some() {
+            self.quiet = true;
+        }
 
-        let spinner = ProgressBar::new_spinner();
+        let spinner = if self.quiet {
+            ProgressBar::hidden()
+        } else {
+            ProgressBar::new_spinner()
+        };
         spinner.set_style(
             ProgressStyle::default_spinner()
                 .template("{spinner:.green} {msg}")
@@ -76,35 +353,89 @@ impl CLI {
 
         // Initialize indexer
         spinner.set_message("Initializing indexer...");
-        self.indexer = Some(Indexer::new()?);
+        self.indexer = Some(Indexer::new()?.with_summary_cache(
+            self.project_root.join(".deeptracking-summaries.json"),
+            matches.get_flag("refresh-summaries"),
+        ));
 
-        // Load existing index or perform new analysis
+        if let Some(graph_path) = graph_path {
+            spinner.set_message("Loading dependency graph...");
+            self.graph = DependencyGraph::load(Path::new(graph_path))?;
+        }
+
+        // Load an existing index so only files changed since it was saved
+        // need embedding again
         if let Some(index_path) = index_path {
             spinner.set_message("Loading existing index...");
             if let Some(indexer) = &mut self.indexer {
                 indexer.load(PathBuf::from(index_path)).await?;
+                indexer.load_index_state(&index_state_path(index_path))?;
             }
-        } else {
-            // Perform new analysis
-            spinner.set_message("Analyzing project structure and dependencies...");
-            let analysis_result = self.analyzer.analyze_project(&self.project_root)?;
+        }
 
-            // Index all files
-            spinner.set_message("Indexing files...");
-            if let Some(indexer) = &mut self.indexer {
-                for file_entry in &analysis_result.project_structure.files {
-                    indexer.index_file(file_entry).await?;
-                }
-            }
+        spinner.set_message("Analyzing project and indexing changed files...");
+        let progress_spinner = spinner.clone();
+        self.set_progress(ProgressReporter::new(move |event| {
+            progress_spinner.set_message(event.to_string())
+        }));
+        let sync = self
+            .refresh_project(index_path.map(String::as_str), graph_path.is_none())
+            .await;
+        self.set_progress(ProgressReporter::default());
+        let sync = sync?;
+        if let (Some(sync), false) = (sync, self.quiet) {
+            spinner.println(format!(
+                "{} files re-indexed, {} removed, {} unchanged",
+                sync.reindexed, sync.removed, sync.unchanged
+            ));
+        }
+
+        if watch {
+            spinner.set_message("Watching project for changes...");
+            self.watcher = Some(ProjectWatcher::new(&self.project_root, &["rs", "py"])?);
         }
 
         // Initialize query engine
         spinner.set_message("Initializing query engine...");
         if let Some(indexer) = &self.indexer {
-            self.query_engine = Some(QueryEngine::new(
-                indexer.clone(),
-                model_path.map(PathBuf::from),
-            )?);
+            self.query_engine = Some(
+                QueryEngine::new(
+                    indexer.clone(),
+                    model_path
+                        .map(|path| load_model(Path::new(path), embedding_dim))
+                        .transpose()?,
+                )?
+                .with_settings(settings),
+            );
+        }
+
+        if let Some(query) = single_query {
+            spinner.finish_and_clear();
+            let output_path = matches.get_one::<String>("output");
+            let format = matches
+                .get_one::<String>("format")
+                .map(String::as_str)
+                .unwrap_or("markdown");
+            return self.run_single_query(query, output_path, format).await;
+        }
+
+        if let Some(path) = explain_path {
+            spinner.finish_and_clear();
+            let response = self.explain_file(path).await?;
+            let report = match matches.get_one::<String>("format").map(String::as_str) {
+                Some("json") => serde_json::to_string_pretty(&response)
+                    .map_err(|e| format!("Failed to serialize explanation: {}", e))?,
+                _ => Self::format_explain_report(&response),
+            };
+            return match matches.get_one::<String>("output") {
+                Some(output) => {
+                    fs::write(output, report).map_err(|e| format!("Failed to write report: {}", e))
+                }
+                None => {
+                    println!("{}", report);
+                    Ok(())
+                }
+            };
         }
 
         spinner.finish_with_message("Analysis complete! Starting query interface...");
@@ -113,56 +444,628 @@ impl CLI {
         self.run_query_interface().await
     }
 
-    async fn run_query_interface(&mut self) -> Result<(), String> {
-        let mut rl = Editor::<(), DefaultHistory>::new()
-            .map_err(|e| format!("Failed to create line editor: {}", e))?;
-
-        println!("\n{}", "DeepTracking Code Query Interface".green().bold());
-        println!("{}", "Enter your query or 'exit' to quit".cyan());
-        println!("Commands:".cyan());
-        println!("  :save <path> - Save current index");
-        println!("  :load <path> - Load index from file");
-        println!("  :help       - Show this help");
+    fn set_progress(&mut self, progress: ProgressReporter) {
+        if let Some(indexer) = &mut self.indexer {
+            indexer.set_progress(progress.clone());
+        }
+        self.analyzer.set_progress(progress);
+    }
+
+    /// Analyzes the project and re-indexes whatever changed, saving the
+    /// index to `index_path` when given. The graph is rebuilt from the new
+    /// dependencies only when `rebuild_graph` is set, so a loaded graph stays
+    /// as saved.
+    async fn refresh_project(
+        &mut self,
+        index_path: Option<&str>,
+        rebuild_graph: bool,
+    ) -> Result<Option<IndexSync>, String> {
+        let analysis_result = self.analyze_scope()?;
+
+        let mut sync = None;
+        if let Some(indexer) = &mut self.indexer {
+            indexer.set_cancellation(self.cancel.clone());
+            let synced = indexer
+                .sync_with_state(
+                    &self.project_root,
+                    self.analyzer.project_state(),
+                    &analysis_result.project_structure.files,
+                )
+                .await;
+
+            // Saved even when cancelled, keeping the files indexed so far
+            indexer.save_summaries()?;
+            if let Some(index_path) = index_path {
+                indexer.save(PathBuf::from(index_path)).await?;
+                indexer.save_index_state(&index_state_path(index_path))?;
+            }
+            sync = Some(synced?);
+        }
+        if rebuild_graph {
+            self.graph = DependencyGraph::new();
+            self.graph.add_dependencies(analysis_result.dependencies)?;
+            self.graph.resolve_calls();
+        }
+
+        Ok(sync)
+    }
+
+    /// Answers one query without the REPL, writing the report to `output_path`
+    /// or stdout. Any failure is returned so the process exits non-zero.
+    async fn run_single_query(
+        &self,
+        query: &str,
+        output_path: Option<&String>,
+        format: &str,
+    ) -> Result<(), String> {
+        let result = self.execute_query(query).await?;
+        let report = match format {
+            "json" => serde_json::to_string_pretty(&result)
+                .map_err(|e| format!("Failed to serialize query result: {}", e))?,
+            _ => self.format_query_report(&result),
+        };
+
+        match output_path {
+            Some(path) => {
+                fs::write(path, report).map_err(|e| format!("Failed to write report: {}", e))?
+            }
+            None => println!("{}", report),
+        }
+
+        Ok(())
+    }
+
+    /// Analyzes the paths given on the command line, or the whole project
+    /// when none were.
+    fn analyze_scope(&mut self) -> Result<AnalysisResult, String> {
+        if self.scope.is_empty() {
+            self.analyzer.analyze_project_parallel(&self.project_root)
+        } else {
+            self.analyzer.analyze_paths(&self.scope)
+        }
+    }
+
+    fn handle_analyze(&mut self, matches: &ArgMatches) -> Result<(), String> {
+        let output_path = matches.get_one::<String>("output");
+        if let Some(output) = output_path {
+            self.analyzer.add_output_path(Path::new(output));
+        }
+        self.scope = scope_paths(matches);
+
+        let report = if matches.get_flag("complexity") {
+            let complexities = if self.scope.is_empty() {
+                self.analyzer.analyze_complexity(&self.project_root)?
+            } else {
+                self.analyzer.analyze_paths_complexity(&self.scope)?
+            };
+            serde_json::to_string_pretty(&complexities)
+                .map_err(|e| format!("Failed to serialize complexity report: {}", e))?
+        } else if matches.get_flag("instability") {
+            self.analyze_scope()?;
+            self.graph
+                .add_dependencies(self.analyzer.resolved_dependencies())?;
+            let modules = self.graph.module_instability(&self.project_root);
+
+            // Unstable abstractions have no implementers to justify them
+            for module in &modules {
+                if module.instability >= UNSTABLE_THRESHOLD
+                    && module.abstractness >= ABSTRACT_THRESHOLD
+                {
+                    eprintln!(
+                        "{}: {} is both unstable (I={:.2}) and abstract (A={:.2})",
+                        "Warning".yellow().bold(),
+                        module.module.display(),
+                        module.instability,
+                        module.abstractness
+                    );
+                }
+            }
+
+            serde_json::to_string_pretty(&modules)
+                .map_err(|e| format!("Failed to serialize instability report: {}", e))?
+        } else if let Some(&top) = matches.get_one::<usize>("top-files") {
+            self.analyze_scope()?;
+            self.graph
+                .add_dependencies(self.analyzer.resolved_dependencies())?;
+            self.graph.resolve_calls();
+
+            let pagerank = self.graph.pagerank(0.85, 50);
+            let betweenness = self.graph.betweenness_centrality();
+            let mut files: Vec<(&PathBuf, f32)> = pagerank
+                .iter()
+                .filter(|(path, _)| path.is_file())
+                .map(|(path, rank)| (path, *rank))
+                .collect();
+            files.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+            let report: Vec<serde_json::Value> = files
+                .into_iter()
+                .take(top)
+                .map(|(path, rank)| {
+                    serde_json::json!({
+                        "file": path.strip_prefix(&self.project_root).unwrap_or(path),
+                        "pagerank": rank,
+                        "betweenness": betweenness.get(path).copied().unwrap_or(0.0),
+                    })
+                })
+                .collect();
+            serde_json::to_string_pretty(&report)
+                .map_err(|e| format!("Failed to serialize centrality report: {}", e))?
+        } else if matches.get_flag("unresolved") {
+            self.analyze_scope()?;
+            self.graph
+                .add_dependencies(self.analyzer.resolved_dependencies())?;
+            self.format_unresolved_report(matches)?
+        } else if matches.get_flag("matrix") {
+            self.analyze_scope()?;
+            self.graph.add_dependencies(self.analyzer.dependencies())?;
+            if matches.get_flag("sparse") {
+                let (nodes, triplets) = self.graph.to_adjacency_triplets();
+                format_sparse_matrix(&nodes, &triplets)
+            } else {
+                let (nodes, matrix) = self.graph.to_adjacency_matrix();
+                format_dense_matrix(&nodes, &matrix)
+            }
+        } else {
+            self.format_project_report(matches)?
+        };
+
+        match output_path {
+            Some(path) => {
+                fs::write(path, report).map_err(|e| format!("Failed to write report: {}", e))?;
+                println!("Report written to: {}", path.bright_green());
+            }
+            None => println!("{}", report),
+        }
+
+        Ok(())
+    }
+
+    fn format_unresolved_report(&self, matches: &ArgMatches) -> Result<String, String> {
+        let unresolved: Vec<(PathBuf, String)> = self
+            .graph
+            .unresolved_targets()
+            .into_iter()
+            .map(|(source, target)| {
+                let source = source
+                    .strip_prefix(&self.project_root)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(source);
+                (source, target)
+            })
+            .collect();
+
+        if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+            let report: Vec<serde_json::Value> = unresolved
+                .iter()
+                .map(|(source, target)| serde_json::json!({ "source": source, "target": target }))
+                .collect();
+            return serde_json::to_string_pretty(&report)
+                .map_err(|e| format!("Failed to serialize unresolved imports: {}", e));
+        }
+
+        let files: HashSet<&PathBuf> = unresolved.iter().map(|(source, _)| source).collect();
+        let mut report = format!(
+            "Unresolved imports: {} in {} files\n",
+            unresolved.len(),
+            files.len()
+        );
+        for (source, target) in &unresolved {
+            report.push_str(&format!("  {}: {}\n", source.display(), target));
+        }
+        Ok(report)
+    }
+
+    fn handle_graph_export(&mut self, matches: &ArgMatches) -> Result<(), String> {
+        match matches.get_one::<String>("graph") {
+            Some(graph_path) => self.graph = DependencyGraph::load(Path::new(graph_path))?,
+            None => {
+                if let Some(output) = matches.get_one::<String>("output") {
+                    self.analyzer.add_output_path(Path::new(output));
+                }
+                self.analyzer.analyze_project(&self.project_root)?;
+                self.graph
+                    .add_dependencies(self.analyzer.resolved_dependencies())?;
+                self.graph.resolve_calls();
+            }
+        }
+
+        let export = match matches.get_one::<String>("format").map(String::as_str) {
+            Some("graphml") => self.graph.to_graphml(),
+            Some("dot") => self.graph.to_dot(),
+            _ => serde_json::to_string_pretty(&self.graph.to_json())
+                .map_err(|e| format!("Failed to serialize graph: {}", e))?,
+        };
+
+        match matches.get_one::<String>("output") {
+            Some(path) => {
+                fs::write(path, export).map_err(|e| format!("Failed to write export: {}", e))?;
+                if !self.quiet {
+                    println!("Graph exported to: {}", path.bright_green());
+                }
+            }
+            None => println!("{}", export),
+        }
+
+        Ok(())
+    }
+
+    fn handle_coverage_map(&mut self, matches: &ArgMatches) -> Result<(), String> {
+        self.analyzer.analyze_project(&self.project_root)?;
+        let coverage = self.analyzer.test_coverage();
+
+        if matches.get_flag("json") {
+            let report = serde_json::to_string_pretty(&coverage)
+                .map_err(|e| format!("Failed to serialize coverage map: {}", e))?;
+            println!("{}", report);
+            return Ok(());
+        }
+
+        for path in &coverage.untested {
+            println!("{} {}", "untested:".yellow(), path.display());
+        }
         println!(
-            "{}",
-            "------------------------------------------------".cyan()
+            "{} of {} source files have tests",
+            coverage.tested.len(),
+            coverage.tested.len() + coverage.untested.len()
         );
 
+        Ok(())
+    }
+
+    /// Project tree, metrics and cycles straight from the analyzers; never
+    /// loads a model or builds an index.
+    fn format_project_report(&mut self, matches: &ArgMatches) -> Result<String, String> {
+        let analysis_result = self.analyze_scope()?;
+        self.graph
+            .add_dependencies(self.analyzer.resolved_dependencies())?;
+        self.graph.resolve_calls();
+
+        let format = matches
+            .get_one::<String>("format")
+            .map(String::as_str)
+            .unwrap_or("text");
+        if format == "dot" {
+            return Ok(self.graph.to_dot());
+        }
+
+        let mut files: Vec<PathBuf> = self.analyzer.analyzed_files().cloned().collect();
+        files.sort();
+        let metrics = if matches.get_flag("metrics") {
+            files
+                .iter()
+                .map(|path| {
+                    let metrics = self.graph.calculate_metrics(path)?;
+                    let relative = path.strip_prefix(&self.project_root).unwrap_or(path);
+                    Ok((relative.to_path_buf(), metrics))
+                })
+                .collect::<Result<Vec<_>, String>>()?
+        } else {
+            Vec::new()
+        };
+        let cycles = if matches.get_flag("cycles") {
+            self.graph.find_cycles(&[DependencyType::Import])
+        } else {
+            Vec::new()
+        };
+
+        if format == "json" {
+            let report = serde_json::json!({
+                "project_structure": analysis_result.project_structure,
+                "dependency_count": analysis_result.dependencies.len(),
+                "metrics": metrics
+                    .iter()
+                    .map(|(path, metrics)| (path.to_string_lossy().into_owned(), metrics))
+                    .collect::<HashMap<_, _>>(),
+                "cycles": cycles,
+                "timed_out": analysis_result.timed_out,
+                "depth_limited": analysis_result.depth_limited,
+            });
+            return serde_json::to_string_pretty(&report)
+                .map_err(|e| format!("Failed to serialize project report: {}", e));
+        }
+
+        let mut report = String::new();
+        self.format_project_structure(&analysis_result.project_structure, &mut report);
+        report.push_str(&format!(
+            "\nAnalyzed {} files, found {} dependencies\n",
+            files.len(),
+            analysis_result.dependencies.len()
+        ));
+
+        if !metrics.is_empty() {
+            report.push_str("\nMetrics (in / out / coupling):\n");
+            for (path, metrics) in &metrics {
+                report.push_str(&format!(
+                    "  {}: {} / {} / {:.2}\n",
+                    path.display(),
+                    metrics.incoming_dependencies,
+                    metrics.outgoing_dependencies,
+                    metrics.coupling_factor
+                ));
+            }
+        }
+
+        if matches.get_flag("cycles") {
+            report.push_str(&format!("\nImport cycles: {}\n", cycles.len()));
+            for cycle in &cycles {
+                let members: Vec<String> = cycle
+                    .iter()
+                    .map(|path| {
+                        path.strip_prefix(&self.project_root)
+                            .unwrap_or(path)
+                            .display()
+                            .to_string()
+                    })
+                    .collect();
+                report.push_str(&format!("  {}\n", members.join(" <-> ")));
+            }
+        }
+
+        for path in &analysis_result.timed_out {
+            report.push_str(&format!("Timed out: {}\n", path.display()));
+        }
+        for path in &analysis_result.depth_limited {
+            report.push_str(&format!("Max depth reached, skipped: {}\n", path.display()));
+        }
+
+        Ok(report)
+    }
+
+    async fn handle_validate_index(&mut self, matches: &ArgMatches) -> Result<(), String> {
+        let index_path = matches
+            .get_one::<String>("index")
+            .ok_or("An index path is required")?;
+        let graph_path = matches.get_one::<String>("graph");
+        let fix = matches.get_flag("fix");
+
+        let mut indexer = Indexer::new()?;
+        indexer.load(PathBuf::from(index_path)).await?;
+        if let Some(graph_path) = graph_path {
+            self.graph = DependencyGraph::load(Path::new(graph_path))?;
+        }
+
+        let indexed: HashSet<PathBuf> = indexer
+            .indexed_paths()
+            .map(|path| self.project_root.join(path))
+            .collect();
+
+        // Vectors left behind for files that were deleted
+        let orphaned_vectors: Vec<PathBuf> = indexed
+            .iter()
+            .filter(|path| !path.exists())
+            .cloned()
+            .collect();
+
+        // Analyzed files that never made it into the index
+        let unindexed: Vec<PathBuf> = self
+            .analyzer
+            .analyzed_files()
+            .filter(|path| path.exists() && !indexed.contains(*path))
+            .cloned()
+            .collect();
+
+        let stale_state: Vec<PathBuf> = self
+            .analyzer
+            .analyzed_files()
+            .filter(|path| !path.exists())
+            .cloned()
+            .collect();
+
+        let missing_nodes = self.graph.missing_file_nodes(&self.project_root);
+
+        for path in &orphaned_vectors {
+            println!("orphaned vector: {}", path.display());
+        }
+        for path in &unindexed {
+            println!("missing vector: {}", path.display());
+        }
+        for path in &stale_state {
+            println!("stale state entry: {}", path.display());
+        }
+        for path in &missing_nodes {
+            println!("missing graph file: {}", path.display());
+        }
+
+        let issues =
+            orphaned_vectors.len() + unindexed.len() + stale_state.len() + missing_nodes.len();
+        if issues == 0 {
+            println!("Index is consistent");
+            return Ok(());
+        }
+
+        if !fix {
+            return Err(format!(
+                "Found {} inconsistencies, rerun with --fix to repair",
+                issues
+            ));
+        }
+
+        for path in &orphaned_vectors {
+            let relative_path = path.strip_prefix(&self.project_root).unwrap_or(path);
+            indexer.remove_file(relative_path).await?;
+        }
+        for path in &unindexed {
+            let relative_path = path.strip_prefix(&self.project_root).unwrap_or(path);
+            if let Some(entry) = self.analyzer.create_file_entry(path, relative_path) {
+                indexer.index_file(&entry).await?;
+            }
+        }
+        indexer.save(PathBuf::from(index_path)).await?;
+
+        self.analyzer.prune_missing_files()?;
+
+        if let Some(graph_path) = graph_path {
+            for key in &missing_nodes {
+                self.graph.remove_node(key)?;
+            }
+            self.graph.save(Path::new(graph_path))?;
+        }
+
+        println!("Fixed {} inconsistencies", issues);
+        Ok(())
+    }
+
+    async fn run_query_interface(&mut self) -> Result<(), String> {
+        if !self.quiet {
+            println!("\n{}", "DeepTracking Code Query Interface".green().bold());
+            println!("{}", "Enter your query or 'exit' to quit".cyan());
+            println!("Commands:".cyan());
+            println!("  :save <path> - Save current index");
+            println!("  :load <path> - Load index from file");
+            println!("  :save-graph <path> - Save dependency graph");
+            println!("  :load-graph <path> - Load dependency graph");
+            println!("  :stats      - Show analysis and index statistics");
+            println!("  :explain <path> - Explain a file's role in the project");
+            println!("  :dry-run <query> - Show the prompt and context a query would use");
+            println!("  :related <path> - List files related to a file");
+            println!("  :help       - Show this help");
+            println!(
+                "{}",
+                "------------------------------------------------".cyan()
+            );
+        }
+
+        // Reading a line blocks, so the editor lives on its own thread and the
+        // loop below interleaves queries with file change notifications
+        let (line_tx, mut line_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel::<()>();
+        std::thread::spawn(move || {
+            let mut rl = match Editor::<(), DefaultHistory>::new() {
+                Ok(rl) => rl,
+                Err(e) => {
+                    let _ = line_tx.send(Err(format!("Failed to create line editor: {}", e)));
+                    return;
+                }
+            };
+
+            loop {
+                let readline = rl.readline("query> ").map_err(|e| e.to_string());
+                if let Ok(line) = &readline {
+                    if !line.trim().is_empty() {
+                        let _ = rl.add_history_entry(line.trim());
+                    }
+                }
+
+                let stop = readline.is_err();
+                if line_tx.send(readline).is_err() || stop {
+                    break;
+                }
+
+                // Don't prompt again until the previous input has been handled
+                if ready_rx.recv().is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut watcher = self.watcher.take();
         loop {
-            let readline = rl.readline("query> ");
-            match readline {
-                Ok(line) => {
+            tokio::select! {
+                readline = line_rx.recv() => {
+                    let line = match readline {
+                        Some(Ok(line)) => line,
+                        Some(Err(err)) => {
+                            println!("Error: {}", err);
+                            break;
+                        }
+                        None => break,
+                    };
+
                     let input = line.trim();
                     if input.eq_ignore_ascii_case("exit") {
                         break;
                     }
 
-                    if !input.is_empty() {
-                        rl.add_history_entry(input);
-
+                    if input.starts_with(':') {
                         // Handle special commands
-                        if input.starts_with(':') {
-                            self.handle_command(input).await?;
-                            continue;
-                        }
-
+                        self.handle_command(input).await?;
+                    } else if !input.is_empty() {
                         // Execute query
                         match self.execute_query(input).await {
                             Ok(result) => self.generate_query_report(&result)?,
                             Err(e) => println!("{}: {}", "Error".red().bold(), e),
                         }
                     }
+
+                    let _ = ready_tx.send(());
                 }
-                Err(err) => {
-                    println!("Error: {}", err);
-                    break;
+                Some(changes) = watch::next_changes(&mut watcher) => {
+                    for path in changes {
+                        if let Err(e) = self.handle_file_change(&path).await {
+                            println!("{}: {}", "Error".red().bold(), e);
+                        }
+                    }
                 }
             }
         }
+        self.watcher = watcher;
 
         Ok(())
     }
 
+    async fn handle_file_change(&mut self, path: &Path) -> Result<(), String> {
+        let relative_path = path.strip_prefix(&self.project_root).unwrap_or(path);
+        match self.apply_file_change(path).await? {
+            FileChange::Ignored => {}
+            FileChange::Removed => println!("removed {}", relative_path.display()),
+            FileChange::Reindexed { dependencies } => println!(
+                "re-indexed {} ({} deps)",
+                relative_path.display(),
+                dependencies
+            ),
+        }
+        Ok(())
+    }
+
+    /// Reanalyzes a single changed file, patching the graph and the index
+    /// in place. Deleted files are dropped from both.
+    async fn apply_file_change(&mut self, path: &Path) -> Result<FileChange, String> {
+        if self.analyzer.is_ignored(path) {
+            return Ok(FileChange::Ignored);
+        }
+
+        let relative_path = path
+            .strip_prefix(&self.project_root)
+            .unwrap_or(path)
+            .to_path_buf();
+
+        if !path.exists() {
+            self.analyzer.analyze_file(path)?;
+            self.graph.remove_node(&path.to_path_buf())?;
+            if let Some(indexer) = &mut self.indexer {
+                indexer.remove_file(&relative_path).await?;
+            }
+            return Ok(FileChange::Removed);
+        }
+
+        let deps = self.analyzer.analyze_file(path)?;
+        let dep_count = deps.len();
+
+        // Scoped sources such as `function:foo` are replaced alongside the file
+        let mut edges_by_source: HashMap<PathBuf, Vec<Edge>> = HashMap::new();
+        edges_by_source.entry(path.to_path_buf()).or_default();
+        for dep in deps {
+            edges_by_source
+                .entry(dep.source.clone())
+                .or_default()
+                .push(Edge::from(dep));
+        }
+        for (source, edges) in edges_by_source {
+            self.graph.replace_edges_for_source(&source, edges)?;
+        }
+
+        if let Some(entry) = self.analyzer.create_file_entry(path, &relative_path) {
+            if let Some(indexer) = &mut self.indexer {
+                indexer.index_file(&entry).await?;
+            }
+        }
+
+        Ok(FileChange::Reindexed {
+            dependencies: dep_count,
+        })
+    }
+
     async fn handle_command(&mut self, command: &str) -> Result<(), String> {
         let parts: Vec<&str> = command.splitn(2, ' ').collect();
         match parts[0] {
@@ -186,10 +1089,92 @@ impl CLI {
                     println!("Index loaded from: {}", parts[1]);
                 }
             }
+            ":save-graph" => {
+                if parts.len() != 2 {
+                    println!("Usage: :save-graph <path>");
+                    return Ok(());
+                }
+                self.graph.save(Path::new(parts[1]))?;
+                println!("Graph saved to: {}", parts[1]);
+            }
+            ":load-graph" => {
+                if parts.len() != 2 {
+                    println!("Usage: :load-graph <path>");
+                    return Ok(());
+                }
+                self.graph = DependencyGraph::load(Path::new(parts[1]))?;
+                println!("Graph loaded from: {}", parts[1]);
+            }
+            ":stats" => self.print_stats(),
+            ":explain" => {
+                if parts.len() != 2 {
+                    println!("Usage: :explain <path>");
+                    return Ok(());
+                }
+                let response = self.explain_file(parts[1].trim()).await?;
+                println!("{}", Self::format_explain_report(&response));
+            }
+            ":related" => {
+                if parts.len() != 2 {
+                    println!("Usage: :related <path>");
+                    return Ok(());
+                }
+                let related = self
+                    .related_files(parts[1].trim(), RELATED_FILES_SHOWN)
+                    .await?;
+                if related.is_empty() {
+                    println!("No related files found");
+                }
+                for file in related {
+                    let reasons: Vec<String> =
+                        file.reasons.iter().map(ToString::to_string).collect();
+                    println!(
+                        "  {} ({:.2}) - {}",
+                        file.path
+                            .strip_prefix(&self.project_root)
+                            .unwrap_or(&file.path)
+                            .display(),
+                        file.score,
+                        reasons.join("; ")
+                    );
+                }
+            }
+            ":dry-run" => {
+                if parts.len() != 2 {
+                    println!("Usage: :dry-run <query>");
+                    return Ok(());
+                }
+                let engine = self
+                    .query_engine
+                    .as_ref()
+                    .ok_or_else(|| "Query engine not initialized".to_string())?;
+                let dry_run = engine.explain(parts[1].trim()).await?;
+
+                println!("{}", "Selected code contexts:".bold());
+                for context in &dry_run.code_contexts {
+                    println!(
+                        "  {} ({:.2})",
+                        context.file_path.display(),
+                        context.similarity_score
+                    );
+                }
+                println!("\n{}\n{}", "Prompt:".bold(), dry_run.prompt);
+                println!(
+                    "Prompt size: ~{} tokens, {} characters",
+                    dry_run.prompt_tokens,
+                    dry_run.prompt.len()
+                );
+            }
             ":help" => {
                 println!("Available commands:");
                 println!("  :save <path> - Save current index");
                 println!("  :load <path> - Load index from file");
+                println!("  :save-graph <path> - Save dependency graph");
+                println!("  :load-graph <path> - Load dependency graph");
+                println!("  :stats      - Show analysis and index statistics");
+                println!("  :explain <path> - Explain a file's role in the project");
+                println!("  :dry-run <query> - Show the prompt and context a query would use");
+                println!("  :related <path> - List files related to a file");
                 println!("  :help       - Show this help");
                 println!("  exit        - Exit the program");
             }
@@ -198,20 +1183,214 @@ impl CLI {
         Ok(())
     }
 
-    async fn execute_query(&self, query: &str) -> Result<QueryResult, String> {
-        if let Some(engine) = &self.query_engine {
-            engine.query(query).await
+    fn print_stats(&self) {
+        let mut by_type: BTreeMap<String, usize> = BTreeMap::new();
+        let dependencies = self.analyzer.dependencies();
+        for dep in &dependencies {
+            *by_type
+                .entry(format!("{:?}", dep.dependency_type))
+                .or_default() += 1;
+        }
+        let cycles = self.graph.find_cycles(&[DependencyType::Import]);
+
+        println!("\n{}", "Statistics".green().bold());
+        println!(
+            "  {} {}",
+            "Files analyzed:".cyan(),
+            self.analyzer.analyzed_files().count()
+        );
+        println!("  {} {}", "Dependencies:".cyan(), dependencies.len());
+        for (dependency_type, count) in &by_type {
+            println!("    {}: {}", dependency_type, count);
+        }
+        println!(
+            "  {} {} nodes, {} edges",
+            "Graph:".cyan(),
+            self.graph.node_count(),
+            self.graph.edge_count()
+        );
+        let cycle_count = if cycles.is_empty() {
+            "0".normal()
         } else {
-            Err("Query engine not initialized".to_string())
+            cycles.len().to_string().yellow()
+        };
+        println!("  {} {}", "Import cycles:".cyan(), cycle_count);
+        match &self.indexer {
+            Some(indexer) => println!(
+                "  {} {} chunks from {} files",
+                "Index:".cyan(),
+                indexer.chunk_count(),
+                indexer.indexed_paths().count()
+            ),
+            None => println!("  {} not built", "Index:".cyan()),
+        }
+    }
+
+    /// Files related to `path` by graph proximity, indexed content similarity
+    /// and commit history.
+    async fn related_files(&self, path: &str, top_k: usize) -> Result<Vec<RelatedFile>, String> {
+        let file = self.project_root.join(path);
+        let similarities = match (&self.query_engine, fs::read_to_string(&file)) {
+            (Some(engine), Ok(content)) => engine
+                .similar_files(&content)
+                .await?
+                .into_iter()
+                .map(|(path, similarity)| (self.project_root.join(path), similarity))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Recommender::new(&self.graph)
+            .with_similarities(similarities)
+            .with_co_changes(co_changed_files(
+                &self.project_root,
+                &file,
+                CO_CHANGE_COMMITS,
+            ))
+            .related_files(&file, top_k)
+            .map_err(String::from)
+    }
+
+    /// Gathers `path`'s content, direct and indirect relationships and
+    /// dependents from the graph and asks the model to explain the file.
+    async fn explain_file(&self, path: &str) -> Result<QueryResponse, String> {
+        let engine = self
+            .query_engine
+            .as_ref()
+            .ok_or_else(|| "Query engine not initialized".to_string())?;
+        let file = self.project_root.join(path);
+        let content = fs::read_to_string(&file)
+            .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+
+        let mut relationships = self.graph.get_direct_relationships(&file)?;
+        let dependents = self.graph.get_dependents(
+            &file,
+            &[DependencyType::Import, DependencyType::FunctionCall],
+        )?;
+        if !dependents.is_empty() {
+            relationships.insert(
+                "dependents".to_string(),
+                dependents
+                    .iter()
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .collect(),
+            );
         }
+        let indirect: Vec<String> = self
+            .graph
+            .get_indirect_relationships(&file)?
+            .into_iter()
+            .filter(|rel| rel.depth > 0)
+            .map(|rel| {
+                let inner = rel.intermediate_nodes.len().saturating_sub(1);
+                let via: Vec<String> = rel
+                    .intermediate_nodes
+                    .iter()
+                    .take(inner)
+                    .skip(1)
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .collect();
+                format!("{} (via {})", rel.path, via.join(" → "))
+            })
+            .collect();
+        if !indirect.is_empty() {
+            relationships.insert("indirect".to_string(), indirect);
+        }
+
+        let language = file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| match ext {
+                "rs" => "rust".to_string(),
+                "py" => "python".to_string(),
+                other => other.to_string(),
+            });
+
+        engine
+            .explain_file(CodeContext {
+                file_path: PathBuf::from(path),
+                content,
+                language,
+                relationships,
+                similarity_score: 1.0,
+            })
+            .await
     }
 
+    fn format_explain_report(response: &QueryResponse) -> String {
+        let mut content = String::new();
+        content.push_str("# File Explanation\n\n");
+        content.push_str(&format!("## Query\n\n{}\n\n", response.query));
+        content.push_str(&format!("## Response\n\n{}\n\n", response.response));
+
+        content.push_str("## Relationship Summary\n\n");
+        for context in &response.code_contexts {
+            let mut relationships: Vec<_> = context.relationships.iter().collect();
+            relationships.sort();
+            if relationships.is_empty() {
+                content.push_str("No relationships found.\n");
+            }
+            for (rel_type, targets) in relationships {
+                content.push_str(&format!("- {}: {}\n", rel_type, targets.join(", ")));
+            }
+            content.push_str("\n");
+        }
+
+        content
+    }
+
+    /// Runs the query, then reorders its code contexts so central and
+    /// recently changed files come first among similar matches.
+    async fn execute_query(&self, query: &str) -> Result<QueryResult, String> {
+        let engine = self
+            .query_engine
+            .as_ref()
+            .ok_or_else(|| "Query engine not initialized".to_string())?;
+
+        let mut result = engine.query(query).await?;
+        RelevanceScorer::new(
+            &self.graph,
+            self.analyzer.project_state(),
+            self.project_root.clone(),
+        )
+        .with_weights(engine.settings().relevance.clone())
+        .rank(&mut result.code_contexts);
+        Ok(result)
+    }
+
+    /// Writes the result as `<report-dir>/<prefix>N.{md,json}` per
+    /// `--report-format`; both formats share the same report number.
     fn generate_query_report(&self, result: &QueryResult) -> Result<(), String> {
-        let report_number = self.get_next_report_number()?;
-        let report_path = self
-            .project_root
-            .join(format!(".deeptracking-query-report-{}.md", report_number));
+        let report_dir = self.project_root.join(&self.report_dir);
+        let report_number = self.get_next_report_number(&report_dir)?;
 
+        let mut reports = Vec::new();
+        if self.report_format != "json" {
+            reports.push(("md", self.format_query_report(result)));
+        }
+        if self.report_format != "md" {
+            let json = serde_json::to_string_pretty(result)
+                .map_err(|e| format!("Failed to serialize query report: {}", e))?;
+            reports.push(("json", json));
+        }
+
+        if !self.quiet {
+            println!("\n{}", "Report generated:".green().bold());
+        }
+        for (extension, content) in reports {
+            let report_path = report_dir.join(format!(
+                "{}{}.{}",
+                self.report_prefix, report_number, extension
+            ));
+            fs::write(&report_path, content)
+                .map_err(|e| format!("Failed to write report: {}", e))?;
+            println!("→ {}", report_path.display());
+        }
+
+        Ok(())
+    }
+
+    fn format_query_report(&self, result: &QueryResult) -> String {
         let mut content = String::new();
 
         // Add project structure
@@ -283,34 +1462,28 @@ impl CLI {
             }
         }
 
-        fs::write(&report_path, content).map_err(|e| format!("Failed to write report: {}", e))?;
-
-        println!("\n{}", "Report generated:".green().bold());
-        println!("→ {}", report_path.display());
-
-        Ok(())
+        content
     }
 
-    fn get_next_report_number(&self) -> Result<u32, String> {
+    /// Next free report number in `report_dir`, which is created if missing.
+    fn get_next_report_number(&self, report_dir: &Path) -> Result<u32, String> {
+        fs::create_dir_all(report_dir)
+            .map_err(|e| format!("Failed to create {}: {}", report_dir.display(), e))?;
         let mut highest = 0;
 
-        for entry in fs::read_dir(&self.project_root)
-            .map_err(|e| format!("Failed to read directory: {}", e))?
+        for entry in
+            fs::read_dir(report_dir).map_err(|e| format!("Failed to read directory: {}", e))?
         {
             let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
             let filename = entry.file_name();
             let filename_str = filename.to_string_lossy();
 
-            if filename_str.starts_with(".deeptracking-query-report-")
-                && filename_str.ends_with(".md")
+            if let Some(num_str) = filename_str
+                .strip_prefix(self.report_prefix.as_str())
+                .and_then(|s| s.strip_suffix(".md").or_else(|| s.strip_suffix(".json")))
             {
-                if let Some(num_str) = filename_str
-                    .strip_prefix(".deeptracking-query-report-")
-                    .and_then(|s| s.strip_suffix(".md"))
-                {
-                    if let Ok(num) = num_str.parse::<u32>() {
-                        highest = highest.max(num);
-                    }
+                if let Ok(num) = num_str.parse::<u32>() {
+                    highest = highest.max(num);
                 }
             }
         }
@@ -319,42 +1492,167 @@ impl CLI {
     }
 }
 
-fn format_project_structure(&self, structure: &ProjectStructure, content: &mut String) {
-    for entry in &structure.files {
-        self.format_entry(entry, 0, content);
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
     }
 }
 
-fn format_entry(&self, entry: &FileEntry, depth: usize, content: &mut String) {
-    let indent = "  ".repeat(depth);
-    if entry.file_type == "directory" {
-        content.push_str(&format!("{}📁 {}/\n", indent, entry.path.display()));
-        for child in &entry.children {
-            self.format_entry(child, depth + 1, content);
+fn format_dense_matrix(nodes: &[PathBuf], matrix: &[Vec<u32>]) -> String {
+    let mut content = String::new();
+    for node in nodes {
+        content.push(',');
+        content.push_str(&csv_field(&node.to_string_lossy()));
+    }
+    content.push('\n');
+
+    for (node, row) in nodes.iter().zip(matrix) {
+        content.push_str(&csv_field(&node.to_string_lossy()));
+        for weight in row {
+            content.push_str(&format!(",{}", weight));
         }
+        content.push('\n');
+    }
+
+    content
+}
+
+fn format_sparse_matrix(nodes: &[PathBuf], triplets: &[(usize, usize, u32)]) -> String {
+    let mut content = String::from("source,target,weight\n");
+    for (row, col, weight) in triplets {
+        content.push_str(&format!(
+            "{},{},{}\n",
+            csv_field(&nodes[*row].to_string_lossy()),
+            csv_field(&nodes[*col].to_string_lossy()),
+            weight
+        ));
+    }
+
+    content
+}
+
+/// Paths given to `run` or `analyze` to limit analysis to.
+fn scope_paths(matches: &ArgMatches) -> Vec<PathBuf> {
+    matches
+        .get_many::<String>("PATHS")
+        .unwrap_or_default()
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// File hashes saved next to an index so unchanged files are not re-embedded.
+fn index_state_path(index_path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.state.json", index_path))
+}
+
+/// Query settings from `run`'s flags, falling back to the defaults.
+fn query_settings(matches: &ArgMatches, exclude: Vec<glob::Pattern>) -> QuerySettings {
+    let defaults = QuerySettings::default();
+    QuerySettings {
+        max_results: matches
+            .get_one::<usize>("top-k")
+            .copied()
+            .unwrap_or(defaults.max_results),
+        similarity_threshold: matches
+            .get_one::<f32>("min-similarity")
+            .copied()
+            .unwrap_or(defaults.similarity_threshold),
+        include_relationships: !matches.get_flag("no-relationships"),
+        rerank: matches.get_flag("rerank"),
+        context_window: matches
+            .get_one::<usize>("context-window")
+            .copied()
+            .unwrap_or(defaults.context_window),
+        exclude,
+        filters: CodeQueryFilters {
+            languages: list_arg(matches, "lang"),
+            path_glob: list_arg(matches, "path-glob"),
+            relationship_types: list_arg(matches, "rel"),
+            ..Default::default()
+        },
+        ..defaults
+    }
+}
+
+fn list_arg(matches: &ArgMatches, id: &str) -> Option<Vec<String>> {
+    matches
+        .get_many::<String>(id)
+        .map(|values| values.cloned().collect())
+}
+
+/// Rejects malformed globs at parse time; the store matches the text.
+fn parse_glob(value: &str) -> Result<String, String> {
+    glob::Pattern::new(value)
+        .map(|_| value.to_string())
+        .map_err(|e| format!("invalid glob: {}", e))
+}
+
+fn parse_similarity(value: &str) -> Result<f32, String> {
+    let score: f32 = value
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", value))?;
+    if (0.0..=1.0).contains(&score) {
+        Ok(score)
     } else {
-        let icon = match entry.file_type.as_str() {
-            "rs" => "🦀",
-            "py" => "🐍",
-            _ => "📄",
-        };
+        Err(format!(
+            "similarity must be between 0.0 and 1.0, got {}",
+            score
+        ))
+    }
+}
 
-        content.push_str(&format!("{}{} {}", indent, icon, entry.path.display()));
+/// The first Ctrl-C cancels `cancel`, so analysis and indexing stop after the
+/// current file with their progress saved. A second one exits at once.
+async fn cancel_on_interrupt(cancel: CancellationToken) {
+    while tokio::signal::ctrl_c().await.is_ok() {
+        if cancel.is_cancelled() {
+            std::process::exit(130);
+        }
+        eprintln!("Stopping after the current file, press Ctrl-C again to exit now");
+        cancel.cancel();
+    }
+}
 
-        // Add file metadata if available
-        if let Some(metadata) = &entry.metadata {
-            if let Some(lang) = &metadata.language {
-                content.push_str(&format!(" ({})", lang));
+impl CLI {
+    fn format_project_structure(&self, structure: &ProjectStructure, content: &mut String) {
+        for entry in &structure.files {
+            self.format_entry(entry, 0, content);
+        }
+    }
+
+    fn format_entry(&self, entry: &FileEntry, depth: usize, content: &mut String) {
+        let indent = "  ".repeat(depth);
+        if entry.file_type == "directory" {
+            content.push_str(&format!("{}📁 {}/\n", indent, entry.path.display()));
+            for child in &entry.children {
+                self.format_entry(child, depth + 1, content);
             }
-            if !metadata.dependencies.is_empty() {
-                content.push_str("\n");
-                content.push_str(&format!(
-                    "{}  Dependencies: {}\n",
-                    indent,
-                    metadata.dependencies.join(", ")
-                ));
+        } else {
+            let icon = match entry.file_type.as_str() {
+                "rs" => "🦀",
+                "py" => "🐍",
+                _ => "📄",
+            };
+
+            content.push_str(&format!("{}{} {}", indent, icon, entry.path.display()));
+
+            // Add file metadata if available
+            if let Some(metadata) = &entry.metadata {
+                if let Some(lang) = &metadata.language {
+                    content.push_str(&format!(" ({})", lang));
+                }
+                if !metadata.dependencies.is_empty() {
+                    content.push_str("\n");
+                    content.push_str(&format!(
+                        "{}  Dependencies: {}\n",
+                        indent,
+                        metadata.dependencies.join(", ")
+                    ));
+                }
             }
+            content.push_str("\n");
         }
-        content.push_str("\n");
     }
 }
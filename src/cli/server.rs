@@ -0,0 +1,226 @@
+use super::{FileChange, CLI};
+use crate::graph::DependencyGraph;
+use crate::indexing::Indexer;
+use crate::llm::load_model;
+use crate::query::{QueryEngine, QueryResult};
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Absent for notifications, which get no response.
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        Self::new(SERVER_ERROR, message)
+    }
+}
+
+impl Response {
+    fn new(id: Value, outcome: Result<Value, RpcError>) -> Self {
+        let (result, error) = match outcome {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result,
+            error,
+        }
+    }
+}
+
+/// Reads a string parameter by name, or by position when `params` is an
+/// array.
+fn string_param<'a>(params: &'a Value, index: usize, name: &str) -> Result<&'a str, RpcError> {
+    let value = match params {
+        Value::Array(values) => values.get(index),
+        Value::Object(values) => values.get(name),
+        _ => None,
+    };
+    value.and_then(Value::as_str).ok_or_else(|| {
+        RpcError::new(
+            INVALID_PARAMS,
+            format!("Missing string parameter '{}'", name),
+        )
+    })
+}
+
+fn paths_to_json(paths: &[PathBuf]) -> Value {
+    paths
+        .iter()
+        .map(|path| Value::String(path.to_string_lossy().into_owned()))
+        .collect()
+}
+
+impl CLI {
+    /// Serves requests from stdin until it closes. Each line is one JSON-RPC
+    /// request and each response is written as one line on stdout, so the
+    /// analysis, index and model stay loaded between requests.
+    pub(super) async fn handle_serve(&mut self, matches: &ArgMatches) -> Result<(), String> {
+        let model_path = matches.get_one::<String>("model");
+        let embedding_dim = matches.get_one::<usize>("embedding-dim").copied();
+        let index_path = matches.get_one::<String>("index").cloned();
+        let graph_path = matches.get_one::<String>("graph");
+
+        let mut indexer = Indexer::new()?;
+        if let Some(index_path) = &index_path {
+            indexer.load(PathBuf::from(index_path)).await?;
+            indexer.load_index_state(&super::index_state_path(index_path))?;
+        }
+        self.indexer = Some(indexer);
+
+        if let Some(graph_path) = graph_path {
+            self.graph = DependencyGraph::load(Path::new(graph_path))?;
+        }
+        self.refresh_project(index_path.as_deref(), graph_path.is_none())
+            .await?;
+
+        if let Some(indexer) = &self.indexer {
+            self.query_engine = Some(QueryEngine::new(
+                indexer.clone(),
+                model_path
+                    .map(|path| load_model(Path::new(path), embedding_dim))
+                    .transpose()?,
+            )?);
+        }
+
+        let stdin = std::io::stdin();
+        let mut stdout = std::io::stdout();
+        for line in stdin.lock().lines() {
+            let line = line.map_err(|e| format!("Failed to read request: {}", e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) if request.jsonrpc != "2.0" => Some(Response::new(
+                    request.id.unwrap_or(Value::Null),
+                    Err(RpcError::new(INVALID_REQUEST, "Expected jsonrpc \"2.0\"")),
+                )),
+                Ok(request) => {
+                    let outcome = self
+                        .dispatch(&request.method, &request.params, index_path.as_deref())
+                        .await;
+                    request.id.map(|id| Response::new(id, outcome))
+                }
+                Err(e) => Some(Response::new(
+                    Value::Null,
+                    Err(RpcError::new(PARSE_ERROR, e.to_string())),
+                )),
+            };
+
+            if let Some(response) = response {
+                let encoded = serde_json::to_string(&response)
+                    .map_err(|e| format!("Failed to encode response: {}", e))?;
+                writeln!(stdout, "{}", encoded)
+                    .and_then(|_| stdout.flush())
+                    .map_err(|e| format!("Failed to write response: {}", e))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn dispatch(
+        &mut self,
+        method: &str,
+        params: &Value,
+        index_path: Option<&str>,
+    ) -> Result<Value, RpcError> {
+        match method {
+            "analyze" => {
+                let sync = self.refresh_project(index_path, true).await?;
+                Ok(json!({
+                    "nodes": self.graph.node_count(),
+                    "edges": self.graph.edge_count(),
+                    "reindexed": sync.map(|sync| sync.reindexed),
+                    "removed": sync.map(|sync| sync.removed),
+                    "unchanged": sync.map(|sync| sync.unchanged),
+                }))
+            }
+            "query" => {
+                let query = string_param(params, 0, "query")?;
+                let result: QueryResult = self.execute_query(query).await?;
+                serde_json::to_value(result)
+                    .map_err(|e| RpcError::from(format!("Failed to encode result: {}", e)))
+            }
+            "getDependencies" => {
+                let path = self.project_root.join(string_param(params, 0, "path")?);
+                let dependencies = self.graph.get_dependencies(&path).map_err(String::from)?;
+                Ok(json!(dependencies))
+            }
+            "getCallers" => {
+                let path = self.project_root.join(string_param(params, 0, "path")?);
+                let callers = self.graph.get_callers(&path).map_err(String::from)?;
+                Ok(paths_to_json(&callers))
+            }
+            "findPath" => {
+                let from = self.project_root.join(string_param(params, 0, "from")?);
+                let to = self.project_root.join(string_param(params, 1, "to")?);
+                Ok(self
+                    .graph
+                    .shortest_path(&from, &to, None)
+                    .map_or(Value::Null, |path| paths_to_json(&path)))
+            }
+            "reindexFile" => {
+                let path = self.project_root.join(string_param(params, 0, "path")?);
+                let status = match self.apply_file_change(&path).await? {
+                    FileChange::Ignored => json!({ "status": "ignored" }),
+                    FileChange::Removed => json!({ "status": "removed" }),
+                    FileChange::Reindexed { dependencies } => {
+                        json!({ "status": "reindexed", "dependencies": dependencies })
+                    }
+                };
+                Ok(status)
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method '{}'", method),
+            )),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches the project tree and reports changed source files once saves have
+/// settled for the debounce window.
+pub struct ProjectWatcher {
+    _watcher: RecommendedWatcher,
+    changes: UnboundedReceiver<Vec<PathBuf>>,
+}
+
+impl ProjectWatcher {
+    pub fn new(root: &Path, extensions: &[&str]) -> Result<Self, String> {
+        let extensions: Vec<String> = extensions.iter().map(|ext| ext.to_string()).collect();
+        let (event_tx, event_rx) = mpsc::channel::<PathBuf>();
+
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            let event = match res {
+                Ok(event) => event,
+                Err(_) => return,
+            };
+            if !matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) {
+                return;
+            }
+
+            for path in event.paths {
+                let is_source = path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(|ext| extensions.iter().any(|e| e == ext))
+                    .unwrap_or(false);
+                if is_source {
+                    let _ = event_tx.send(path);
+                }
+            }
+        })
+        .map_err(|e| format!("Failed to create file watcher: {}", e))?;
+
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch {}: {}", root.display(), e))?;
+
+        let (change_tx, changes) = unbounded_channel();
+        thread::spawn(move || {
+            while let Ok(first) = event_rx.recv() {
+                let mut pending = HashSet::from([first]);
+
+                // Keep collecting until no event arrived for the debounce window
+                while let Ok(path) = event_rx.recv_timeout(DEBOUNCE) {
+                    pending.insert(path);
+                }
+
+                if change_tx.send(pending.into_iter().collect()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    pub async fn next_changes(&mut self) -> Option<Vec<PathBuf>> {
+        self.changes.recv().await
+    }
+}
+
+/// Resolves to the next batch of changes, or never when watching is disabled.
+pub async fn next_changes(watcher: &mut Option<ProjectWatcher>) -> Option<Vec<PathBuf>> {
+    match watcher {
+        Some(watcher) => watcher.next_changes().await,
+        None => std::future::pending().await,
+    }
+}
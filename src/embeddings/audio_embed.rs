@@ -2,9 +2,10 @@ use super::{EmbeddingError, EmbeddingGenerator, EmbeddingMetadata};
 use crate::indexing::base::audio::AudioAnalysis;
 use crate::llm::Model;
 use async_trait::async_trait;
+use rustfft::num_complex::Complex;
 use rustfft::FftPlanner;
 use std::f32::consts::PI;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone)]
 pub struct AudioEmbedding {
@@ -24,7 +25,7 @@ pub struct AudioFeatures {
 pub struct AudioEmbeddingGenerator {
     llm: Arc<dyn Model>,
     config: AudioEmbeddingConfig,
-    fft_planner: FftPlanner<f32>,
+    fft_planner: Mutex<FftPlanner<f32>>,
 }
 
 #[derive(Debug, Clone)]
@@ -63,7 +64,7 @@ impl AudioEmbeddingGenerator {
         Self {
             llm,
             config,
-            fft_planner: FftPlanner::new(),
+            fft_planner: Mutex::new(FftPlanner::new()),
         }
     }
 
@@ -77,7 +78,7 @@ impl AudioEmbeddingGenerator {
         let stft = self.compute_stft(&analysis.waveform.samples)?;
 
         // 2. Generate mel-spectrogram
-        let mel_spec = self.compute_mel_spectrogram(&stft)?;
+        let mel_spec = self.compute_mel_spectrogram(&stft, analysis.waveform.sample_rate)?;
 
         // 3. Extract MFCCs
         let mfccs = self.compute_mfcc(&mel_spec)?;
@@ -154,17 +155,136 @@ impl AudioEmbeddingGenerator {
     }
 
     // Helper methods for feature computation
+
+    /// Hann-windowed frames of `window_size` samples every `hop_size`,
+    /// keeping the `window_size / 2 + 1` non-negative frequency bins of
+    /// each. Input shorter than one window becomes a single zero-padded frame.
     fn compute_stft(&self, samples: &[f32]) -> Result<Vec<Vec<Complex<f32>>>, EmbeddingError> {
-        // Implement STFT computation
-        todo!()
+        let SpectralConfig {
+            window_size,
+            hop_size,
+            ..
+        } = self.config.spectral_config;
+        if window_size == 0 || hop_size == 0 {
+            return Err(EmbeddingError::InvalidInput(
+                "STFT window and hop sizes must be non-zero".to_string(),
+            ));
+        }
+        if samples.is_empty() {
+            return Err(EmbeddingError::InvalidInput(
+                "No audio samples to transform".to_string(),
+            ));
+        }
+
+        let window: Vec<f32> = (0..window_size)
+            .map(|n| 0.5 - 0.5 * (2.0 * PI * n as f32 / window_size as f32).cos())
+            .collect();
+        let fft = self
+            .fft_planner
+            .lock()
+            .map_err(|_| EmbeddingError::GenerationFailed("FFT planner lock poisoned".into()))?
+            .plan_fft_forward(window_size);
+
+        let frame_count = if samples.len() <= window_size {
+            1
+        } else {
+            1 + (samples.len() - window_size) / hop_size
+        };
+
+        let mut frames = Vec::with_capacity(frame_count);
+        for frame in 0..frame_count {
+            let start = frame * hop_size;
+            let mut buffer: Vec<Complex<f32>> = window
+                .iter()
+                .enumerate()
+                .map(|(n, w)| Complex::new(samples.get(start + n).copied().unwrap_or(0.0) * w, 0.0))
+                .collect();
+            fft.process(&mut buffer);
+            buffer.truncate(window_size / 2 + 1);
+            frames.push(buffer);
+        }
+
+        Ok(frames)
     }
 
+    /// Power spectrum of each STFT frame summed through `mel_bands`
+    /// triangular filters spaced evenly on the mel scale up to Nyquist.
     fn compute_mel_spectrogram(
         &self,
         stft: &[Vec<Complex<f32>>],
+        sample_rate: u32,
     ) -> Result<Vec<Vec<f32>>, EmbeddingError> {
-        // Implement mel-spectrogram computation
-        todo!()
+        let bands = self.config.spectral_config.mel_bands;
+        let window_size = self.config.spectral_config.window_size;
+        if bands == 0 || sample_rate == 0 {
+            return Err(EmbeddingError::InvalidInput(
+                "Mel spectrogram needs mel bands and a sample rate".to_string(),
+            ));
+        }
+
+        let hz_to_mel = |hz: f32| 2595.0 * (1.0 + hz / 700.0).log10();
+        let mel_to_hz = |mel: f32| 700.0 * (10f32.powf(mel / 2595.0) - 1.0);
+
+        // Filter edges as fractional FFT bins: band i rises from edge i to
+        // edge i + 1 and falls back to zero at edge i + 2
+        let max_mel = hz_to_mel(sample_rate as f32 / 2.0);
+        let edges: Vec<f32> = (0..bands + 2)
+            .map(|i| mel_to_hz(max_mel * i as f32 / (bands + 1) as f32))
+            .map(|hz| hz * window_size as f32 / sample_rate as f32)
+            .collect();
+
+        let filter_weight = |band: usize, bin: f32| {
+            let (lower, center, upper) = (edges[band], edges[band + 1], edges[band + 2]);
+            if bin <= lower || bin >= upper {
+                0.0
+            } else if bin <= center {
+                (bin - lower) / (center - lower).max(f32::EPSILON)
+            } else {
+                (upper - bin) / (upper - center).max(f32::EPSILON)
+            }
+        };
+
+        Ok(stft
+            .iter()
+            .map(|frame| {
+                (0..bands)
+                    .map(|band| {
+                        frame
+                            .iter()
+                            .enumerate()
+                            .map(|(bin, value)| filter_weight(band, bin as f32) * value.norm_sqr())
+                            .sum()
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// First `mfcc_coeffs` DCT-II coefficients of each frame's log-mel
+    /// energies, averaged over frames.
+    fn compute_mfcc(&self, mel_spec: &[Vec<f32>]) -> Result<Vec<f32>, EmbeddingError> {
+        let coeffs = self.config.spectral_config.mfcc_coeffs;
+        let mut mfcc = vec![0.0; coeffs];
+        if mel_spec.is_empty() {
+            return Ok(mfcc);
+        }
+
+        for frame in mel_spec {
+            let bands = frame.len() as f32;
+            let log_mel: Vec<f32> = frame.iter().map(|energy| (energy + 1e-10).ln()).collect();
+            for (k, coefficient) in mfcc.iter_mut().enumerate() {
+                *coefficient += log_mel
+                    .iter()
+                    .enumerate()
+                    .map(|(n, value)| value * (PI / bands * (n as f32 + 0.5) * k as f32).cos())
+                    .sum::<f32>();
+            }
+        }
+
+        let frames = mel_spec.len() as f32;
+        mfcc.iter_mut()
+            .for_each(|coefficient| *coefficient /= frames);
+        Ok(mfcc)
     }
 
     // Additional helper methods...
@@ -205,3 +325,77 @@ impl EmbeddingGenerator for AudioEmbeddingGenerator {
         self.config.dimension
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockModel;
+
+    const SAMPLE_RATE: u32 = 16_000;
+
+    fn generator() -> AudioEmbeddingGenerator {
+        AudioEmbeddingGenerator::new(
+            Arc::new(MockModel::new()),
+            AudioEmbeddingConfig {
+                dimension: 64,
+                feature_weights: AudioFeatureWeights {
+                    spectral: 1.0,
+                    temporal: 1.0,
+                    rhythmic: 1.0,
+                    timbral: 1.0,
+                },
+                spectral_config: SpectralConfig {
+                    window_size: 1024,
+                    hop_size: 512,
+                    mel_bands: 26,
+                    mfcc_coeffs: 13,
+                },
+                temporal_config: TemporalConfig {
+                    segment_size: 1024,
+                    overlap: 512,
+                    tempo_range: (60.0, 180.0),
+                },
+            },
+        )
+    }
+
+    fn sine(frequency: f32, seconds: f32) -> Vec<f32> {
+        let count = (SAMPLE_RATE as f32 * seconds) as usize;
+        (0..count)
+            .map(|n| (2.0 * PI * frequency * n as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn sine_peaks_in_the_stft_bin_of_its_frequency() {
+        let generator = generator();
+        let stft = generator.compute_stft(&sine(1000.0, 1.0)).unwrap();
+
+        // One frame per hop, each holding the non-negative frequencies
+        assert_eq!(stft.len(), 1 + (16_000 - 1024) / 512);
+        // 1 kHz at 16 kHz with a 1024-point window lands on bin 64
+        for frame in &stft {
+            assert_eq!(frame.len(), 513);
+            let peak = (0..frame.len())
+                .max_by(|&a, &b| frame[a].norm().total_cmp(&frame[b].norm()))
+                .unwrap();
+            assert_eq!(peak, 64);
+        }
+    }
+
+    #[test]
+    fn mel_and_mfcc_follow_the_spectral_config() {
+        let generator = generator();
+        let stft = generator.compute_stft(&sine(440.0, 0.5)).unwrap();
+
+        let mel = generator
+            .compute_mel_spectrogram(&stft, SAMPLE_RATE)
+            .unwrap();
+        assert_eq!(mel.len(), stft.len());
+        assert!(mel.iter().all(|frame| frame.len() == 26));
+
+        let mfcc = generator.compute_mfcc(&mel).unwrap();
+        assert_eq!(mfcc.len(), 13);
+        assert!(mfcc.iter().all(|coefficient| coefficient.is_finite()));
+    }
+}
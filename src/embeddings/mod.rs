@@ -1,3 +1,4 @@
+use crate::llm::ModelError;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -42,6 +43,12 @@ pub enum EmbeddingError {
     DimensionMismatch(String),
 }
 
+impl From<ModelError> for EmbeddingError {
+    fn from(error: ModelError) -> Self {
+        EmbeddingError::GenerationFailed(error.to_string())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingMetadata {
     pub path: PathBuf,
@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GraphError {
+    #[error("Failed to access {}: {source}", .path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to serialize graph: {0}")]
+    Serialization(String),
+    #[error("Invalid graph data: {0}")]
+    Parse(String),
+    #[error("Node not found: {}", .0.display())]
+    NotFound(PathBuf),
+    #[error("Cyclic inheritance detected at {}", .0.display())]
+    CyclicInheritance(PathBuf),
+    #[error("Graph lock poisoned: {0}")]
+    LockPoisoned(String),
+}
+
+/// Lets code that still returns `Result<_, String>` use `?` on graph calls.
+impl From<GraphError> for String {
+    fn from(error: GraphError) -> Self {
+        error.to_string()
+    }
+}
@@ -1,12 +1,23 @@
+mod errors;
+
+pub use errors::GraphError;
+
 use crate::analyzers::{Dependency, DependencyMetadata, DependencyType};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::path::PathBuf;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Node {
     pub path: PathBuf,
+    /// Crate or package the node belongs to, if the graph spans several.
+    #[serde(default)]
+    pub package: Option<String>,
     pub metadata: HashMap<String, Value>,
     pub node_type: NodeType,
 }
@@ -19,7 +30,9 @@ pub enum NodeType {
     Type,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
+/// Edges are identified by endpoints, type and line, so separate call sites
+/// of the same function stay distinct while their metadata may differ.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Edge {
     pub source: PathBuf,
     pub target: PathBuf,
@@ -27,12 +40,58 @@ pub struct Edge {
     pub metadata: DependencyMetadata,
 }
 
-#[derive(Debug)]
+impl PartialEq for Edge {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+            && self.target == other.target
+            && self.edge_type == other.edge_type
+            && self.metadata.line_number == other.metadata.line_number
+    }
+}
+
+impl Eq for Edge {}
+
+impl Hash for Edge {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.source.hash(state);
+        self.target.hash(state);
+        self.edge_type.hash(state);
+        self.metadata.line_number.hash(state);
+    }
+}
+
+impl From<Dependency> for Edge {
+    fn from(dep: Dependency) -> Self {
+        Edge {
+            source: dep.source,
+            target: dep.target,
+            edge_type: dep.dependency_type,
+            metadata: dep.metadata,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DependencyGraph {
     nodes: HashMap<PathBuf, Node>,
     edges: Vec<Edge>,
+    // Indices are derived from nodes and edges and rebuilt on load
+    #[serde(skip)]
     node_index: HashMap<String, HashSet<PathBuf>>,
+    #[serde(skip)]
     edge_index: HashMap<PathBuf, HashSet<Edge>>,
+    #[serde(skip)]
+    reverse_edge_index: HashMap<PathBuf, HashSet<Edge>>,
+    // PageRank and betweenness per node, computed on first use and cleared
+    // whenever nodes or edges change
+    #[serde(skip)]
+    centrality: OnceLock<Centrality>,
+}
+
+#[derive(Debug)]
+struct Centrality {
+    pagerank: HashMap<PathBuf, f32>,
+    betweenness: HashMap<PathBuf, f32>,
 }
 
 impl DependencyGraph {
@@ -42,52 +101,420 @@ impl DependencyGraph {
             edges: Vec::new(),
             node_index: HashMap::new(),
             edge_index: HashMap::new(),
+            reverse_edge_index: HashMap::new(),
+            centrality: OnceLock::new(),
         }
     }
 
-    pub fn add_node(&mut self, path: &PathBuf, node_type: NodeType) -> Result<(), String> {
+    /// Writes the graph to a temp file next to `path` and renames it into
+    /// place, so an interrupted save never leaves a truncated graph behind.
+    pub fn save(&self, path: &Path) -> Result<(), GraphError> {
+        let io_error = |source| GraphError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        let mut tmp_file = path.as_os_str().to_owned();
+        tmp_file.push(".tmp");
+        let tmp_file = PathBuf::from(tmp_file);
+
+        let write_tmp = || -> Result<(), GraphError> {
+            let file = fs::File::create(&tmp_file).map_err(io_error)?;
+            let mut writer = BufWriter::new(file);
+            serde_json::to_writer(&mut writer, self)
+                .map_err(|e| GraphError::Serialization(e.to_string()))?;
+            writer.flush().map_err(io_error)?;
+            writer.get_ref().sync_all().map_err(io_error)
+        };
+        if let Err(e) = write_tmp() {
+            let _ = fs::remove_file(&tmp_file);
+            return Err(e);
+        }
+
+        fs::rename(&tmp_file, path).map_err(io_error)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, GraphError> {
+        let file = fs::File::open(path).map_err(|source| GraphError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut graph: Self = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| GraphError::Parse(e.to_string()))?;
+        graph.rebuild_indices();
+        Ok(graph)
+    }
+
+    fn rebuild_indices(&mut self) {
+        self.centrality.take();
+        self.node_index.clear();
+        for (key, node) in &self.nodes {
+            self.node_index
+                .entry(node.path.to_string_lossy().into_owned())
+                .or_insert_with(HashSet::new)
+                .insert(key.clone());
+        }
+
+        self.edge_index.clear();
+        self.reverse_edge_index.clear();
+        for edge in &self.edges {
+            self.edge_index
+                .entry(edge.source.clone())
+                .or_insert_with(HashSet::new)
+                .insert(edge.clone());
+            self.reverse_edge_index
+                .entry(edge.target.clone())
+                .or_insert_with(HashSet::new)
+                .insert(edge.clone());
+        }
+    }
+
+    /// Key a node is stored under. Nodes of different packages never collide,
+    /// even when their paths are identical such as two crates' `src/lib.rs`.
+    pub fn node_key(package: Option<&str>, path: &Path) -> PathBuf {
+        match package {
+            Some(package) => PathBuf::from(format!("{}::{}", package, path.to_string_lossy())),
+            None => path.to_path_buf(),
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn add_node(&mut self, path: &PathBuf, node_type: NodeType) -> Result<(), GraphError> {
+        self.add_package_node(None, path, node_type).map(|_| ())
+    }
+
+    /// Adds a node namespaced by `package`, returning the key it is stored under.
+    pub fn add_package_node(
+        &mut self,
+        package: Option<&str>,
+        path: &PathBuf,
+        node_type: NodeType,
+    ) -> Result<PathBuf, GraphError> {
+        let key = Self::node_key(package, path);
         let node = Node {
             path: path.clone(),
+            package: package.map(String::from),
             metadata: HashMap::new(),
             node_type,
         };
 
-        self.nodes.insert(path.clone(), node);
+        self.nodes.insert(key.clone(), node);
+        self.centrality.take();
         self.node_index
             .entry(path.to_string_lossy().into_owned())
             .or_insert_with(HashSet::new)
-            .insert(path.clone());
+            .insert(key.clone());
+
+        Ok(key)
+    }
+
+    /// Merges another graph into this one. Nodes without a package of their
+    /// own are assigned `package`, so merging per-crate graphs of a workspace
+    /// keeps same-named files distinct.
+    pub fn merge(
+        &mut self,
+        other: DependencyGraph,
+        package: Option<&str>,
+    ) -> Result<(), GraphError> {
+        let mut renamed: HashMap<PathBuf, PathBuf> = HashMap::new();
+
+        for (old_key, node) in other.nodes {
+            let node_package = node.package.clone().or_else(|| package.map(String::from));
+            let key = self.add_package_node(node_package.as_deref(), &node.path, node.node_type)?;
+            if let Some(merged) = self.nodes.get_mut(&key) {
+                merged.metadata.extend(node.metadata);
+            }
+            renamed.insert(old_key, key);
+        }
+
+        for mut edge in other.edges {
+            if let Some(source) = renamed.get(&edge.source) {
+                edge.source = source.clone();
+            }
+            if let Some(target) = renamed.get(&edge.target) {
+                edge.target = target.clone();
+            }
+            self.add_edge(edge)?;
+        }
 
         Ok(())
     }
 
-    pub fn add_edge(&mut self, edge: Edge) -> Result<(), String> {
-        self.edges.push(edge.clone());
-        self.edge_index
+    /// Adds an edge unless an identical one is already present, so
+    /// reanalyzing a file doesn't duplicate its edges.
+    pub fn add_edge(&mut self, edge: Edge) -> Result<(), GraphError> {
+        let is_new = self
+            .edge_index
             .entry(edge.source.clone())
             .or_insert_with(HashSet::new)
+            .insert(edge.clone());
+        if !is_new {
+            return Ok(());
+        }
+
+        self.edges.push(edge.clone());
+        self.centrality.take();
+        self.reverse_edge_index
+            .entry(edge.target.clone())
+            .or_insert_with(HashSet::new)
             .insert(edge);
         Ok(())
     }
 
-    pub fn add_dependencies(&mut self, deps: Vec<Dependency>) -> Result<(), String> {
+    pub fn add_dependencies(&mut self, mut deps: Vec<Dependency>) -> Result<(), GraphError> {
+        self.link_type_usages(&mut deps);
         for dep in deps {
             self.add_node(&dep.source, NodeType::File)?;
-            self.add_node(&dep.target, NodeType::File)?;
+            self.add_node(&dep.target, Self::target_node_type(&dep))?;
+            self.add_edge(Edge::from(dep))?;
+        }
+        Ok(())
+    }
 
-            let edge = Edge {
-                source: dep.source.clone(),
-                target: dep.target.clone(),
-                edge_type: dep.dependency_type,
-                metadata: dep.metadata,
-            };
+    pub fn remove_node(&mut self, path: &PathBuf) -> Result<(), GraphError> {
+        if let Some(node) = self.nodes.remove(path) {
+            let name = node.path.to_string_lossy().into_owned();
+            if let Some(keys) = self.node_index.get_mut(&name) {
+                keys.remove(path);
+                if keys.is_empty() {
+                    self.node_index.remove(&name);
+                }
+            }
+        }
 
+        // Drop every edge touching the node
+        self.centrality.take();
+        self.edges
+            .retain(|edge| edge.source != *path && edge.target != *path);
+        if let Some(outgoing) = self.edge_index.remove(path) {
+            for edge in outgoing {
+                if let Some(edges) = self.reverse_edge_index.get_mut(&edge.target) {
+                    edges.remove(&edge);
+                }
+            }
+        }
+        if let Some(incoming) = self.reverse_edge_index.remove(path) {
+            for edge in incoming {
+                if let Some(edges) = self.edge_index.get_mut(&edge.source) {
+                    edges.remove(&edge);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn replace_edges_for_source(
+        &mut self,
+        source: &PathBuf,
+        edges: Vec<Edge>,
+    ) -> Result<(), GraphError> {
+        self.edges.retain(|edge| edge.source != *source);
+        self.centrality.take();
+        if let Some(outgoing) = self.edge_index.remove(source) {
+            for edge in outgoing {
+                if let Some(edges) = self.reverse_edge_index.get_mut(&edge.target) {
+                    edges.remove(&edge);
+                }
+            }
+        }
+
+        for edge in edges {
+            if !self.nodes.contains_key(&edge.source) {
+                self.add_node(&edge.source, NodeType::File)?;
+            }
+            if !self.nodes.contains_key(&edge.target) {
+                self.add_node(&edge.target, NodeType::File)?;
+            }
             self.add_edge(edge)?;
         }
+
         Ok(())
     }
 
-    pub fn get_dependencies(&self, file: &PathBuf) -> Result<Vec<String>, String> {
+    /// Points `FunctionCall` edges at the definition node of the function
+    /// they call, preferring a definition in the calling file, then one in a
+    /// file it imports, then one anywhere in the project. When the preferred
+    /// tier has several matches the edge is left alone and the candidates
+    /// are recorded under `context.candidates`. Run once every file has been
+    /// added; returns the number of calls resolved.
+    pub fn resolve_calls(&mut self) -> usize {
+        // Definition node -> file defining it, and short name -> definition nodes
+        let mut defining_file: HashMap<PathBuf, PathBuf> = HashMap::new();
+        let mut by_name: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        let mut imports: HashMap<&PathBuf, HashSet<&PathBuf>> = HashMap::new();
+        for edge in &self.edges {
+            match edge.edge_type {
+                DependencyType::FunctionDefinition => {
+                    defining_file.insert(edge.target.clone(), edge.source.clone());
+                    by_name
+                        .entry(Self::function_name(&edge.target))
+                        .or_default()
+                        .push(edge.target.clone());
+                }
+                DependencyType::Import => {
+                    imports
+                        .entry(&edge.source)
+                        .or_default()
+                        .insert(&edge.target);
+                }
+                _ => {}
+            }
+        }
+
+        let mut rewrites: Vec<(usize, Result<PathBuf, Vec<PathBuf>>)> = Vec::new();
+        for (i, edge) in self.edges.iter().enumerate() {
+            if edge.edge_type != DependencyType::FunctionCall
+                || defining_file.contains_key(&edge.target)
+            {
+                continue;
+            }
+            let candidates = match by_name.get(&Self::function_name(&edge.target)) {
+                Some(candidates) => candidates,
+                None => continue,
+            };
+
+            // Callers are files, or functions whose file is known from their definition
+            let caller_file = defining_file.get(&edge.source).unwrap_or(&edge.source);
+            let caller_imports = imports.get(caller_file);
+            let same_file: Vec<&PathBuf> = candidates
+                .iter()
+                .filter(|c| defining_file.get(*c) == Some(caller_file))
+                .collect();
+            let imported: Vec<&PathBuf> = candidates
+                .iter()
+                .filter(|c| {
+                    caller_imports.map_or(false, |files| files.contains(&defining_file[*c]))
+                })
+                .collect();
+            let project: Vec<&PathBuf> = candidates.iter().collect();
+
+            let tier = [same_file, imported, project]
+                .into_iter()
+                .find(|tier| !tier.is_empty())
+                .unwrap_or_default();
+            let rewrite = if tier.len() == 1 {
+                Ok(tier[0].clone())
+            } else {
+                Err(tier.into_iter().cloned().collect())
+            };
+            rewrites.push((i, rewrite));
+        }
+
+        let mut resolved = 0;
+        let mut old_targets = HashSet::new();
+        for (i, rewrite) in rewrites {
+            let edge = &mut self.edges[i];
+            match rewrite {
+                Ok(definition) => {
+                    old_targets.insert(std::mem::replace(&mut edge.target, definition));
+                    resolved += 1;
+                }
+                Err(candidates) => {
+                    let context = edge.metadata.context.get_or_insert_with(|| json!({}));
+                    if let Some(context) = context.as_object_mut() {
+                        context.insert("candidates".to_string(), json!(candidates));
+                    }
+                }
+            }
+        }
+
+        // Bare call-name nodes nothing points at anymore
+        let referenced: HashSet<&PathBuf> = self
+            .edges
+            .iter()
+            .flat_map(|edge| [&edge.source, &edge.target])
+            .collect();
+        let orphaned: Vec<PathBuf> = old_targets
+            .into_iter()
+            .filter(|target| !referenced.contains(target))
+            .collect();
+        for target in orphaned {
+            self.nodes.remove(&target);
+        }
+
+        self.rebuild_indices();
+        resolved
+    }
+
+    /// Bare name of a function node, e.g. `bar` for `foo::bar` or
+    /// `function:Foo.bar`.
+    fn function_name(path: &Path) -> String {
+        let name = path.to_string_lossy();
+        let name = name.strip_prefix("function:").unwrap_or(&name);
+        name.rsplit(|c| c == ':' || c == '.')
+            .next()
+            .unwrap_or(name)
+            .to_string()
+    }
+
+    /// Points `TypeUsage` targets such as `&mut Config` or `Vec<Config>` at the
+    /// node of the type they name, when exactly one type of that name is
+    /// defined in `deps` or already in the graph.
+    fn link_type_usages(&self, deps: &mut [Dependency]) {
+        let mut definitions: HashMap<String, HashSet<PathBuf>> = HashMap::new();
+        let type_paths = deps
+            .iter()
+            .filter(|dep| matches!(Self::target_node_type(dep), NodeType::Type))
+            .map(|dep| &dep.target)
+            .chain(
+                self.nodes
+                    .iter()
+                    .filter(|(_, node)| matches!(node.node_type, NodeType::Type))
+                    .map(|(key, _)| key),
+            );
+        for path in type_paths {
+            let full_path = path.to_string_lossy();
+            let name = full_path.rsplit("::").next().unwrap_or(&full_path);
+            definitions
+                .entry(name.to_string())
+                .or_default()
+                .insert(path.clone());
+        }
+
+        for dep in deps
+            .iter_mut()
+            .filter(|dep| dep.dependency_type == DependencyType::TypeUsage)
+        {
+            let type_name = dep.target.to_string_lossy().into_owned();
+            let definition = type_name
+                .split(|c: char| !c.is_alphanumeric() && c != '_')
+                .filter_map(|ident| definitions.get(ident))
+                .find(|paths| paths.len() == 1)
+                .and_then(|paths| paths.iter().next());
+
+            if let Some(definition) = definition {
+                dep.target = definition.clone();
+            }
+        }
+    }
+
+    fn target_node_type(dep: &Dependency) -> NodeType {
+        let kind = dep
+            .metadata
+            .context
+            .as_ref()
+            .and_then(|context| context.get("kind"))
+            .and_then(|kind| kind.as_str());
+
+        match (&dep.dependency_type, kind) {
+            (DependencyType::TypeDefinition, _) | (DependencyType::TypeUsage, _) => NodeType::Type,
+            // Graphs saved before `TypeDefinition` recorded types as definitions
+            (DependencyType::FunctionDefinition, Some("struct" | "enum" | "trait" | "type")) => {
+                NodeType::Type
+            }
+            (DependencyType::FunctionDefinition, _) => NodeType::Function,
+            _ => NodeType::File,
+        }
+    }
+
+    pub fn get_dependencies(&self, file: &PathBuf) -> Result<Vec<String>, GraphError> {
         let mut deps = Vec::new();
 
         if let Some(edges) = self.edge_index.get(file) {
@@ -95,26 +522,40 @@ impl DependencyGraph {
                 deps.push(edge.target.to_string_lossy().into_owned());
             }
         }
+        // Several edges, e.g. calls on different lines, can share a target
+        deps.sort();
+        deps.dedup();
 
         Ok(deps)
     }
 
-    pub fn get_callers(&self, target: &PathBuf) -> Result<Vec<PathBuf>, String> {
-        let mut callers = Vec::new();
+    pub fn get_callers(&self, target: &PathBuf) -> Result<Vec<PathBuf>, GraphError> {
+        self.get_dependents(target, &[DependencyType::FunctionCall])
+    }
 
-        for edge in &self.edges {
-            if edge.target == *target && edge.edge_type == DependencyType::FunctionCall {
-                callers.push(edge.source.clone());
+    /// Sources of every edge into `target` whose type is one of `edge_types`.
+    pub fn get_dependents(
+        &self,
+        target: &PathBuf,
+        edge_types: &[DependencyType],
+    ) -> Result<Vec<PathBuf>, GraphError> {
+        let mut dependents = Vec::new();
+
+        if let Some(edges) = self.reverse_edge_index.get(target) {
+            for edge in edges {
+                if edge_types.contains(&edge.edge_type) && !dependents.contains(&edge.source) {
+                    dependents.push(edge.source.clone());
+                }
             }
         }
 
-        Ok(callers)
+        Ok(dependents)
     }
 
     pub fn get_function_dependencies(
         &self,
         function_name: &str,
-    ) -> Result<Vec<Dependency>, String> {
+    ) -> Result<Vec<Dependency>, GraphError> {
         let mut deps = Vec::new();
 
         // Find all edges where this function is involved
@@ -144,7 +585,8 @@ impl DependencyGraph {
         Ok(deps)
     }
 
-    pub fn get_llamaindex_metadata(&self, file: &PathBuf) -> Result<Value, String> {
+    pub fn get_llamaindex_metadata(&self, file: &PathBuf) -> Result<Value, GraphError> {
+        let centrality = self.centrality();
         let mut metadata = json!({
             "file_path": file.to_string_lossy(),
             "dependencies": self.get_dependencies(file)?,
@@ -154,6 +596,10 @@ impl DependencyGraph {
                 "indirect": self.get_indirect_relationships(file)?,
             },
             "metrics": self.calculate_metrics(file)?,
+            "centrality": {
+                "pagerank": centrality.pagerank.get(file).copied().unwrap_or(0.0),
+                "betweenness": centrality.betweenness.get(file).copied().unwrap_or(0.0),
+            },
         });
 
         if let Some(node) = self.nodes.get(file) {
@@ -168,7 +614,7 @@ impl DependencyGraph {
     pub fn get_direct_relationships(
         &self,
         file: &PathBuf,
-    ) -> Result<HashMap<String, Vec<String>>, String> {
+    ) -> Result<HashMap<String, Vec<String>>, GraphError> {
         let mut relationships = HashMap::new();
 
         if let Some(edges) = self.edge_index.get(file) {
@@ -180,6 +626,7 @@ impl DependencyGraph {
                     DependencyType::Usage => "uses",
                     DependencyType::FunctionDefinition => "defines",
                     DependencyType::TypeUsage => "uses_type",
+                    DependencyType::TypeDefinition => "defines_type",
                 };
 
                 relationships
@@ -192,10 +639,12 @@ impl DependencyGraph {
         Ok(relationships)
     }
 
-    fn get_indirect_relationships(
+    /// Everything reachable from `file` within a few hops, with the path
+    /// taken to reach it.
+    pub fn get_indirect_relationships(
         &self,
         file: &PathBuf,
-    ) -> Result<Vec<IndirectRelationship>, String> {
+    ) -> Result<Vec<IndirectRelationship>, GraphError> {
         let mut relationships = Vec::new();
         let mut visited = HashSet::new();
         let mut queue = VecDeque::new();
@@ -219,7 +668,9 @@ impl DependencyGraph {
                             path: edge.target.to_string_lossy().into_owned(),
                             relationship_type: edge.edge_type.clone(),
                             depth,
-                            intermediate_nodes: self.get_path_between(file, &edge.target)?,
+                            intermediate_nodes: self
+                                .shortest_path(file, &edge.target, None)
+                                .unwrap_or_default(),
                         });
                     }
                 }
@@ -229,7 +680,7 @@ impl DependencyGraph {
         Ok(relationships)
     }
 
-    fn calculate_metrics(&self, file: &PathBuf) -> Result<DependencyMetrics, String> {
+    pub fn calculate_metrics(&self, file: &PathBuf) -> Result<DependencyMetrics, GraphError> {
         let mut metrics = DependencyMetrics {
             incoming_dependencies: 0,
             outgoing_dependencies: 0,
@@ -260,35 +711,52 @@ impl DependencyGraph {
         Ok(metrics)
     }
 
-    fn calculate_inheritance_depth(&self, file: &PathBuf) -> Result<usize, String> {
-        let mut depth = 0;
-        let mut current = file.clone();
-        let mut visited = HashSet::new();
+    /// Longest chain of inheritance edges above `file`. A type implementing
+    /// several traits counts the deepest trait hierarchy.
+    fn calculate_inheritance_depth(&self, file: &PathBuf) -> Result<usize, GraphError> {
+        let mut ancestors = HashSet::new();
+        self.inheritance_depth_from(file, &mut ancestors)
+    }
+
+    fn inheritance_depth_from(
+        &self,
+        node: &PathBuf,
+        ancestors: &mut HashSet<PathBuf>,
+    ) -> Result<usize, GraphError> {
+        ancestors.insert(node.clone());
 
-        while let Some(parent) = self.find_parent(&current)? {
-            if !visited.insert(parent.clone()) {
-                return Err("Cyclic inheritance detected".to_string());
+        let mut depth = 0;
+        for parent in self.find_parents(node) {
+            if ancestors.contains(&parent) {
+                return Err(GraphError::CyclicInheritance(parent));
             }
-            depth += 1;
-            current = parent;
+            depth = depth.max(1 + self.inheritance_depth_from(&parent, ancestors)?);
         }
 
+        ancestors.remove(node);
         Ok(depth)
     }
 
-    fn find_parent(&self, file: &PathBuf) -> Result<Option<PathBuf>, String> {
-        if let Some(edges) = self.edge_index.get(file) {
-            for edge in edges {
-                if edge.edge_type == DependencyType::Inheritance {
-                    return Ok(Some(edge.target.clone()));
-                }
-            }
-        }
-        Ok(None)
+    fn find_parents(&self, file: &PathBuf) -> Vec<PathBuf> {
+        self.edge_index
+            .get(file)
+            .into_iter()
+            .flatten()
+            .filter(|edge| edge.edge_type == DependencyType::Inheritance)
+            .map(|edge| edge.target.clone())
+            .collect()
     }
 
-    fn get_path_between(&self, start: &PathBuf, end: &PathBuf) -> Result<Vec<PathBuf>, String> {
-        let mut path = Vec::new();
+    /// Shortest path from `start` to `end`, both included, following only
+    /// `edge_types` edges when given. `Some(vec![start])` when they are the
+    /// same node, `None` when `end` is unreachable. Neighbors are visited in
+    /// path order so ties resolve the same way every time.
+    pub fn shortest_path(
+        &self,
+        start: &PathBuf,
+        end: &PathBuf,
+        edge_types: Option<&[DependencyType]>,
+    ) -> Option<Vec<PathBuf>> {
         let mut visited = HashSet::new();
         let mut queue = VecDeque::new();
         let mut parent_map: HashMap<PathBuf, PathBuf> = HashMap::new();
@@ -298,29 +766,705 @@ impl DependencyGraph {
 
         while let Some(current) = queue.pop_front() {
             if current == *end {
-                // Reconstruct path
-                let mut current = current;
-                while current != *start {
-                    path.push(current.clone());
-                    current = parent_map.get(&current).unwrap().clone();
+                let mut path = vec![current];
+                while let Some(parent) = parent_map.get(path.last()?) {
+                    path.push(parent.clone());
                 }
-                path.push(start.clone());
                 path.reverse();
-                return Ok(path);
+                return Some(path);
             }
 
-            if let Some(edges) = self.edge_index.get(&current) {
-                for edge in edges {
-                    if !visited.contains(&edge.target) {
-                        visited.insert(edge.target.clone());
-                        queue.push_back(edge.target.clone());
-                        parent_map.insert(edge.target.clone(), current.clone());
+            let mut targets: Vec<&PathBuf> = self
+                .edge_index
+                .get(&current)
+                .into_iter()
+                .flatten()
+                .filter(|edge| edge_types.map_or(true, |types| types.contains(&edge.edge_type)))
+                .map(|edge| &edge.target)
+                .collect();
+            targets.sort();
+            for target in targets {
+                if visited.insert(target.clone()) {
+                    queue.push_back(target.clone());
+                    parent_map.insert(target.clone(), current.clone());
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Dense adjacency matrix; cell `[i][j]` counts edges from node `i` to node `j`.
+    pub fn to_adjacency_matrix(&self) -> (Vec<PathBuf>, Vec<Vec<u32>>) {
+        let (nodes, triplets) = self.to_adjacency_triplets();
+        let mut matrix = vec![vec![0u32; nodes.len()]; nodes.len()];
+        for (row, col, weight) in triplets {
+            matrix[row][col] = weight;
+        }
+
+        (nodes, matrix)
+    }
+
+    /// Sparse `(row, col, weight)` form of the adjacency matrix for graphs too
+    /// large to materialize densely. Nodes are ordered by key.
+    pub fn to_adjacency_triplets(&self) -> (Vec<PathBuf>, Vec<(usize, usize, u32)>) {
+        let mut nodes: Vec<PathBuf> = self.nodes.keys().cloned().collect();
+        nodes.sort();
+        let positions: HashMap<&PathBuf, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, path)| (path, i))
+            .collect();
+
+        let mut weights: HashMap<(usize, usize), u32> = HashMap::new();
+        for edge in &self.edges {
+            if let (Some(&row), Some(&col)) =
+                (positions.get(&edge.source), positions.get(&edge.target))
+            {
+                *weights.entry((row, col)).or_insert(0) += 1;
+            }
+        }
+
+        let mut triplets: Vec<(usize, usize, u32)> = weights
+            .into_iter()
+            .map(|((row, col), weight)| (row, col, weight))
+            .collect();
+        triplets.sort();
+
+        (nodes, triplets)
+    }
+
+    /// Groups of nodes that reach each other through `edge_types` edges,
+    /// found with Tarjan's algorithm. Each cycle is sorted, and cycles are
+    /// ordered by their first node.
+    pub fn find_cycles(&self, edge_types: &[DependencyType]) -> Vec<Vec<PathBuf>> {
+        struct Tarjan<'a> {
+            adjacency: HashMap<&'a PathBuf, Vec<&'a PathBuf>>,
+            index: HashMap<&'a PathBuf, usize>,
+            low_link: HashMap<&'a PathBuf, usize>,
+            stack: Vec<&'a PathBuf>,
+            on_stack: HashSet<&'a PathBuf>,
+            cycles: Vec<Vec<PathBuf>>,
+        }
+
+        impl<'a> Tarjan<'a> {
+            fn visit(&mut self, node: &'a PathBuf) {
+                let index = self.index.len();
+                self.index.insert(node, index);
+                self.low_link.insert(node, index);
+                self.stack.push(node);
+                self.on_stack.insert(node);
+
+                let targets = self.adjacency.get(node).cloned().unwrap_or_default();
+                for &target in &targets {
+                    if !self.index.contains_key(target) {
+                        self.visit(target);
+                        let low = self.low_link[node].min(self.low_link[target]);
+                        self.low_link.insert(node, low);
+                    } else if self.on_stack.contains(target) {
+                        let low = self.low_link[node].min(self.index[target]);
+                        self.low_link.insert(node, low);
+                    }
+                }
+
+                if self.low_link[node] == self.index[node] {
+                    let mut component = Vec::new();
+                    while let Some(member) = self.stack.pop() {
+                        self.on_stack.remove(member);
+                        component.push(member.clone());
+                        if member == node {
+                            break;
+                        }
+                    }
+                    if component.len() > 1 || targets.contains(&node) {
+                        component.sort();
+                        self.cycles.push(component);
+                    }
+                }
+            }
+        }
+
+        let mut adjacency: HashMap<&PathBuf, Vec<&PathBuf>> = HashMap::new();
+        for edge in &self.edges {
+            if edge_types.contains(&edge.edge_type) && self.nodes.contains_key(&edge.target) {
+                adjacency
+                    .entry(&edge.source)
+                    .or_default()
+                    .push(&edge.target);
+            }
+        }
+
+        let mut nodes: Vec<&PathBuf> = self.nodes.keys().collect();
+        nodes.sort();
+
+        let mut tarjan = Tarjan {
+            adjacency,
+            index: HashMap::new(),
+            low_link: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            cycles: Vec::new(),
+        };
+        for node in nodes {
+            if !tarjan.index.contains_key(node) {
+                tarjan.visit(node);
+            }
+        }
+
+        tarjan.cycles.sort();
+        tarjan.cycles
+    }
+
+    /// Files in dependency order over `Import` edges, each after everything it
+    /// imports, or the import cycles that make such an order impossible.
+    /// Ties are broken by path so the order is deterministic.
+    pub fn topological_order(&self) -> Result<Vec<PathBuf>, Vec<Vec<PathBuf>>> {
+        let cycles = self.find_cycles(&[DependencyType::Import]);
+        if !cycles.is_empty() {
+            return Err(cycles);
+        }
+
+        Ok(self
+            .import_layers()
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect())
+    }
+
+    /// Files grouped by their longest import chain: layer 0 imports no other
+    /// project file, layer N only imports from layers below N. Files in an
+    /// import cycle share a layer.
+    pub fn layers(&self) -> Vec<Vec<PathBuf>> {
+        self.import_layers()
+            .into_iter()
+            .map(|layer| layer.into_iter().flatten().collect())
+            .collect()
+    }
+
+    /// Layers of the import graph condensed by cycle, each holding its
+    /// components (a single file, or every file of a cycle) sorted by path.
+    /// Only nodes with an extension count, leaving out unresolved imports.
+    fn import_layers(&self) -> Vec<Vec<Vec<PathBuf>>> {
+        let is_file = |path: &PathBuf| path.extension().is_some() && self.nodes.contains_key(path);
+
+        let mut components: Vec<Vec<PathBuf>> = self.find_cycles(&[DependencyType::Import]);
+        let mut component_of: HashMap<PathBuf, usize> = HashMap::new();
+        for (i, cycle) in components.iter().enumerate() {
+            for path in cycle {
+                component_of.insert(path.clone(), i);
+            }
+        }
+
+        let import_edges: Vec<&Edge> = self
+            .edges
+            .iter()
+            .filter(|edge| {
+                edge.edge_type == DependencyType::Import
+                    && is_file(&edge.source)
+                    && is_file(&edge.target)
+            })
+            .collect();
+        let mut files: Vec<&PathBuf> = import_edges
+            .iter()
+            .flat_map(|edge| [&edge.source, &edge.target])
+            .collect();
+        files.sort();
+        files.dedup();
+        for file in files {
+            if !component_of.contains_key(file) {
+                component_of.insert(file.clone(), components.len());
+                components.push(vec![file.clone()]);
+            }
+        }
+
+        // Component -> components it imports
+        let mut imports: Vec<HashSet<usize>> = vec![HashSet::new(); components.len()];
+        for edge in import_edges {
+            let (source, target) = (component_of[&edge.source], component_of[&edge.target]);
+            if source != target {
+                imports[source].insert(target);
+            }
+        }
+
+        let mut depth: Vec<Option<usize>> = vec![None; components.len()];
+        fn depth_of(
+            component: usize,
+            imports: &[HashSet<usize>],
+            depth: &mut Vec<Option<usize>>,
+        ) -> usize {
+            if let Some(d) = depth[component] {
+                return d;
+            }
+            let d = imports[component]
+                .iter()
+                .map(|&target| depth_of(target, imports, depth) + 1)
+                .max()
+                .unwrap_or(0);
+            depth[component] = Some(d);
+            d
+        }
+
+        let mut layers: Vec<Vec<Vec<PathBuf>>> = Vec::new();
+        for (i, component) in components.into_iter().enumerate() {
+            let d = depth_of(i, &imports, &mut depth);
+            if layers.len() <= d {
+                layers.resize(d + 1, Vec::new());
+            }
+            layers[d].push(component);
+        }
+        for layer in &mut layers {
+            layer.sort();
+        }
+
+        layers
+    }
+
+    /// PageRank over import and call edges; scores sum to 1. Rank of nodes
+    /// without outgoing edges is spread evenly over every node.
+    pub fn pagerank(&self, damping: f32, iterations: usize) -> HashMap<PathBuf, f32> {
+        let (nodes, adjacency) = self.centrality_adjacency();
+        let n = nodes.len();
+        if n == 0 {
+            return HashMap::new();
+        }
+
+        let mut rank = vec![1.0 / n as f32; n];
+        for _ in 0..iterations {
+            let dangling: f32 = (0..n)
+                .filter(|&i| adjacency[i].is_empty())
+                .map(|i| rank[i])
+                .sum();
+            let base = (1.0 - damping) / n as f32 + damping * dangling / n as f32;
+
+            let mut next = vec![base; n];
+            for (i, targets) in adjacency.iter().enumerate() {
+                let share = damping * rank[i] / targets.len().max(1) as f32;
+                for &target in targets {
+                    next[target] += share;
+                }
+            }
+            rank = next;
+        }
+
+        nodes.into_iter().zip(rank).collect()
+    }
+
+    /// Brandes' betweenness centrality over import and call edges,
+    /// normalized by `(n - 1)(n - 2)` so scores fall in `[0, 1]`.
+    pub fn betweenness_centrality(&self) -> HashMap<PathBuf, f32> {
+        let (nodes, adjacency) = self.centrality_adjacency();
+        let n = nodes.len();
+        let mut centrality = vec![0.0f32; n];
+
+        for source in 0..n {
+            let mut stack = Vec::new();
+            let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+            let mut paths = vec![0.0f32; n];
+            let mut distance: Vec<Option<usize>> = vec![None; n];
+            paths[source] = 1.0;
+            distance[source] = Some(0);
+
+            let mut queue = VecDeque::from([source]);
+            while let Some(current) = queue.pop_front() {
+                stack.push(current);
+                let next_distance = distance[current].map(|d| d + 1);
+                for &target in &adjacency[current] {
+                    if distance[target].is_none() {
+                        distance[target] = next_distance;
+                        queue.push_back(target);
+                    }
+                    if distance[target] == next_distance {
+                        paths[target] += paths[current];
+                        predecessors[target].push(current);
+                    }
+                }
+            }
+
+            let mut dependency = vec![0.0f32; n];
+            while let Some(node) = stack.pop() {
+                for &predecessor in &predecessors[node] {
+                    dependency[predecessor] +=
+                        paths[predecessor] / paths[node] * (1.0 + dependency[node]);
+                }
+                if node != source {
+                    centrality[node] += dependency[node];
+                }
+            }
+        }
+
+        let scale = if n > 2 {
+            1.0 / ((n - 1) * (n - 2)) as f32
+        } else {
+            1.0
+        };
+        nodes
+            .into_iter()
+            .zip(centrality.into_iter().map(|c| c * scale))
+            .collect()
+    }
+
+    /// Centrality scores of every node, computed once until the graph changes.
+    fn centrality(&self) -> &Centrality {
+        self.centrality.get_or_init(|| Centrality {
+            pagerank: self.pagerank(0.85, 50),
+            betweenness: self.betweenness_centrality(),
+        })
+    }
+
+    /// Nodes sorted by key and, per node, the sorted distinct nodes it
+    /// imports or calls.
+    fn centrality_adjacency(&self) -> (Vec<PathBuf>, Vec<Vec<usize>>) {
+        let mut nodes: Vec<PathBuf> = self.nodes.keys().cloned().collect();
+        nodes.sort();
+        let positions: HashMap<&PathBuf, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, path)| (path, i))
+            .collect();
+
+        let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+        for edge in &self.edges {
+            if !matches!(
+                edge.edge_type,
+                DependencyType::Import | DependencyType::FunctionCall
+            ) {
+                continue;
+            }
+            if let (Some(&source), Some(&target)) =
+                (positions.get(&edge.source), positions.get(&edge.target))
+            {
+                if source != target {
+                    adjacency[source].push(target);
+                }
+            }
+        }
+        for targets in &mut adjacency {
+            targets.sort_unstable();
+            targets.dedup();
+        }
+
+        (nodes, adjacency)
+    }
+
+    /// Graphviz rendering with one node per graph node and edges labeled by type.
+    pub fn to_dot(&self) -> String {
+        let (nodes, _) = self.to_adjacency_triplets();
+        let mut dot = String::from("digraph dependencies {\n");
+        for node in &nodes {
+            dot.push_str(&format!("    {:?};\n", portable_path(node)));
+        }
+
+        let mut edges: Vec<&Edge> = self
+            .edges
+            .iter()
+            .filter(|edge| self.nodes.contains_key(&edge.target))
+            .collect();
+        edges.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+        for edge in edges {
+            dot.push_str(&format!(
+                "    {:?} -> {:?} [label={:?}];\n",
+                portable_path(&edge.source),
+                portable_path(&edge.target),
+                format!("{:?}", edge.edge_type)
+            ));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Interchange form `{nodes: [...], edges: [...]}` for external graph
+    /// tools. Nodes are sorted by id and edges by endpoints so the output is
+    /// stable; paths always use forward slashes. `from_json` reads it back.
+    pub fn to_json(&self) -> Value {
+        let mut nodes: Vec<(&PathBuf, &Node)> = self.nodes.iter().collect();
+        nodes.sort_by(|a, b| a.0.cmp(b.0));
+        let nodes: Vec<Value> = nodes
+            .into_iter()
+            .map(|(key, node)| {
+                json!({
+                    "id": portable_path(key),
+                    "path": portable_path(&node.path),
+                    "package": node.package,
+                    "type": node.node_type,
+                    "metadata": node.metadata,
+                })
+            })
+            .collect();
+
+        let mut edges: Vec<&Edge> = self.edges.iter().collect();
+        edges.sort_by(|a, b| {
+            (&a.source, &a.target, a.metadata.line_number).cmp(&(
+                &b.source,
+                &b.target,
+                b.metadata.line_number,
+            ))
+        });
+        let edges: Vec<Value> = edges
+            .into_iter()
+            .map(|edge| {
+                json!({
+                    "source": portable_path(&edge.source),
+                    "target": portable_path(&edge.target),
+                    "type": edge.edge_type,
+                    "metadata": edge.metadata,
+                })
+            })
+            .collect();
+
+        json!({ "nodes": nodes, "edges": edges })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self, GraphError> {
+        let field = |item: &Value, name: &str| -> Result<Value, GraphError> {
+            item.get(name)
+                .cloned()
+                .ok_or_else(|| GraphError::Parse(format!("item is missing '{}'", name)))
+        };
+        let parse = |value: Value| -> Result<String, GraphError> {
+            serde_json::from_value(value).map_err(|e| GraphError::Parse(e.to_string()))
+        };
+
+        let mut graph = Self::new();
+        for node in value
+            .get("nodes")
+            .and_then(Value::as_array)
+            .ok_or_else(|| GraphError::Parse("no nodes array".to_string()))?
+        {
+            graph.nodes.insert(
+                PathBuf::from(parse(field(node, "id")?)?),
+                Node {
+                    path: PathBuf::from(parse(field(node, "path")?)?),
+                    package: serde_json::from_value(field(node, "package")?)
+                        .map_err(|e| GraphError::Parse(e.to_string()))?,
+                    metadata: serde_json::from_value(field(node, "metadata")?)
+                        .map_err(|e| GraphError::Parse(e.to_string()))?,
+                    node_type: serde_json::from_value(field(node, "type")?)
+                        .map_err(|e| GraphError::Parse(e.to_string()))?,
+                },
+            );
+        }
+
+        for edge in value
+            .get("edges")
+            .and_then(Value::as_array)
+            .ok_or_else(|| GraphError::Parse("no edges array".to_string()))?
+        {
+            graph.edges.push(Edge {
+                source: PathBuf::from(parse(field(edge, "source")?)?),
+                target: PathBuf::from(parse(field(edge, "target")?)?),
+                edge_type: serde_json::from_value(field(edge, "type")?)
+                    .map_err(|e| GraphError::Parse(e.to_string()))?,
+                metadata: serde_json::from_value(field(edge, "metadata")?)
+                    .map_err(|e| GraphError::Parse(e.to_string()))?,
+            });
+        }
+
+        graph.rebuild_indices();
+        Ok(graph)
+    }
+
+    /// GraphML with node type and package on nodes, and edge type and line
+    /// number on edges.
+    pub fn to_graphml(&self) -> String {
+        let mut graphml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        graphml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        for (id, domain, name, kind) in [
+            ("node_type", "node", "type", "string"),
+            ("package", "node", "package", "string"),
+            ("edge_type", "edge", "type", "string"),
+            ("line", "edge", "line", "int"),
+        ] {
+            graphml.push_str(&format!(
+                "  <key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"/>\n",
+                id, domain, name, kind
+            ));
+        }
+        graphml.push_str("  <graph id=\"dependencies\" edgedefault=\"directed\">\n");
+
+        let mut nodes: Vec<(&PathBuf, &Node)> = self.nodes.iter().collect();
+        nodes.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, node) in nodes {
+            graphml.push_str(&format!(
+                "    <node id=\"{}\">\n      <data key=\"node_type\">{:?}</data>\n",
+                xml_escape(&portable_path(key)),
+                node.node_type
+            ));
+            if let Some(package) = &node.package {
+                graphml.push_str(&format!(
+                    "      <data key=\"package\">{}</data>\n",
+                    xml_escape(package)
+                ));
+            }
+            graphml.push_str("    </node>\n");
+        }
+
+        let mut edges: Vec<&Edge> = self.edges.iter().collect();
+        edges.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+        for edge in edges {
+            graphml.push_str(&format!(
+                "    <edge source=\"{}\" target=\"{}\">\n      <data key=\"edge_type\">{:?}</data>\n",
+                xml_escape(&portable_path(&edge.source)),
+                xml_escape(&portable_path(&edge.target)),
+                edge.edge_type
+            ));
+            if let Some(line) = edge.metadata.line_number {
+                graphml.push_str(&format!("      <data key=\"line\">{}</data>\n", line));
+            }
+            graphml.push_str("    </edge>\n");
+        }
+
+        graphml.push_str("  </graph>\n</graphml>\n");
+        graphml
+    }
+
+    /// Martin's instability `I = Ce / (Ca + Ce)` for every directory under
+    /// `root`, most unstable first. Only import edges between project files
+    /// count, so imports should be resolved to paths before insertion.
+    pub fn module_instability(&self, root: &Path) -> Vec<ModuleInstability> {
+        let module_of = |path: &PathBuf| -> Option<PathBuf> {
+            if path.extension().is_none() {
+                return None;
+            }
+            path.strip_prefix(root)
+                .ok()
+                .map(|relative| relative.parent().unwrap_or(Path::new("")).to_path_buf())
+        };
+
+        let mut afferent: HashMap<PathBuf, HashSet<&PathBuf>> = HashMap::new();
+        let mut efferent: HashMap<PathBuf, HashSet<&PathBuf>> = HashMap::new();
+        let mut types: HashMap<PathBuf, (usize, usize)> = HashMap::new();
+
+        for edge in &self.edges {
+            let source_module = match module_of(&edge.source) {
+                Some(module) => module,
+                None => continue,
+            };
+
+            match edge.edge_type {
+                DependencyType::Import => {
+                    afferent.entry(source_module.clone()).or_default();
+                    efferent.entry(source_module.clone()).or_default();
+                    if let Some(target_module) = module_of(&edge.target) {
+                        if target_module != source_module {
+                            efferent
+                                .entry(source_module)
+                                .or_default()
+                                .insert(&edge.target);
+                            afferent
+                                .entry(target_module)
+                                .or_default()
+                                .insert(&edge.source);
+                        }
                     }
                 }
+                DependencyType::TypeDefinition | DependencyType::FunctionDefinition => {
+                    if let Some(is_abstract) = Self::type_abstractness(&edge.metadata) {
+                        let counts = types.entry(source_module).or_insert((0, 0));
+                        counts.0 += 1;
+                        if is_abstract {
+                            counts.1 += 1;
+                        }
+                    }
+                }
+                _ => {}
             }
         }
 
-        Ok(Vec::new())
+        let modules: HashSet<&PathBuf> = afferent.keys().chain(efferent.keys()).collect();
+        let mut results: Vec<ModuleInstability> = modules
+            .into_iter()
+            .map(|module| {
+                let ca = afferent.get(module).map_or(0, HashSet::len);
+                let ce = efferent.get(module).map_or(0, HashSet::len);
+                let (total_types, abstract_types) = types.get(module).copied().unwrap_or((0, 0));
+
+                ModuleInstability {
+                    module: module.clone(),
+                    afferent_coupling: ca,
+                    efferent_coupling: ce,
+                    instability: if ca + ce == 0 {
+                        0.0
+                    } else {
+                        ce as f64 / (ca + ce) as f64
+                    },
+                    abstractness: if total_types == 0 {
+                        0.0
+                    } else {
+                        abstract_types as f64 / total_types as f64
+                    },
+                }
+            })
+            .collect();
+
+        results.sort_by(|a, b| {
+            b.instability
+                .partial_cmp(&a.instability)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.module.cmp(&b.module))
+        });
+        results
+    }
+
+    /// Whether a definition edge declares an abstract type, `None` if it
+    /// doesn't declare a type at all.
+    fn type_abstractness(metadata: &DependencyMetadata) -> Option<bool> {
+        let context = metadata.context.as_ref()?;
+        let kind = context
+            .get("kind")
+            .or_else(|| context.get("type"))
+            .and_then(|kind| kind.as_str())?;
+
+        match kind {
+            "trait" => Some(true),
+            "struct" | "enum" | "type" => Some(false),
+            "class" => Some(
+                context
+                    .get("bases")
+                    .and_then(|bases| bases.as_array())
+                    .map(|bases| {
+                        bases.iter().filter_map(|base| base.as_str()).any(|base| {
+                            matches!(base, "ABC" | "abc.ABC" | "Protocol" | "typing.Protocol")
+                                || base.contains("ABCMeta")
+                        })
+                    })
+                    .unwrap_or(false),
+            ),
+            _ => None,
+        }
+    }
+
+    /// File nodes whose path points into `root` but no longer exists on disk.
+    pub fn missing_file_nodes(&self, root: &Path) -> Vec<PathBuf> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| matches!(node.node_type, NodeType::File))
+            .filter(|(_, node)| node.path.starts_with(root) && !node.path.exists())
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Imports that never resolved to a project file, as `(source, target)`
+    /// pairs sorted by source. Resolved imports point at absolute file
+    /// paths; the rest, external crates and packages included, keep the
+    /// name the analyzer saw.
+    pub fn unresolved_targets(&self) -> Vec<(PathBuf, String)> {
+        let mut unresolved: Vec<(PathBuf, String)> = self
+            .edges
+            .iter()
+            .filter(|edge| edge.edge_type == DependencyType::Import)
+            .filter(|edge| !edge.target.is_absolute())
+            .map(|edge| {
+                (
+                    edge.source.clone(),
+                    edge.target.to_string_lossy().into_owned(),
+                )
+            })
+            .collect();
+        unresolved.sort();
+        unresolved.dedup();
+        unresolved
     }
 
     pub fn get_node_metadata(&self, path: &PathBuf) -> Option<&HashMap<String, Value>> {
@@ -331,29 +1475,394 @@ impl DependencyGraph {
         &mut self,
         path: &PathBuf,
         metadata: HashMap<String, Value>,
-    ) -> Result<(), String> {
+    ) -> Result<(), GraphError> {
         if let Some(node) = self.nodes.get_mut(path) {
             node.metadata.extend(metadata);
             Ok(())
         } else {
-            Err("Node not found".to_string())
+            Err(GraphError::NotFound(path.clone()))
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct IndirectRelationship {
-    path: String,
-    relationship_type: DependencyType,
-    depth: usize,
-    intermediate_nodes: Vec<PathBuf>,
+pub struct IndirectRelationship {
+    pub path: String,
+    pub relationship_type: DependencyType,
+    pub depth: usize,
+    pub intermediate_nodes: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModuleInstability {
+    pub module: PathBuf,
+    pub afferent_coupling: usize,
+    pub efferent_coupling: usize,
+    pub instability: f64,
+    pub abstractness: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct DependencyMetrics {
-    incoming_dependencies: usize,
-    outgoing_dependencies: usize,
-    cyclomatic_complexity: usize,
-    depth_of_inheritance: usize,
-    coupling_factor: f64,
+pub struct DependencyMetrics {
+    pub incoming_dependencies: usize,
+    pub outgoing_dependencies: usize,
+    pub cyclomatic_complexity: usize,
+    pub depth_of_inheritance: usize,
+    pub coupling_factor: f64,
+}
+
+/// Path as a string with forward slashes, whatever the host OS.
+fn portable_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(source: &str, target: &str, edge_type: DependencyType, line: usize) -> Edge {
+        Edge {
+            source: PathBuf::from(source),
+            target: PathBuf::from(target),
+            edge_type,
+            metadata: DependencyMetadata {
+                line_number: Some(line),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn graph(edges: &[Edge]) -> DependencyGraph {
+        let mut graph = DependencyGraph::new();
+        for edge in edges {
+            for path in [&edge.source, &edge.target] {
+                if !graph.nodes.contains_key(path) {
+                    graph.add_node(path, NodeType::File).unwrap();
+                }
+            }
+            graph.add_edge(edge.clone()).unwrap();
+        }
+        graph
+    }
+
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    fn metadata_centrality(graph: &DependencyGraph, file: &str) -> (f64, f64) {
+        let metadata = graph.get_llamaindex_metadata(&PathBuf::from(file)).unwrap();
+        (
+            metadata["centrality"]["pagerank"].as_f64().unwrap(),
+            metadata["centrality"]["betweenness"].as_f64().unwrap(),
+        )
+    }
+
+    #[test]
+    fn merging_crates_keeps_same_named_files_apart() {
+        let crate_graph = || graph(&[edge("src/lib.rs", "src/util.rs", DependencyType::Import, 1)]);
+        let mut workspace = DependencyGraph::new();
+        workspace.merge(crate_graph(), Some("alpha")).unwrap();
+        workspace.merge(crate_graph(), Some("beta")).unwrap();
+
+        assert_eq!(workspace.node_count(), 4);
+        assert_eq!(workspace.edge_count(), 2);
+        for package in ["alpha", "beta"] {
+            let lib = DependencyGraph::node_key(Some(package), Path::new("src/lib.rs"));
+            let util = DependencyGraph::node_key(Some(package), Path::new("src/util.rs"));
+            assert_eq!(workspace.nodes[&lib].package.as_deref(), Some(package));
+            assert_eq!(workspace.nodes[&lib].path, PathBuf::from("src/lib.rs"));
+            assert_eq!(
+                workspace.get_dependencies(&lib).unwrap(),
+                vec![util.to_string_lossy().into_owned()]
+            );
+        }
+        assert_eq!(workspace.node_index["src/lib.rs"].len(), 2);
+    }
+
+    #[test]
+    fn add_edge_ignores_duplicates() {
+        let mut graph = graph(&[
+            edge("a.py", "b.py", DependencyType::Import, 3),
+            edge("a.py", "b.py", DependencyType::Import, 3),
+        ]);
+        assert_eq!(graph.edge_count(), 1);
+        let dependents = graph
+            .get_dependents(&PathBuf::from("b.py"), &[DependencyType::Import])
+            .unwrap();
+        assert_eq!(dependents.len(), 1);
+
+        // Another call site of the same target is a distinct edge
+        graph
+            .add_edge(edge("a.py", "b.py", DependencyType::Import, 7))
+            .unwrap();
+        assert_eq!(graph.edge_count(), 2);
+    }
+
+    #[test]
+    fn calls_on_different_lines_are_distinct_edges() {
+        let call = |line, scope: &str| {
+            let mut edge = edge(
+                "main.py",
+                "function:run",
+                DependencyType::FunctionCall,
+                line,
+            );
+            edge.metadata.context = Some(json!({ "scope": scope }));
+            edge
+        };
+        let graph = graph(&[call(2, "main"), call(5, "main"), call(5, "retry")]);
+
+        // Context is detail rather than identity, so the second line-5 call
+        // is the same edge
+        assert_eq!(graph.edge_count(), 2);
+        assert_eq!(call(5, "main"), call(5, "retry"));
+        let edges: HashSet<Edge> = [call(5, "main"), call(5, "retry")].into_iter().collect();
+        assert_eq!(edges.len(), 1);
+    }
+
+    #[test]
+    fn reanalyzed_dependencies_keep_a_stable_dependency_count() {
+        let dependencies = || {
+            [3, 3, 9]
+                .into_iter()
+                .map(|line| Dependency {
+                    source: PathBuf::from("a.py"),
+                    target: PathBuf::from("b.py"),
+                    dependency_type: DependencyType::Import,
+                    metadata: DependencyMetadata {
+                        line_number: Some(line),
+                        ..Default::default()
+                    },
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut graph = DependencyGraph::new();
+        graph.add_dependencies(dependencies()).unwrap();
+        graph.add_dependencies(dependencies()).unwrap();
+
+        assert_eq!(graph.edge_count(), 2);
+        assert_eq!(
+            graph.get_dependencies(&PathBuf::from("a.py")).unwrap(),
+            vec!["b.py".to_string()]
+        );
+    }
+
+    #[test]
+    fn reverse_index_matches_a_scan_of_all_edges() {
+        let dependency = |source: &str, target: &str, dependency_type, line| Dependency {
+            source: PathBuf::from(source),
+            target: PathBuf::from(target),
+            dependency_type,
+            metadata: DependencyMetadata {
+                line_number: Some(line),
+                ..Default::default()
+            },
+        };
+        let mut graph = DependencyGraph::new();
+        graph
+            .add_dependencies(vec![
+                dependency("a.py", "b.py", DependencyType::Import, 1),
+                dependency("a.py", "c.py", DependencyType::Import, 2),
+                dependency("b.py", "c.py", DependencyType::FunctionCall, 5),
+                dependency("b.py", "c.py", DependencyType::FunctionCall, 9),
+                dependency("d.py", "c.py", DependencyType::Inheritance, 3),
+                dependency("d.py", "b.py", DependencyType::Import, 1),
+                dependency("c.py", "a.py", DependencyType::Usage, 4),
+            ])
+            .unwrap();
+
+        let all_types = [
+            DependencyType::Import,
+            DependencyType::FunctionCall,
+            DependencyType::Inheritance,
+            DependencyType::Usage,
+        ];
+        let assert_consistent = |graph: &DependencyGraph| {
+            for target in graph.nodes.keys() {
+                for edge_types in [&all_types[..1], &all_types[1..2], &all_types[..]] {
+                    let mut indexed = graph.get_dependents(target, edge_types).unwrap();
+                    indexed.sort();
+                    let mut scanned: Vec<PathBuf> = graph
+                        .edges
+                        .iter()
+                        .filter(|e| e.target == *target && edge_types.contains(&e.edge_type))
+                        .map(|e| e.source.clone())
+                        .collect();
+                    scanned.sort();
+                    scanned.dedup();
+                    assert_eq!(indexed, scanned, "dependents of {}", target.display());
+                }
+            }
+        };
+
+        assert_consistent(&graph);
+        graph.remove_node(&PathBuf::from("b.py")).unwrap();
+        assert_consistent(&graph);
+        graph
+            .replace_edges_for_source(
+                &PathBuf::from("d.py"),
+                vec![edge("d.py", "a.py", DependencyType::Import, 2)],
+            )
+            .unwrap();
+        assert_consistent(&graph);
+        assert_eq!(
+            graph
+                .get_dependents(&PathBuf::from("c.py"), &all_types)
+                .unwrap(),
+            vec![PathBuf::from("a.py")]
+        );
+    }
+
+    #[test]
+    fn save_replaces_the_graph_without_leaving_a_temp_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.json");
+        fs::write(&path, "stale").unwrap();
+
+        let graph = graph(&[edge("a.py", "b.py", DependencyType::Import, 1)]);
+        graph.save(&path).unwrap();
+
+        let loaded = DependencyGraph::load(&path).unwrap();
+        assert_eq!(loaded.edge_count(), 1);
+        assert!(!dir.path().join("graph.json.tmp").exists());
+    }
+
+    #[test]
+    fn llamaindex_metadata_centrality_follows_graph_changes() {
+        let mut graph = graph(&[
+            edge("a.py", "b.py", DependencyType::Import, 1),
+            edge("c.py", "b.py", DependencyType::Import, 1),
+        ]);
+        let (pagerank, betweenness) = metadata_centrality(&graph, "b.py");
+        let expected = graph.pagerank(0.85, 50)[&PathBuf::from("b.py")];
+        assert!((pagerank - expected as f64).abs() < 1e-6);
+        assert_eq!(betweenness, 0.0);
+
+        // b.py now sits on the only path from a.py and c.py to d.py
+        graph
+            .add_node(&PathBuf::from("d.py"), NodeType::File)
+            .unwrap();
+        graph
+            .add_edge(edge("b.py", "d.py", DependencyType::Import, 1))
+            .unwrap();
+        let (_, betweenness) = metadata_centrality(&graph, "b.py");
+        let expected = graph.betweenness_centrality()[&PathBuf::from("b.py")];
+        assert!(betweenness > 0.0);
+        assert!((betweenness - expected as f64).abs() < 1e-6);
+
+        graph.remove_node(&PathBuf::from("d.py")).unwrap();
+        assert_eq!(metadata_centrality(&graph, "b.py").1, 0.0);
+    }
+
+    #[test]
+    fn adjacency_matrix_counts_edges_between_nodes() {
+        let graph = graph(&[
+            edge("b.rs", "a.rs", DependencyType::Import, 1),
+            edge("b.rs", "a.rs", DependencyType::FunctionCall, 4),
+            edge("a.rs", "c.rs", DependencyType::Import, 2),
+        ]);
+
+        let (nodes, matrix) = graph.to_adjacency_matrix();
+        assert_eq!(nodes, paths(&["a.rs", "b.rs", "c.rs"]));
+        assert_eq!(matrix, vec![vec![0, 0, 1], vec![2, 0, 0], vec![0, 0, 0]]);
+
+        let (_, triplets) = graph.to_adjacency_triplets();
+        assert_eq!(triplets, vec![(0, 2, 1), (1, 0, 2)]);
+    }
+
+    #[test]
+    fn module_instability_follows_import_coupling() {
+        let graph = graph(&[
+            edge(
+                "/p/api/handler.rs",
+                "/p/core/model.rs",
+                DependencyType::Import,
+                1,
+            ),
+            edge(
+                "/p/api/handler.rs",
+                "/p/db/store.rs",
+                DependencyType::Import,
+                2,
+            ),
+            edge(
+                "/p/db/store.rs",
+                "/p/core/model.rs",
+                DependencyType::Import,
+                1,
+            ),
+            edge("/p/db/store.rs", "/p/db/pool.rs", DependencyType::Import, 2),
+        ]);
+
+        let summary: Vec<(PathBuf, usize, usize, f64)> = graph
+            .module_instability(Path::new("/p"))
+            .into_iter()
+            .map(|m| {
+                (
+                    m.module,
+                    m.afferent_coupling,
+                    m.efferent_coupling,
+                    m.instability,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (PathBuf::from("api"), 0, 2, 1.0),
+                (PathBuf::from("db"), 1, 1, 0.5),
+                (PathBuf::from("core"), 2, 0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn imports_order_and_layer_a_dag() {
+        let graph = graph(&[
+            edge("main.rs", "cli.rs", DependencyType::Import, 1),
+            edge("main.rs", "util.rs", DependencyType::Import, 2),
+            edge("cli.rs", "util.rs", DependencyType::Import, 1),
+            edge("lib.rs", "util.rs", DependencyType::Import, 1),
+            edge("main.rs", "lib.rs", DependencyType::FunctionCall, 3),
+        ]);
+
+        assert_eq!(
+            graph.topological_order(),
+            Ok(paths(&["util.rs", "cli.rs", "lib.rs", "main.rs"]))
+        );
+        assert_eq!(
+            graph.layers(),
+            vec![
+                paths(&["util.rs"]),
+                paths(&["cli.rs", "lib.rs"]),
+                paths(&["main.rs"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn import_cycles_prevent_an_order_and_share_a_layer() {
+        let graph = graph(&[
+            edge("a.rs", "b.rs", DependencyType::Import, 1),
+            edge("b.rs", "a.rs", DependencyType::Import, 1),
+            edge("c.rs", "a.rs", DependencyType::Import, 1),
+        ]);
+
+        assert_eq!(
+            graph.topological_order(),
+            Err(vec![paths(&["a.rs", "b.rs"])])
+        );
+        assert_eq!(
+            graph.layers(),
+            vec![paths(&["a.rs", "b.rs"]), paths(&["c.rs"])]
+        );
+    }
 }
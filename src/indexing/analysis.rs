@@ -1,4 +1,4 @@
-use crate::llm::Model;
+use crate::llm::{GenerationConfig, Model, RetryingModel};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -41,11 +41,15 @@ pub struct AnalyzerConfig {
     pub pattern_strength_threshold: f32,
     pub max_relationships_per_type: usize,
     pub context_window_size: usize,
+    pub generation: GenerationConfig,
 }
 
 impl RelationshipAnalyzer {
     pub fn new(llm: Arc<dyn Model>, config: AnalyzerConfig) -> Self {
-        Self { llm, config }
+        Self {
+            llm: RetryingModel::from_config(llm, &config.generation),
+            config,
+        }
     }
 
     pub async fn analyze_content(&self, content: &str) -> Result<RelationshipAnalysis, String> {
@@ -82,7 +86,10 @@ impl RelationshipAnalyzer {
             content
         );
 
-        let response = self.llm.generate(&prompt).await?;
+        let response = self
+            .llm
+            .generate_with_config(&prompt, self.config.generation.clone())
+            .await?;
         self.parse_relationship_response(&response.text)
     }
 
@@ -105,7 +112,10 @@ impl RelationshipAnalyzer {
             content
         );
 
-        let response = self.llm.generate(&prompt).await?;
+        let response = self
+            .llm
+            .generate_with_config(&prompt, self.config.generation.clone())
+            .await?;
         self.parse_relationship_response(&response.text)
     }
 
@@ -1,11 +1,13 @@
 use super::{AnalysisError, BaseAnalyzer};
 use crate::analyzers::{CodeAnalyzer, Dependency, DependencyType};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CodeAnalysis {
+    pub file: PathBuf,
+    pub language: Option<String>,
     pub functions: Vec<FunctionInfo>,
     pub modules: Vec<ModuleInfo>,
     pub dependencies: Vec<Dependency>,
@@ -52,6 +54,9 @@ pub struct CodeBaseAnalyzer {
 
 #[derive(Debug, Clone)]
 pub struct CodeAnalyzerConfig {
+    /// File the analyzed content was read from. The dependency analyzers
+    /// read it themselves, and it is recorded as `CodeAnalysis::file`.
+    pub path: PathBuf,
     pub max_depth: usize,
     pub include_tests: bool,
     pub analysis_level: AnalysisLevel,
@@ -69,12 +74,8 @@ impl CodeBaseAnalyzer {
         Self { analyzers, config }
     }
 
-    fn analyze_functions(
-        &self,
-        content: &str,
-        path: &PathBuf,
-    ) -> Result<Vec<FunctionInfo>, AnalysisError> {
-        let mut functions = Vec::new();
+    fn analyze_dependencies(&self, path: &PathBuf) -> Result<Vec<Dependency>, AnalysisError> {
+        let mut dependencies = Vec::new();
 
         for analyzer in &self.analyzers {
             if let Some(ext) = path.extension() {
@@ -82,34 +83,101 @@ impl CodeBaseAnalyzer {
                     .supported_extensions()
                     .contains(&ext.to_str().unwrap_or(""))
                 {
-                    let deps = analyzer.analyze(path)?;
-
-                    for dep in deps {
-                        if let DependencyType::FunctionDefinition = dep.dependency_type {
-                            functions.push(FunctionInfo {
-                                name: dep
-                                    .target
-                                    .file_name()
-                                    .unwrap()
-                                    .to_string_lossy()
-                                    .to_string(),
-                                signature: "".to_string(), // Extract from metadata
-                                body: "".to_string(),      // Extract from content
-                                dependencies: vec![dep],
-                                complexity: 1, // Calculate cyclomatic complexity
-                                location: Location {
-                                    file: path.clone(),
-                                    start_line: dep.metadata.line_number.unwrap_or(0) as u32,
-                                    end_line: dep.metadata.line_number.unwrap_or(0) as u32,
-                                },
-                            });
-                        }
-                    }
+                    dependencies.extend(analyzer.analyze(path)?);
                 }
             }
         }
 
-        Ok(functions)
+        Ok(dependencies)
+    }
+
+    fn analyze_functions(&self, dependencies: &[Dependency], path: &PathBuf) -> Vec<FunctionInfo> {
+        dependencies
+            .iter()
+            .filter(|dep| dep.dependency_type == DependencyType::FunctionDefinition)
+            .map(|dep| FunctionInfo {
+                name: dep
+                    .target
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string(),
+                signature: "".to_string(), // Extract from metadata
+                body: "".to_string(),      // Extract from content
+                dependencies: vec![dep.clone()],
+                complexity: 1, // Calculate cyclomatic complexity
+                location: Location {
+                    file: path.clone(),
+                    start_line: dep.metadata.line_number.unwrap_or(0) as u32,
+                    end_line: dep.metadata.line_number.unwrap_or(0) as u32,
+                },
+            })
+            .collect()
+    }
+
+    /// `loc` counts non-blank, non-comment lines. `dependency_count` is the
+    /// number of distinct import and call targets, and `modularity_score` the
+    /// share of those that stay inside the file's module: calls to functions
+    /// it defines and imports of files in its own directory. A file with no
+    /// imports or calls scores 1.0.
+    fn calculate_metrics(
+        &self,
+        content: &str,
+        functions: &[FunctionInfo],
+        dependencies: &[Dependency],
+        path: &PathBuf,
+    ) -> CodeMetrics {
+        let is_python = path.extension().and_then(|ext| ext.to_str()) == Some("py");
+        let mut in_block_comment = false;
+        let mut loc = 0;
+        for line in content.lines().map(str::trim) {
+            if in_block_comment {
+                in_block_comment = !line.contains("*/");
+                continue;
+            }
+            if line.is_empty() || line.starts_with("//") || (is_python && line.starts_with('#')) {
+                continue;
+            }
+            if !is_python && line.starts_with("/*") {
+                in_block_comment = !line.contains("*/");
+                continue;
+            }
+            loc += 1;
+        }
+
+        let local_functions: HashSet<&str> = functions.iter().map(|f| f.name.as_str()).collect();
+        let module_dir = path.parent();
+        let mut intra = HashSet::new();
+        let mut inter = HashSet::new();
+        for dep in dependencies {
+            let is_local = match dep.dependency_type {
+                DependencyType::FunctionCall => {
+                    let name = dep.target.to_string_lossy();
+                    local_functions.contains(name.rsplit("::").next().unwrap_or(&name))
+                }
+                DependencyType::Import => dep.target.is_file() && dep.target.parent() == module_dir,
+                _ => continue,
+            };
+            if is_local {
+                intra.insert(&dep.target);
+            } else {
+                inter.insert(&dep.target);
+            }
+        }
+
+        let dependency_count = intra.union(&inter).count();
+        let modularity_score = if dependency_count == 0 {
+            1.0
+        } else {
+            intra.len() as f32 / (intra.len() + inter.len()) as f32
+        };
+
+        CodeMetrics {
+            loc,
+            complexity: functions.iter().map(|f| f.complexity).sum(),
+            dependency_count: dependency_count as u32,
+            modularity_score,
+        }
     }
 }
 
@@ -123,16 +191,135 @@ impl BaseAnalyzer for CodeBaseAnalyzer {
         config: &Self::Config,
     ) -> Result<Self::Output, AnalysisError> {
         let content_str = String::from_utf8_lossy(content);
-        let path = PathBuf::from("temp.rs"); // Replace with actual path
+        let path = config.path.clone();
 
-        let functions = self.analyze_functions(&content_str, &path)?;
-        let metrics = self.calculate_metrics(&content_str, &functions)?;
+        let dependencies = self.analyze_dependencies(&path)?;
+        let functions = self.analyze_functions(&dependencies, &path);
+        let metrics = self.calculate_metrics(&content_str, &functions, &dependencies, &path);
+
+        let language = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("rs") => Some("rust".to_string()),
+            Some("py") => Some("python".to_string()),
+            _ => None,
+        };
 
         Ok(CodeAnalysis {
+            file: path,
+            language,
             functions,
-            modules: Vec::new(),      // Implement module analysis
-            dependencies: Vec::new(), // Aggregate dependencies
+            modules: Vec::new(), // Implement module analysis
+            dependencies,
             metrics,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzers::{DependencyMetadata, RustAnalyzer};
+    use std::path::Path;
+
+    const FIXTURE: &str = "// Parsing helpers
+use crate::util;
+
+/* Block comments
+   don't count either */
+fn helper() -> u32 {
+    1
+}
+
+fn run() -> u32 {
+    helper() + util::parse() + helper()
+}
+";
+
+    fn config(path: &Path) -> CodeAnalyzerConfig {
+        CodeAnalyzerConfig {
+            path: path.to_path_buf(),
+            max_depth: 1,
+            include_tests: false,
+            analysis_level: AnalysisLevel::Basic,
+        }
+    }
+
+    fn dependency(source: &Path, target: PathBuf, dependency_type: DependencyType) -> Dependency {
+        Dependency {
+            source: source.to_path_buf(),
+            target,
+            dependency_type,
+            metadata: DependencyMetadata::default(),
+        }
+    }
+
+    #[test]
+    fn metrics_count_code_lines_and_local_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        let sibling = dir.path().join("util.rs");
+        std::fs::write(&path, FIXTURE).unwrap();
+        std::fs::write(&sibling, "pub fn parse() -> u32 { 0 }\n").unwrap();
+
+        let dependencies = vec![
+            dependency(
+                &path,
+                PathBuf::from("helper"),
+                DependencyType::FunctionDefinition,
+            ),
+            dependency(
+                &path,
+                PathBuf::from("run"),
+                DependencyType::FunctionDefinition,
+            ),
+            dependency(&path, sibling, DependencyType::Import),
+            dependency(&path, PathBuf::from("helper"), DependencyType::FunctionCall),
+            dependency(&path, PathBuf::from("helper"), DependencyType::FunctionCall),
+            dependency(
+                &path,
+                PathBuf::from("util::parse"),
+                DependencyType::FunctionCall,
+            ),
+            dependency(
+                &path,
+                PathBuf::from("serde::Serialize"),
+                DependencyType::Import,
+            ),
+        ];
+        let analyzer = CodeBaseAnalyzer::new(Vec::new(), config(&path));
+        let functions = analyzer.analyze_functions(&dependencies, &path);
+        let metrics = analyzer.calculate_metrics(FIXTURE, &functions, &dependencies, &path);
+
+        assert_eq!(metrics.loc, 7);
+        assert_eq!(metrics.complexity, 2);
+        // helper and util.rs stay in the module, util::parse and serde leave it
+        assert_eq!(metrics.dependency_count, 4);
+        assert_eq!(metrics.modularity_score, 0.5);
+    }
+
+    #[test]
+    fn analyze_reads_dependencies_from_the_configured_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        std::fs::write(&path, FIXTURE).unwrap();
+
+        let analyzer = CodeBaseAnalyzer::new(vec![Box::new(RustAnalyzer::new())], config(&path));
+        let analysis = analyzer
+            .analyze(FIXTURE.as_bytes(), &config(&path))
+            .unwrap();
+
+        assert_eq!(analysis.file, path);
+        assert_eq!(analysis.language.as_deref(), Some("rust"));
+        let mut names: Vec<&str> = analysis.functions.iter().map(|f| f.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["helper", "run"]);
+        assert!(analysis
+            .dependencies
+            .iter()
+            .all(|dep| dep.source == path || dep.dependency_type == DependencyType::TypeUsage));
+        assert_eq!(analysis.metrics.loc, 7);
+        // The call to helper stays in the file, util::parse leaves it
+        assert!(analysis.metrics.dependency_count >= 2);
+        assert!(analysis.metrics.modularity_score > 0.0);
+        assert!(analysis.metrics.modularity_score < 1.0);
+    }
+}
@@ -30,4 +30,6 @@ pub enum AnalysisError {
     AnalysisFailed(String),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Analyzer(#[from] crate::analyzers::AnalyzerError),
 }
@@ -1,6 +1,5 @@
-use super::embeddings::{EmbeddingVector, TextChunk};
+use super::embeddings::{DistanceMetric, EmbeddingVector, TextChunk};
 use std::collections::HashMap;
-use std::sync::Arc;
 
 pub struct EmbeddingIndex {
     vectors: Vec<(EmbeddingVector, TextChunk)>,
@@ -17,8 +16,15 @@ struct DimensionIndex {
 struct IndexConfig {
     num_dimensions_to_index: usize,
     similarity_threshold: f32,
+    metric: DistanceMetric,
+    /// Fewer candidates than this (or than `top_k`) falls back to scoring
+    /// every vector, so sparse dimension matches can't hide the nearest ones.
+    min_candidates: usize,
 }
 
+/// Share of a dimension's value range counted as close to the query's value.
+const DIMENSION_WINDOW: f32 = 0.1;
+
 impl EmbeddingIndex {
     pub fn new(config: IndexConfig) -> Self {
         Self {
@@ -56,28 +62,43 @@ impl EmbeddingIndex {
 
     pub fn search(&self, query_vector: &EmbeddingVector, top_k: usize) -> Vec<(TextChunk, f32)> {
         // Find candidate vectors using dimension indices
-        let candidates = self.find_candidates(query_vector);
+        let candidates = self.find_candidates(query_vector, top_k);
 
         // Calculate exact similarities for candidates
         let mut results: Vec<_> = candidates
             .into_iter()
             .map(|idx| {
-                let (vector, chunk) = &self.vectors[idx];
-                let similarity = vector.cosine_similarity(query_vector);
-                (chunk.clone(), similarity)
+                let (vector, _) = &self.vectors[idx];
+                (idx, vector.similarity(query_vector, self.config.metric))
             })
             .filter(|(_, similarity)| *similarity >= self.config.similarity_threshold)
             .collect();
 
-        // Sort by similarity and take top k
-        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        // Candidates come out of a HashMap, so break ties by source file and
+        // insertion order to keep results stable between runs
+        results.sort_by(|a, b| {
+            b.1.total_cmp(&a.1)
+                .then_with(|| {
+                    let source = |idx: usize| &self.vectors[idx].1.metadata.source_file;
+                    source(a.0).cmp(source(b.0))
+                })
+                .then(a.0.cmp(&b.0))
+        });
         results.truncate(top_k);
 
         results
+            .into_iter()
+            .map(|(idx, similarity)| (self.vectors[idx].1.clone(), similarity))
+            .collect()
     }
 
-    fn find_candidates(&self, query_vector: &EmbeddingVector) -> Vec<usize> {
-        let mut candidate_scores: HashMap<usize, u32> = HashMap::new();
+    fn find_candidates(&self, query_vector: &EmbeddingVector, top_k: usize) -> Vec<usize> {
+        // Per-dimension closeness says nothing about unnormalized dot products
+        if self.config.metric == DistanceMetric::DotProduct {
+            return (0..self.vectors.len()).collect();
+        }
+
+        let mut candidate_scores: HashMap<usize, usize> = HashMap::new();
 
         // Check each indexed dimension
         for (dim_idx, value) in query_vector.0.iter().enumerate() {
@@ -85,7 +106,9 @@ impl EmbeddingIndex {
                 break;
             }
 
-            let dimension_index = &self.dimension_indices[dim_idx];
+            let Some(dimension_index) = self.dimension_indices.get(dim_idx) else {
+                break;
+            };
             let similar_vectors = self.find_similar_in_dimension(dimension_index, *value);
 
             for vector_idx in similar_vectors {
@@ -94,16 +117,31 @@ impl EmbeddingIndex {
         }
 
         // Return candidates that appear in multiple dimensions
-        candidate_scores
+        let candidates: Vec<usize> = candidate_scores
             .into_iter()
             .filter(|(_, score)| *score > self.config.num_dimensions_to_index / 3)
             .map(|(idx, _)| idx)
-            .collect()
+            .collect();
+
+        let wanted = self
+            .config
+            .min_candidates
+            .max(top_k)
+            .min(self.vectors.len());
+        if candidates.len() < wanted {
+            return (0..self.vectors.len()).collect();
+        }
+        candidates
     }
 
     fn find_similar_in_dimension(&self, index: &DimensionIndex, value: f32) -> Vec<usize> {
         let mut similar = Vec::new();
-        let threshold = 0.1; // Adjustable similarity threshold
+        // Normalized embeddings spread each dimension over a small range, so
+        // closeness is judged against this dimension's own spread
+        let threshold = match (index.values.first(), index.values.last()) {
+            (Some(min), Some(max)) => (max.0 - min.0) * DIMENSION_WINDOW,
+            _ => return similar,
+        };
 
         // Binary search for closest value
         let pos = index.values.binary_search_by(|probe| {
@@ -135,3 +173,177 @@ impl EmbeddingIndex {
         similar
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexing::embeddings::ChunkMetadata;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashSet;
+
+    fn config(metric: DistanceMetric, min_candidates: usize) -> IndexConfig {
+        IndexConfig {
+            num_dimensions_to_index: 3,
+            similarity_threshold: f32::MIN,
+            metric,
+            min_candidates,
+        }
+    }
+
+    fn chunk(name: &str) -> TextChunk {
+        TextChunk {
+            content: name.to_string(),
+            metadata: ChunkMetadata {
+                start_idx: 0,
+                end_idx: name.len(),
+                source_file: format!("{}.rs", name),
+                language: Some("rust".to_string()),
+                symbol: None,
+                line_range: None,
+            },
+        }
+    }
+
+    fn index(
+        metric: DistanceMetric,
+        min_candidates: usize,
+        vectors: &[(&str, Vec<f32>)],
+    ) -> EmbeddingIndex {
+        let mut index = EmbeddingIndex::new(config(metric, min_candidates));
+        for (name, values) in vectors {
+            index.add(EmbeddingVector(values.clone()), chunk(name));
+        }
+        index
+    }
+
+    fn ranking(index: &EmbeddingIndex, query: &[f32]) -> Vec<String> {
+        index
+            .search(&EmbeddingVector(query.to_vec()), 10)
+            .into_iter()
+            .map(|(chunk, _)| chunk.content)
+            .collect()
+    }
+
+    #[test]
+    fn cosine_and_dot_product_rank_unequal_norms_differently() {
+        // `aligned` points the same way as the query, `long` is off-axis but much longer
+        let vectors = [
+            ("aligned", vec![1.0, 0.0, 0.0]),
+            ("long", vec![3.0, 3.0, 0.0]),
+        ];
+        let query = [1.0, 0.0, 0.0];
+
+        let cosine = index(DistanceMetric::Cosine, 10, &vectors);
+        let dot = index(DistanceMetric::DotProduct, 10, &vectors);
+        assert_eq!(ranking(&cosine, &query), vec!["aligned", "long"]);
+        assert_eq!(ranking(&dot, &query), vec!["long", "aligned"]);
+    }
+
+    #[test]
+    fn identical_vectors_rank_by_source_file_on_every_search() {
+        let vectors = [
+            ("charlie", vec![1.0, 2.0, 3.0]),
+            ("alpha", vec![1.0, 2.0, 3.0]),
+            ("bravo", vec![1.0, 2.0, 3.0]),
+        ];
+        let query = [1.0, 2.0, 3.0];
+
+        for _ in 0..5 {
+            let index = index(DistanceMetric::Cosine, 10, &vectors);
+            assert_eq!(ranking(&index, &query), vec!["alpha", "bravo", "charlie"]);
+        }
+    }
+
+    fn unit(values: Vec<f32>) -> Vec<f32> {
+        let norm = values.iter().map(|v| v * v).sum::<f32>().sqrt();
+        values.into_iter().map(|v| v / norm).collect()
+    }
+
+    #[test]
+    fn normalized_embeddings_keep_recall_against_brute_force() {
+        // Clusters of unit vectors in 128 dimensions, where each indexed
+        // dimension only spans a few tenths
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut jitter = |center: &[f32]| -> Vec<f32> {
+            unit(
+                center
+                    .iter()
+                    .map(|v| v + rng.gen_range(-0.005..0.005))
+                    .collect(),
+            )
+        };
+        let mut center_rng = StdRng::seed_from_u64(3);
+        let centers: Vec<Vec<f32>> = (0..20)
+            .map(|_| unit((0..128).map(|_| center_rng.gen_range(-1.0..1.0)).collect()))
+            .collect();
+
+        let mut vectors = Vec::new();
+        for (cluster, center) in centers.iter().enumerate() {
+            for member in 0..10 {
+                vectors.push((format!("c{}_{}", cluster, member), jitter(center)));
+            }
+        }
+        let named: Vec<(&str, Vec<f32>)> = vectors
+            .iter()
+            .map(|(name, values)| (name.as_str(), values.clone()))
+            .collect();
+        let index = index(DistanceMetric::Cosine, 10, &named);
+
+        let k = 10;
+        let mut found = 0;
+        for center in &centers {
+            let query = EmbeddingVector(jitter(center));
+            let mut exact: Vec<(&str, f32)> = vectors
+                .iter()
+                .map(|(name, values)| {
+                    let similarity =
+                        EmbeddingVector(values.clone()).similarity(&query, DistanceMetric::Cosine);
+                    (name.as_str(), similarity)
+                })
+                .collect();
+            exact.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let expected: HashSet<&str> = exact.iter().take(k).map(|(name, _)| *name).collect();
+
+            found += index
+                .search(&query, k)
+                .into_iter()
+                .filter(|(chunk, _)| expected.contains(chunk.content.as_str()))
+                .count();
+        }
+        let recall = found as f32 / (k * centers.len()) as f32;
+        assert!(recall >= 0.9, "recall {}", recall);
+    }
+
+    #[test]
+    fn too_few_candidates_falls_back_to_every_vector() {
+        let vectors = [
+            ("x", vec![1.0, 0.0, 0.0]),
+            ("y", vec![0.0, 1.0, 0.0]),
+            ("z", vec![0.0, 0.0, 1.0]),
+        ];
+        // Outside every indexed dimension's range, so no dimension matches
+        let query = EmbeddingVector(vec![10.0, 10.0, 10.0]);
+
+        let index = index(DistanceMetric::Cosine, 0, &vectors);
+        assert_eq!(index.find_candidates(&query, 2).len(), 3);
+        assert_eq!(index.search(&query, 2).len(), 2);
+    }
+
+    #[test]
+    fn dot_product_skips_candidate_pruning() {
+        let vectors = [
+            ("near", vec![1.0, 1.0, 1.0]),
+            ("mid", vec![5.0, 5.0, 5.0]),
+            ("far", vec![9.0, 9.0, 9.0]),
+            ("farthest", vec![10.0, 10.0, 10.0]),
+        ];
+        let query = EmbeddingVector(vec![1.0, 1.0, 1.0]);
+
+        let cosine = index(DistanceMetric::Cosine, 0, &vectors);
+        assert_eq!(cosine.find_candidates(&query, 1), vec![0]);
+
+        let dot = index(DistanceMetric::DotProduct, 0, &vectors);
+        assert_eq!(dot.find_candidates(&query, 1), vec![0, 1, 2, 3]);
+    }
+}
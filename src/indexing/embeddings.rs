@@ -1,19 +1,24 @@
-use crate::llm::Model;
+use crate::analyzers::FunctionComplexity;
+use crate::llm::{CodeTokenizer, Model};
 use dashmap::DashMap;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokenizers::Tokenizer;
 use tokio::sync::RwLock;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct EmbeddingVector(Vec<f32>);
+pub struct EmbeddingVector(pub(crate) Vec<f32>);
 
 #[derive(Clone)]
 pub struct Embeddings {
     model: Arc<dyn Model>,
     cache: Arc<DashMap<String, EmbeddingVector>>,
-    tokenizer: Arc<Tokenizer>,
+    tokenizer: CodeTokenizer,
     config: EmbeddingConfig,
 }
 
@@ -24,6 +29,19 @@ pub struct EmbeddingConfig {
     pub chunk_overlap: usize,
     pub cache_capacity: usize,
     pub batch_size: usize,
+    /// Cache file loaded on construction and written back on drop.
+    #[serde(default)]
+    pub persist_cache: Option<PathBuf>,
+}
+
+/// Bumped whenever the on-disk cache layout changes.
+const CACHE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct PersistedCache {
+    version: u32,
+    dimension: usize,
+    entries: HashMap<String, EmbeddingVector>,
 }
 
 #[derive(Clone, Debug)]
@@ -38,21 +56,77 @@ pub struct ChunkMetadata {
     pub end_idx: usize,
     pub source_file: String,
     pub language: Option<String>,
+    /// Function or class the chunk belongs to, when chunked as code.
+    pub symbol: Option<String>,
+    /// 1-based inclusive line range of the code unit, when chunked as code.
+    pub line_range: Option<(usize, usize)>,
 }
 
 impl Embeddings {
     pub fn new(model: Arc<dyn Model>, tokenizer: Arc<Tokenizer>, config: EmbeddingConfig) -> Self {
-        Self {
+        let mut embeddings = Self {
             model,
             cache: Arc::new(DashMap::with_capacity(config.cache_capacity)),
-            tokenizer,
+            tokenizer: CodeTokenizer::new().with_tokenizer(tokenizer),
             config,
+        };
+
+        // A missing or unreadable cache just means starting cold
+        if let Some(path) = embeddings.config.persist_cache.clone() {
+            let _ = embeddings.load_cache(&path);
         }
+
+        embeddings
+    }
+
+    /// Writes the cache to `path`, keyed by a hash of each text.
+    pub fn save_cache(&self, path: &Path) -> Result<(), String> {
+        let persisted = PersistedCache {
+            version: CACHE_FORMAT_VERSION,
+            dimension: self.config.dimension,
+            entries: self
+                .cache
+                .iter()
+                .map(|entry| (entry.key().clone(), entry.value().clone()))
+                .collect(),
+        };
+
+        let file = fs::File::create(path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        bincode::serialize_into(BufWriter::new(file), &persisted)
+            .map_err(|e| format!("Failed to write embedding cache: {}", e))
+    }
+
+    /// Loads a cache written by `save_cache`. A cache from another format
+    /// version or embedding dimension is discarded rather than mixed in.
+    pub fn load_cache(&mut self, path: &Path) -> Result<(), String> {
+        let file = fs::File::open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let persisted: PersistedCache = bincode::deserialize_from(BufReader::new(file))
+            .map_err(|e| format!("Failed to read embedding cache: {}", e))?;
+
+        if persisted.version != CACHE_FORMAT_VERSION || persisted.dimension != self.config.dimension
+        {
+            return Err(format!(
+                "Discarding embedding cache built for version {} with dimension {}",
+                persisted.version, persisted.dimension
+            ));
+        }
+
+        for (key, vector) in persisted.entries {
+            self.cache.insert(key, vector);
+        }
+        Ok(())
+    }
+
+    fn cache_key(text: &str) -> String {
+        format!("{:x}", md5::compute(text))
     }
 
     pub async fn embed_text(&self, text: &str) -> Result<EmbeddingVector, String> {
         // Check cache first
-        if let Some(cached) = self.cache.get(text) {
+        let key = Self::cache_key(text);
+        if let Some(cached) = self.cache.get(&key) {
             return Ok(cached.clone());
         }
 
@@ -61,8 +135,7 @@ impl Embeddings {
         let embedding_vector = EmbeddingVector(embedding);
 
         // Cache the result
-        self.cache
-            .insert(text.to_string(), embedding_vector.clone());
+        self.cache.insert(key, embedding_vector.clone());
 
         Ok(embedding_vector)
     }
@@ -93,9 +166,7 @@ impl Embeddings {
 
         chunks
             .into_iter()
-            .enumerate()
-            .map(|(i, content)| {
-                let start_idx = i * (self.config.chunk_size - self.config.chunk_overlap);
+            .map(|(start_idx, content)| {
                 let end_idx = start_idx + content.len();
 
                 TextChunk {
@@ -105,41 +176,253 @@ impl Embeddings {
                         end_idx,
                         source_file: metadata.source_file.clone(),
                         language: metadata.language.clone(),
+                        symbol: metadata.symbol.clone(),
+                        line_range: metadata.line_range,
                     },
                 }
             })
             .collect()
     }
 
-    fn create_overlapping_chunks(&self, text: &str) -> Vec<String> {
-        let tokens = self.tokenizer.encode(text, false).unwrap();
-        let token_ids = tokens.get_ids();
+    /// Chunks source code on function and class boundaries. Python files
+    /// are split by top-level indentation; other languages use the outermost
+    /// entries of `functions` (as reported by `analyze_complexity`). Units
+    /// larger than `chunk_size` tokens fall back to token windows, and files
+    /// without any units are chunked as plain text.
+    pub fn chunk_code(
+        &self,
+        text: &str,
+        metadata: ChunkMetadata,
+        functions: &[FunctionComplexity],
+    ) -> Vec<TextChunk> {
+        let is_python = matches!(metadata.language.as_deref(), Some("python") | Some("py"));
+        let units = if is_python {
+            python_units(text)
+        } else {
+            outermost_functions(functions)
+        };
 
-        if token_ids.len() <= self.config.chunk_size {
-            return vec![text.to_string()];
+        if units.is_empty() {
+            return self.chunk_text(text, metadata);
         }
 
+        let line_starts = line_offsets(text);
+        let line_count = line_starts.len();
+        let byte_at = |line: usize| {
+            line_starts
+                .get(line.saturating_sub(1))
+                .copied()
+                .unwrap_or(text.len())
+        };
+
         let mut chunks = Vec::new();
-        let mut start = 0;
+        let mut next_line = 1;
+        for (name, start, end) in units {
+            let end = end.min(line_count);
+            if start < next_line || start > end {
+                continue;
+            }
+            if start > next_line {
+                self.push_code_chunks(
+                    &mut chunks,
+                    text,
+                    &metadata,
+                    None,
+                    (next_line, start - 1),
+                    &byte_at,
+                );
+            }
+            self.push_code_chunks(
+                &mut chunks,
+                text,
+                &metadata,
+                Some(name),
+                (start, end),
+                &byte_at,
+            );
+            next_line = end + 1;
+        }
+        if next_line <= line_count {
+            self.push_code_chunks(
+                &mut chunks,
+                text,
+                &metadata,
+                None,
+                (next_line, line_count),
+                &byte_at,
+            );
+        }
 
-        while start < token_ids.len() {
-            let end = (start + self.config.chunk_size).min(token_ids.len());
-            let chunk_tokens = &token_ids[start..end];
+        chunks
+    }
+
+    /// Emits the lines in `lines` as one chunk, or as token windows when they
+    /// exceed `chunk_size`. Whitespace-only ranges are dropped.
+    fn push_code_chunks(
+        &self,
+        chunks: &mut Vec<TextChunk>,
+        text: &str,
+        metadata: &ChunkMetadata,
+        symbol: Option<String>,
+        lines: (usize, usize),
+        byte_at: &dyn Fn(usize) -> usize,
+    ) {
+        let start_idx = byte_at(lines.0);
+        let end_idx = byte_at(lines.1 + 1);
+        let content = &text[start_idx..end_idx];
+        if content.trim().is_empty() {
+            return;
+        }
+
+        let unit_metadata = ChunkMetadata {
+            start_idx,
+            end_idx,
+            source_file: metadata.source_file.clone(),
+            language: metadata.language.clone(),
+            symbol: symbol.or_else(|| metadata.symbol.clone()),
+            line_range: Some(lines),
+        };
+
+        if self.tokenizer.count_tokens(content) <= self.config.chunk_size {
+            chunks.push(TextChunk {
+                content: content.to_string(),
+                metadata: unit_metadata,
+            });
+            return;
+        }
+
+        chunks.extend(
+            self.chunk_text(content, unit_metadata.clone())
+                .into_iter()
+                .map(|mut chunk| {
+                    chunk.metadata.start_idx += start_idx;
+                    chunk.metadata.end_idx += start_idx;
+                    chunk.metadata.line_range = unit_metadata.line_range;
+                    chunk
+                }),
+        );
+    }
 
-            let chunk_text = self
-                .tokenizer
-                .decode(chunk_tokens, true)
-                .unwrap_or_default();
+    /// Windows of `chunk_size` tokens overlapping by `chunk_overlap`, each
+    /// with the byte offset it starts at in `text`.
+    fn create_overlapping_chunks(&self, text: &str) -> Vec<(usize, String)> {
+        let spans = self.tokenizer.token_spans(text);
 
-            chunks.push(chunk_text);
+        if spans.len() <= self.config.chunk_size {
+            return vec![(0, text.to_string())];
+        }
 
-            start += self.config.chunk_size - self.config.chunk_overlap;
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        let step = self
+            .config
+            .chunk_size
+            .saturating_sub(self.config.chunk_overlap)
+            .max(1);
+
+        while start < spans.len() {
+            let end = (start + self.config.chunk_size).min(spans.len());
+            let (from, to) = (spans[start].start, spans[end - 1].end);
+            chunks.push((from, text[from..to].to_string()));
+
+            if end == spans.len() {
+                break;
+            }
+            start += step;
         }
 
         chunks
     }
 }
 
+/// Byte offset of the start of every line in `text`.
+fn line_offsets(text: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .filter(|&offset| offset < text.len())
+        .collect()
+}
+
+/// Functions not nested inside another function, sorted by start line.
+fn outermost_functions(functions: &[FunctionComplexity]) -> Vec<(String, usize, usize)> {
+    let mut sorted: Vec<&FunctionComplexity> = functions.iter().collect();
+    sorted.sort_by_key(|f| (f.line_range.0, std::cmp::Reverse(f.line_range.1)));
+
+    let mut units: Vec<(String, usize, usize)> = Vec::new();
+    for f in sorted {
+        let (start, end) = f.line_range;
+        if units
+            .last()
+            .map_or(true, |(_, _, last_end)| start > *last_end)
+        {
+            units.push((f.function.clone(), start, end));
+        }
+    }
+    units
+}
+
+/// Top-level `def`/`class` blocks in Python source, including their
+/// decorators. A block ends before the next non-blank line at column zero.
+fn python_units(text: &str) -> Vec<(String, usize, usize)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut units = Vec::new();
+    let mut decorator_start = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if line.starts_with('@') {
+            decorator_start.get_or_insert(i);
+            i += 1;
+            continue;
+        }
+
+        let header = line
+            .strip_prefix("async def ")
+            .or_else(|| line.strip_prefix("def "))
+            .or_else(|| line.strip_prefix("class "));
+        let Some(header) = header else {
+            decorator_start = None;
+            i += 1;
+            continue;
+        };
+
+        let name: String = header
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .collect();
+        let start = decorator_start.take().unwrap_or(i);
+        let mut end = i;
+        let mut j = i + 1;
+        while j < lines.len() {
+            let next = lines[j];
+            if !next.trim().is_empty() {
+                if !next.starts_with(char::is_whitespace) && !next.starts_with(')') {
+                    break;
+                }
+                end = j;
+            }
+            j += 1;
+        }
+
+        units.push((name, start + 1, end + 1));
+        i = end + 1;
+    }
+    units
+}
+
+impl Drop for Embeddings {
+    fn drop(&mut self) {
+        // Clones share the cache, only the last one persists it
+        if Arc::strong_count(&self.cache) > 1 {
+            return;
+        }
+        if let Some(path) = &self.config.persist_cache {
+            let _ = self.save_cache(path);
+        }
+    }
+}
+
 // Similarity calculations
 impl EmbeddingVector {
     pub fn cosine_similarity(&self, other: &EmbeddingVector) -> f32 {
@@ -163,6 +446,49 @@ impl EmbeddingVector {
             .sum::<f32>()
             .sqrt()
     }
+
+    pub fn manhattan_distance(&self, other: &EmbeddingVector) -> f32 {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (a - b).abs())
+            .sum()
+    }
+
+    pub fn dot_product(&self, other: &EmbeddingVector) -> f32 {
+        self.0.iter().zip(other.0.iter()).map(|(a, b)| a * b).sum()
+    }
+
+    /// Distance under `metric`; lower is closer for every metric.
+    pub fn distance(&self, other: &EmbeddingVector, metric: DistanceMetric) -> f32 {
+        match metric {
+            DistanceMetric::Cosine => 1.0 - self.cosine_similarity(other),
+            DistanceMetric::DotProduct => -self.dot_product(other),
+            DistanceMetric::Euclidean => self.euclidean_distance(other),
+            DistanceMetric::Manhattan => self.manhattan_distance(other),
+        }
+    }
+
+    /// Similarity under `metric`; higher is closer. Distances map to `1 / (1 + d)`.
+    /// For normalized vectors cosine and dot product give the same ranking.
+    pub fn similarity(&self, other: &EmbeddingVector, metric: DistanceMetric) -> f32 {
+        match metric {
+            DistanceMetric::Cosine => self.cosine_similarity(other),
+            DistanceMetric::DotProduct => self.dot_product(other),
+            DistanceMetric::Euclidean | DistanceMetric::Manhattan => {
+                1.0 / (1.0 + self.distance(other, metric))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DistanceMetric {
+    #[default]
+    Cosine,
+    DotProduct,
+    Euclidean,
+    Manhattan,
 }
 
 // Cache management
@@ -253,3 +579,113 @@ impl EmbeddingCache {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockModel;
+
+    fn embeddings(dimension: usize) -> Embeddings {
+        Embeddings {
+            model: Arc::new(MockModel::new().with_dimension(dimension)),
+            cache: Arc::new(DashMap::new()),
+            tokenizer: CodeTokenizer::new(),
+            config: EmbeddingConfig {
+                dimension,
+                chunk_size: 512,
+                chunk_overlap: 32,
+                cache_capacity: 16,
+                batch_size: 8,
+                persist_cache: None,
+            },
+        }
+    }
+
+    fn metadata(source_file: &str, language: &str) -> ChunkMetadata {
+        ChunkMetadata {
+            start_idx: 0,
+            end_idx: 0,
+            source_file: source_file.to_string(),
+            language: Some(language.to_string()),
+            symbol: None,
+            line_range: None,
+        }
+    }
+
+    #[test]
+    fn chunk_code_splits_on_function_boundaries() {
+        let text = "def first(a):\n    return a\n\n\ndef second(b):\n    return b * 2\n\n\ndef third(c):\n    return c - 1\n";
+        let chunks = embeddings(8).chunk_code(text, metadata("m.py", "python"), &[]);
+
+        let units: Vec<(Option<&str>, Option<(usize, usize)>, &str)> = chunks
+            .iter()
+            .map(|chunk| {
+                (
+                    chunk.metadata.symbol.as_deref(),
+                    chunk.metadata.line_range,
+                    chunk.content.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            units,
+            vec![
+                (Some("first"), Some((1, 2)), "def first(a):\n    return a\n"),
+                (
+                    Some("second"),
+                    Some((5, 6)),
+                    "def second(b):\n    return b * 2\n"
+                ),
+                (
+                    Some("third"),
+                    Some((9, 10)),
+                    "def third(c):\n    return c - 1\n"
+                ),
+            ]
+        );
+        for chunk in &chunks {
+            assert_eq!(
+                &text[chunk.metadata.start_idx..chunk.metadata.end_idx],
+                chunk.content
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_round_trips_through_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("embeddings.bin");
+        let original = embeddings(8);
+        let vector = original.embed_text("fn parse(input: &str)").await.unwrap();
+        original.save_cache(&path).unwrap();
+
+        let mut restored = embeddings(8);
+        restored.load_cache(&path).unwrap();
+        let key = Embeddings::cache_key("fn parse(input: &str)");
+        assert_eq!(restored.cache.len(), 1);
+        assert_eq!(restored.cache.get(&key).unwrap().0, vector.0);
+    }
+
+    #[tokio::test]
+    async fn cache_from_another_dimension_or_version_is_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("embeddings.bin");
+        let original = embeddings(8);
+        original.embed_text("fn parse(input: &str)").await.unwrap();
+        original.save_cache(&path).unwrap();
+
+        let mut wider = embeddings(16);
+        assert!(wider.load_cache(&path).is_err());
+        assert!(wider.cache.is_empty());
+
+        let stale = PersistedCache {
+            version: CACHE_FORMAT_VERSION + 1,
+            dimension: 8,
+            entries: HashMap::from([("key".to_string(), EmbeddingVector(vec![0.0; 8]))]),
+        };
+        bincode::serialize_into(fs::File::create(&path).unwrap(), &stale).unwrap();
+        let mut current = embeddings(8);
+        assert!(current.load_cache(&path).is_err());
+        assert!(current.cache.is_empty());
+    }
+}
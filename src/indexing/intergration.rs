@@ -1,12 +1,23 @@
 use crate::indexing::{
     embeddings::{
-        AudioEmbeddingGenerator, CodeEmbeddingGenerator, ImageEmbeddingGenerator,
-        VideoEmbeddingGenerator,
+        AudioEmbeddingGenerator, CodeEmbeddingGenerator, EmbeddingGenerator,
+        ImageEmbeddingGenerator, VideoEmbeddingGenerator,
+    },
+    store::{
+        AudioSearchResult, CodeSearchResult, ImageSearchResult, StoreError, StoreWithEmbeddings,
+        VideoSearchResult,
     },
-    store::StoreWithEmbeddings,
 };
 use crate::llm::Model;
+use futures::stream::{self, StreamExt};
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+/// Items processed at once by `MultiModalIndex::index_batch`.
+const BATCH_CONCURRENCY: usize = 8;
 
 // Keep existing ZeroShotIntegration
 pub struct ZeroShotIntegration {
@@ -54,8 +65,9 @@ impl ZeroShotIntegration {
         embedding1: &DynamicEmbedding,
         embedding2: &DynamicEmbedding,
     ) -> Result<f32, String> {
-        // Implement comparison logic using relationships and patterns
-        todo!()
+        // Every modality goes through the same generator and meta-layer, so
+        // aligned embeddings share one space and compare directly
+        Ok(embedding1.vector.cosine_similarity(&embedding2.vector))
     }
 }
 
@@ -68,11 +80,128 @@ pub enum Content {
     Video(VideoAnalysis),
 }
 
+/// Results of a `MultiModalIndex` search, in the searched store's own
+/// result type. Use `hits` for a modality-independent view.
+#[derive(Debug, Clone)]
+pub enum SearchResults {
+    Code(Vec<CodeSearchResult>),
+    Image(Vec<ImageSearchResult>),
+    Audio(Vec<AudioSearchResult>),
+    Video(Vec<VideoSearchResult>),
+    /// Hits from every store, ranked by dynamic-embedding similarity.
+    CrossModal(Vec<SearchHit>),
+}
+
+/// The fields every modality's search result has in common.
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub path: PathBuf,
+    pub similarity: f32,
+    pub modality: Modality,
+    pub snippet: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum SearchError {
+    #[error("Query cannot be used for {0:?} search")]
+    InvalidQuery(Modality),
+    #[error("Store error: {0}")]
+    Store(#[from] StoreError),
+    #[error("Zero-shot processing failed: {0}")]
+    ZeroShot(String),
+}
+
+impl From<String> for SearchError {
+    fn from(message: String) -> Self {
+        SearchError::ZeroShot(message)
+    }
+}
+
+impl SearchResults {
+    pub fn hits(&self) -> Vec<SearchHit> {
+        match self {
+            SearchResults::Code(results) => results
+                .iter()
+                .map(|r| SearchHit {
+                    path: r.path.clone(),
+                    similarity: r.similarity,
+                    modality: Modality::Code,
+                    snippet: r
+                        .analysis
+                        .base
+                        .functions
+                        .first()
+                        .map(|f| f.signature.clone()),
+                })
+                .collect(),
+            SearchResults::Image(results) => results
+                .iter()
+                .map(|r| SearchHit {
+                    path: r.path.clone(),
+                    similarity: r.similarity,
+                    modality: Modality::Image,
+                    snippet: None,
+                })
+                .collect(),
+            SearchResults::Audio(results) => results
+                .iter()
+                .map(|r| SearchHit {
+                    path: r.path.clone(),
+                    similarity: r.similarity,
+                    modality: Modality::Audio,
+                    snippet: None,
+                })
+                .collect(),
+            SearchResults::Video(results) => results
+                .iter()
+                .map(|r| SearchHit {
+                    path: r.path.clone(),
+                    similarity: r.similarity,
+                    modality: Modality::Video,
+                    snippet: None,
+                })
+                .collect(),
+            SearchResults::CrossModal(hits) => hits.clone(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            SearchResults::Code(results) => results.len(),
+            SearchResults::Image(results) => results.len(),
+            SearchResults::Audio(results) => results.len(),
+            SearchResults::Video(results) => results.len(),
+            SearchResults::CrossModal(hits) => hits.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Keeps the best-scoring hit per path and returns the `top_k` highest
+/// similarities, best first. Equal similarities are ordered by path.
+pub fn merge_and_rank(mut results: Vec<SearchHit>, top_k: usize) -> Vec<SearchHit> {
+    results.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+
+    let mut seen = HashSet::new();
+    results.retain(|hit| seen.insert(hit.path.clone()));
+    results.truncate(top_k);
+    results
+}
+
+/// Each store sits behind its own lock so batch ingestion can add to
+/// different modalities concurrently.
 pub struct MultiModalIndex {
-    code_store: StoreWithEmbeddings<CodeEmbeddingGenerator>,
-    image_store: StoreWithEmbeddings<ImageEmbeddingGenerator>,
-    audio_store: StoreWithEmbeddings<AudioEmbeddingGenerator>,
-    video_store: StoreWithEmbeddings<VideoEmbeddingGenerator>,
+    code_store: Mutex<StoreWithEmbeddings<CodeEmbeddingGenerator>>,
+    image_store: Mutex<StoreWithEmbeddings<ImageEmbeddingGenerator>>,
+    audio_store: Mutex<StoreWithEmbeddings<AudioEmbeddingGenerator>>,
+    video_store: Mutex<StoreWithEmbeddings<VideoEmbeddingGenerator>>,
     zero_shot: Arc<ZeroShotIntegration>,
 }
 
@@ -86,36 +215,56 @@ impl MultiModalIndex {
         ));
 
         Ok(Self {
-            code_store: StoreWithEmbeddings::new(
+            code_store: Mutex::new(StoreWithEmbeddings::new(
                 Arc::new(CodeEmbeddingGenerator::new(llm.clone(), config.code_config)),
                 config.code_storage,
-            ),
-            image_store: StoreWithEmbeddings::new(
+            )),
+            image_store: Mutex::new(StoreWithEmbeddings::new(
                 Arc::new(ImageEmbeddingGenerator::new(
                     llm.clone(),
                     config.image_config,
                 )),
                 config.image_storage,
-            ),
-            audio_store: StoreWithEmbeddings::new(
+            )),
+            audio_store: Mutex::new(StoreWithEmbeddings::new(
                 Arc::new(AudioEmbeddingGenerator::new(
                     llm.clone(),
                     config.audio_config,
                 )),
                 config.audio_storage,
-            ),
-            video_store: StoreWithEmbeddings::new(
+            )),
+            video_store: Mutex::new(StoreWithEmbeddings::new(
                 Arc::new(VideoEmbeddingGenerator::new(
                     llm.clone(),
                     config.video_config,
                 )),
                 config.video_storage,
-            ),
+            )),
             zero_shot,
         })
     }
 
     pub async fn index_content(&mut self, content: Content) -> Result<(), IndexError> {
+        self.index_item(content).await
+    }
+
+    /// Indexes `items` with up to `BATCH_CONCURRENCY` in flight. Results are
+    /// returned per item, in input order, so one bad file doesn't abort the
+    /// rest of the batch.
+    pub async fn index_batch(
+        &mut self,
+        items: Vec<Content>,
+    ) -> Result<Vec<Result<(), IndexError>>, IndexError> {
+        let index = &*self;
+        let results = stream::iter(items)
+            .map(|item| index.index_item(item))
+            .buffered(BATCH_CONCURRENCY)
+            .collect()
+            .await;
+        Ok(results)
+    }
+
+    async fn index_item(&self, content: Content) -> Result<(), IndexError> {
         // First, process with zero-shot integration
         let (processed_content, modality) = match &content {
             Content::Code(c) => (c.to_string(), Modality::Code),
@@ -132,29 +281,17 @@ impl MultiModalIndex {
 
         // Then add to appropriate store with enhanced embedding
         match content {
-            Content::Code(code) => {
-                self.code_store
-                    .add_item_with_embedding(code, dynamic_embedding)
-                    .await?;
-            }
+            Content::Code(code) => add_to_store(&self.code_store, code, dynamic_embedding).await,
             Content::Image(image) => {
-                self.image_store
-                    .add_item_with_embedding(image, dynamic_embedding)
-                    .await?;
+                add_to_store(&self.image_store, image, dynamic_embedding).await
             }
             Content::Audio(audio) => {
-                self.audio_store
-                    .add_item_with_embedding(audio, dynamic_embedding)
-                    .await?;
+                add_to_store(&self.audio_store, audio, dynamic_embedding).await
             }
             Content::Video(video) => {
-                self.video_store
-                    .add_item_with_embedding(video, dynamic_embedding)
-                    .await?;
+                add_to_store(&self.video_store, video, dynamic_embedding).await
             }
         }
-
-        Ok(())
     }
 
     pub async fn search(&self, query: MultiModalQuery) -> Result<SearchResults, SearchError> {
@@ -168,21 +305,29 @@ impl MultiModalIndex {
         let results = match query.modality {
             Modality::Code => {
                 self.code_store
+                    .lock()
+                    .await
                     .search_with_embedding(query.into_code_query()?, &enhanced_query)
                     .await?
             }
             Modality::Image => {
                 self.image_store
+                    .lock()
+                    .await
                     .search_with_embedding(query.into_image_query()?, &enhanced_query)
                     .await?
             }
             Modality::Audio => {
                 self.audio_store
+                    .lock()
+                    .await
                     .search_with_embedding(query.into_audio_query()?, &enhanced_query)
                     .await?
             }
             Modality::Video => {
                 self.video_store
+                    .lock()
+                    .await
                     .search_with_embedding(query.into_video_query()?, &enhanced_query)
                     .await?
             }
@@ -197,29 +342,108 @@ impl MultiModalIndex {
         query: MultiModalQuery,
         enhanced_query: &DynamicEmbedding,
     ) -> Result<SearchResults, SearchError> {
-        // Implement cross-modal search using zero-shot integration
-        todo!()
+        let limit = query.limit.max(1);
+
+        // Store scores use per-modality dimensions, so only the aligned
+        // dynamic embeddings are comparable across stores
+        let candidates = [
+            (
+                Modality::Code,
+                self.code_store
+                    .lock()
+                    .await
+                    .nearest_dynamic(enhanced_query, limit)?,
+            ),
+            (
+                Modality::Image,
+                self.image_store
+                    .lock()
+                    .await
+                    .nearest_dynamic(enhanced_query, limit)?,
+            ),
+            (
+                Modality::Audio,
+                self.audio_store
+                    .lock()
+                    .await
+                    .nearest_dynamic(enhanced_query, limit)?,
+            ),
+            (
+                Modality::Video,
+                self.video_store
+                    .lock()
+                    .await
+                    .nearest_dynamic(enhanced_query, limit)?,
+            ),
+        ];
+
+        let mut hits = Vec::new();
+        for (modality, items) in candidates {
+            for (path, embedding) in items {
+                let similarity = self
+                    .zero_shot
+                    .compare_embeddings(enhanced_query, &embedding)
+                    .await?;
+                hits.push(SearchHit {
+                    path: PathBuf::from(path),
+                    similarity,
+                    modality,
+                    snippet: None,
+                });
+            }
+        }
+
+        Ok(SearchResults::CrossModal(merge_and_rank(hits, limit)))
     }
 
     pub async fn save(&self, path: PathBuf) -> Result<(), IndexError> {
         // Save all stores and metadata
-        self.code_store.save(path.join("code")).await?;
-        self.image_store.save(path.join("image")).await?;
-        self.audio_store.save(path.join("audio")).await?;
-        self.video_store.save(path.join("video")).await?;
+        self.code_store.lock().await.save(path.join("code")).await?;
+        self.image_store
+            .lock()
+            .await
+            .save(path.join("image"))
+            .await?;
+        self.audio_store
+            .lock()
+            .await
+            .save(path.join("audio"))
+            .await?;
+        self.video_store
+            .lock()
+            .await
+            .save(path.join("video"))
+            .await?;
         Ok(())
     }
 
     pub async fn load(&mut self, path: PathBuf) -> Result<(), IndexError> {
         // Load all stores and metadata
-        self.code_store.load(path.join("code")).await?;
-        self.image_store.load(path.join("image")).await?;
-        self.audio_store.load(path.join("audio")).await?;
-        self.video_store.load(path.join("video")).await?;
+        self.code_store.get_mut().load(path.join("code")).await?;
+        self.image_store.get_mut().load(path.join("image")).await?;
+        self.audio_store.get_mut().load(path.join("audio")).await?;
+        self.video_store.get_mut().load(path.join("video")).await?;
         Ok(())
     }
 }
 
+/// Embeds `item` with `store`'s generator and adds it. The lock is only held
+/// to fetch the generator and to insert, so items of one modality embed
+/// concurrently.
+async fn add_to_store<T: EmbeddingGenerator>(
+    store: &Mutex<StoreWithEmbeddings<T>>,
+    item: T::Input,
+    dynamic_embedding: DynamicEmbedding,
+) -> Result<(), IndexError> {
+    let embedder = store.lock().await.embedder();
+    let embedding = embedder.embed(&item).await?;
+    store
+        .lock()
+        .await
+        .insert_with_dynamic(item, embedding, dynamic_embedding)?;
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct IndexConfig {
     pub analyzer_config: AnalyzerConfig,
@@ -234,3 +458,188 @@ pub struct IndexConfig {
     pub audio_storage: StorageConfig,
     pub video_storage: StorageConfig,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexing::base::code::{CodeAnalysis, CodeMetrics};
+    use crate::indexing::base::image::{
+        FeatureExtractionLevel, ImageAnalyzerConfig, ImageBaseAnalyzer,
+    };
+    use crate::indexing::base::BaseAnalyzer;
+    use crate::llm::{GenerationConfig, MockModel};
+    use std::path::Path;
+
+    const DIMENSION: usize = 32;
+
+    /// An index whose every embedding comes from `MockModel`.
+    fn index() -> MultiModalIndex {
+        let storage = StorageConfig {
+            vector_dimension: DIMENSION,
+            ..StorageConfig::default()
+        };
+        let config = IndexConfig {
+            analyzer_config: AnalyzerConfig {
+                confidence_threshold: 0.0,
+                pattern_strength_threshold: 0.0,
+                max_relationships_per_type: 10,
+                context_window_size: 2048,
+                generation: GenerationConfig::deterministic(),
+            },
+            embedding_config: ZeroShotConfig::default(),
+            meta_config: MetaLayerConfig {
+                alignment_threshold: 0.0,
+                transformation_types: vec![TransformationType::Normalization],
+            },
+            code_config: CodeEmbeddingConfig {
+                dimension: DIMENSION,
+                feature_weights: FeatureWeights {
+                    syntactic: 1.0,
+                    semantic: 1.0,
+                    structural: 1.0,
+                    dependency: 1.0,
+                },
+                context_window: 512,
+            },
+            image_config: ImageEmbeddingConfig {
+                dimension: DIMENSION,
+                ..ImageEmbeddingConfig::default()
+            },
+            audio_config: AudioEmbeddingConfig {
+                dimension: DIMENSION,
+                ..AudioEmbeddingConfig::default()
+            },
+            video_config: VideoEmbeddingConfig {
+                dimension: DIMENSION,
+                ..VideoEmbeddingConfig::default()
+            },
+            code_storage: storage.clone(),
+            image_storage: storage.clone(),
+            audio_storage: storage.clone(),
+            video_storage: storage,
+        };
+        let model = Arc::new(MockModel::new().with_dimension(DIMENSION));
+        MultiModalIndex::new(model, config).unwrap()
+    }
+
+    fn code(path: &str) -> Content {
+        Content::Code(CodeAnalysis {
+            file: PathBuf::from(path),
+            language: Some("rust".to_string()),
+            functions: Vec::new(),
+            modules: Vec::new(),
+            dependencies: Vec::new(),
+            metrics: CodeMetrics {
+                loc: 1,
+                complexity: 0,
+                dependency_count: 0,
+                modularity_score: 1.0,
+            },
+        })
+    }
+
+    /// A small gradient PNG run through the image analyzer.
+    fn image() -> Content {
+        let mut png = Vec::new();
+        image::RgbImage::from_fn(8, 8, |x, y| image::Rgb([x as u8 * 32, y as u8 * 32, 128]))
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        let config = ImageAnalyzerConfig {
+            feature_extraction_level: FeatureExtractionLevel::Basic,
+            object_detection_threshold: 0.5,
+            max_objects: 10,
+        };
+        let analysis = ImageBaseAnalyzer::new(config.clone())
+            .analyze(&png, &config)
+            .unwrap();
+        Content::Image(analysis)
+    }
+
+    #[tokio::test]
+    async fn mixed_batch_stores_every_item() {
+        let mut index = index();
+        let items = vec![
+            code("src/chart.rs"),
+            image(),
+            code("src/render.rs"),
+            code("src/axis.rs"),
+            image(),
+        ];
+
+        let results = index.index_batch(items).await.unwrap();
+        assert_eq!(results.len(), 5);
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(index.code_store.lock().await.item_count(), 3);
+        assert_eq!(index.image_store.lock().await.item_count(), 2);
+        assert_eq!(index.audio_store.lock().await.item_count(), 0);
+    }
+
+    fn hit(path: &str, similarity: f32, modality: Modality) -> SearchHit {
+        SearchHit {
+            path: PathBuf::from(path),
+            similarity,
+            modality,
+            snippet: None,
+        }
+    }
+
+    #[test]
+    fn cross_modal_hits_rank_across_stores_by_shared_similarity() {
+        let hits = vec![
+            hit("src/chart.rs", 0.62, Modality::Code),
+            hit("docs/chart.png", 0.81, Modality::Image),
+            hit("src/unrelated.rs", 0.05, Modality::Code),
+            hit("docs/chart.png", 0.40, Modality::Image),
+        ];
+
+        let ranked = merge_and_rank(hits, 2);
+        let summary: Vec<(&Path, f32)> = ranked
+            .iter()
+            .map(|hit| (hit.path.as_path(), hit.similarity))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Path::new("docs/chart.png"), 0.81),
+                (Path::new("src/chart.rs"), 0.62),
+            ]
+        );
+        assert!(matches!(ranked[0].modality, Modality::Image));
+        assert!(matches!(ranked[1].modality, Modality::Code));
+    }
+
+    #[tokio::test]
+    async fn cross_modal_query_searches_code_and_image_stores() {
+        let mut index = index();
+        let results = index
+            .index_batch(vec![code("src/chart.rs"), image()])
+            .await
+            .unwrap();
+        assert!(results.iter().all(Result::is_ok));
+
+        let query = MultiModalQuery {
+            text: "chart rendering".to_string(),
+            modality: Modality::CrossModal,
+            limit: 10,
+        };
+        let SearchResults::CrossModal(hits) = index.search(query).await.unwrap() else {
+            panic!("a cross-modal query returns cross-modal hits");
+        };
+
+        assert_eq!(hits.len(), 2);
+        assert!(hits
+            .iter()
+            .any(|hit| matches!(hit.modality, Modality::Code)
+                && hit.path == Path::new("src/chart.rs")));
+        assert!(hits
+            .iter()
+            .any(|hit| matches!(hit.modality, Modality::Image)));
+        assert!(hits[0].similarity >= hits[1].similarity);
+        assert!(hits
+            .iter()
+            .all(|hit| (-1.0..=1.0).contains(&hit.similarity)));
+    }
+}
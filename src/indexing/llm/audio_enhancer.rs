@@ -1,6 +1,6 @@
 use super::{EnhancementError, LLMEnhancer};
 use crate::indexing::base::audio::AudioAnalysis;
-use crate::llm::Model;
+use crate::llm::{GenerationConfig, Model, RetryingModel};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -74,11 +74,15 @@ pub struct AudioEnhancerConfig {
     pub emotional_sensitivity: f32,
     pub structural_detail_level: DetailLevel,
     pub context_scope: ContextScope,
+    pub generation: GenerationConfig,
 }
 
 impl AudioEnhancer {
     pub fn new(llm: Arc<dyn Model>, config: AudioEnhancerConfig) -> Self {
-        Self { llm, config }
+        Self {
+            llm: RetryingModel::from_config(llm, &config.generation),
+            config,
+        }
     }
 
     async fn interpret_content(
@@ -99,9 +103,9 @@ impl AudioEnhancer {
 
         let response = self
             .llm
-            .generate(&prompt)
+            .generate_with_config(&prompt, self.config.generation.clone())
             .await
-            .map_err(|e| EnhancementError::LLMError(e))?;
+            .map_err(|e| EnhancementError::LLMError(e.to_string()))?;
 
         self.parse_content_interpretation(&response.text)
     }
@@ -126,9 +130,9 @@ impl AudioEnhancer {
 
         let response = self
             .llm
-            .generate(&prompt)
+            .generate_with_config(&prompt, self.config.generation.clone())
             .await
-            .map_err(|e| EnhancementError::LLMError(e))?;
+            .map_err(|e| EnhancementError::LLMError(e.to_string()))?;
 
         self.parse_emotional_analysis(&response.text)
     }
@@ -153,9 +157,9 @@ impl AudioEnhancer {
 
         let response = self
             .llm
-            .generate(&prompt)
+            .generate_with_config(&prompt, self.config.generation.clone())
             .await
-            .map_err(|e| EnhancementError::LLMError(e))?;
+            .map_err(|e| EnhancementError::LLMError(e.to_string()))?;
 
         self.parse_structural_understanding(&response.text)
     }
@@ -181,9 +185,9 @@ impl AudioEnhancer {
 
         let response = self
             .llm
-            .generate(&prompt)
+            .generate_with_config(&prompt, self.config.generation.clone())
             .await
-            .map_err(|e| EnhancementError::LLMError(e))?;
+            .map_err(|e| EnhancementError::LLMError(e.to_string()))?;
 
         self.parse_context_analysis(&response.text)
     }
@@ -1,6 +1,6 @@
 use super::{EnhancementError, LLMEnhancer};
 use crate::indexing::base::code::CodeAnalysis;
-use crate::llm::Model;
+use crate::llm::{GenerationConfig, Model, RetryingModel};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -46,11 +46,15 @@ pub struct CodeEnhancerConfig {
     pub pattern_detection_threshold: f32,
     pub context_window_size: usize,
     pub enhancement_depth: EnhancementDepth,
+    pub generation: GenerationConfig,
 }
 
 impl CodeEnhancer {
     pub fn new(llm: Arc<dyn Model>, config: CodeEnhancerConfig) -> Self {
-        Self { llm, config }
+        Self {
+            llm: RetryingModel::from_config(llm, &config.generation),
+            config,
+        }
     }
 
     async fn detect_patterns(
@@ -72,9 +76,9 @@ impl CodeEnhancer {
 
         let response = self
             .llm
-            .generate(&prompt)
+            .generate_with_config(&prompt, self.config.generation.clone())
             .await
-            .map_err(|e| EnhancementError::LLMError(e))?;
+            .map_err(|e| EnhancementError::LLMError(e.to_string()))?;
 
         self.parse_pattern_response(&response.text)
     }
@@ -99,9 +103,9 @@ impl CodeEnhancer {
 
         let response = self
             .llm
-            .generate(&prompt)
+            .generate_with_config(&prompt, self.config.generation.clone())
             .await
-            .map_err(|e| EnhancementError::LLMError(e))?;
+            .map_err(|e| EnhancementError::LLMError(e.to_string()))?;
 
         self.parse_architectural_response(&response.text)
     }
@@ -126,9 +130,9 @@ impl CodeEnhancer {
 
         let response = self
             .llm
-            .generate(&prompt)
+            .generate_with_config(&prompt, self.config.generation.clone())
             .await
-            .map_err(|e| EnhancementError::LLMError(e))?;
+            .map_err(|e| EnhancementError::LLMError(e.to_string()))?;
 
         self.parse_relationships_response(&response.text)
     }
@@ -157,9 +161,9 @@ impl CodeEnhancer {
 
         let response = self
             .llm
-            .generate(&prompt)
+            .generate_with_config(&prompt, self.config.generation.clone())
             .await
-            .map_err(|e| EnhancementError::LLMError(e))?;
+            .map_err(|e| EnhancementError::LLMError(e.to_string()))?;
 
         self.parse_context_response(&response.text)
     }
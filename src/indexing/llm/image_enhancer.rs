@@ -54,9 +54,9 @@ impl ImageEnhancer {
 
         let response = self
             .llm
-            .generate(&prompt)
+            .generate_with_config(&prompt, self.config.generation.clone())
             .await
-            .map_err(|e| EnhancementError::LLMError(e))?;
+            .map_err(|e| EnhancementError::LLMError(e.to_string()))?;
 
         self.parse_scene_interpretation(&response.text)
     }
@@ -1,6 +1,6 @@
 use super::{EnhancementError, LLMEnhancer};
 use crate::indexing::base::video::VideoAnalysis;
-use crate::llm::Model;
+use crate::llm::{GenerationConfig, Model, RetryingModel};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -56,11 +56,15 @@ pub struct VideoEnhancerConfig {
     pub temporal_granularity: TemporalGranularity,
     pub motion_sensitivity: f32,
     pub relationship_analysis_level: AnalysisLevel,
+    pub generation: GenerationConfig,
 }
 
 impl VideoEnhancer {
     pub fn new(llm: Arc<dyn Model>, config: VideoEnhancerConfig) -> Self {
-        Self { llm, config }
+        Self {
+            llm: RetryingModel::from_config(llm, &config.generation),
+            config,
+        }
     }
 
     async fn analyze_narrative(
@@ -81,9 +85,9 @@ impl VideoEnhancer {
 
         let response = self
             .llm
-            .generate(&prompt)
+            .generate_with_config(&prompt, self.config.generation.clone())
             .await
-            .map_err(|e| EnhancementError::LLMError(e))?;
+            .map_err(|e| EnhancementError::LLMError(e.to_string()))?;
 
         self.parse_narrative_understanding(&response.text)
     }
@@ -106,9 +110,9 @@ impl VideoEnhancer {
 
         let response = self
             .llm
-            .generate(&prompt)
+            .generate_with_config(&prompt, self.config.generation.clone())
             .await
-            .map_err(|e| EnhancementError::LLMError(e))?;
+            .map_err(|e| EnhancementError::LLMError(e.to_string()))?;
 
         self.parse_temporal_analysis(&response.text)
     }
@@ -131,9 +135,9 @@ impl VideoEnhancer {
 
         let response = self
             .llm
-            .generate(&prompt)
+            .generate_with_config(&prompt, self.config.generation.clone())
             .await
-            .map_err(|e| EnhancementError::LLMError(e))?;
+            .map_err(|e| EnhancementError::LLMError(e.to_string()))?;
 
         self.parse_motion_interpretation(&response.text)
     }
@@ -157,9 +161,9 @@ impl VideoEnhancer {
 
         let response = self
             .llm
-            .generate(&prompt)
+            .generate_with_config(&prompt, self.config.generation.clone())
             .await
-            .map_err(|e| EnhancementError::LLMError(e))?;
+            .map_err(|e| EnhancementError::LLMError(e.to_string()))?;
 
         self.parse_scene_relationships(&response.text)
     }
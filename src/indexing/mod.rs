@@ -1,11 +1,22 @@
 mod base;
 mod common;
+mod embedding_index;
 mod embeddings;
 mod llm;
 mod store;
+mod summaries;
 
+pub use store::{CodeQuery, CodeQueryFilters, CodeSearchResult, CodeVectorStore, VectorStore};
+pub use summaries::SummaryCache;
+
+use crate::analyzers::manager::{FileEntry, ProjectState};
 use crate::analyzers::{PythonAnalyzer, RustAnalyzer};
+use crate::progress::{AnalysisProgress, ProgressReporter};
+use tokio_util::sync::CancellationToken;
 use common::{IndexConfig, Metadata, Relationship};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
 
 pub enum ModalityType {
     Code,
@@ -26,15 +37,36 @@ pub struct Indexer {
     stores: HashMap<ModalityType, Box<dyn Store>>,
     analyzers: HashMap<ModalityType, Box<dyn Analyzer>>,
     llm_enhancers: HashMap<ModalityType, Box<dyn LLMEnhancer>>,
+    indexed_files: HashSet<PathBuf>,
+    /// Content hash each file had, per `ProjectState`, when it was embedded.
+    indexed_hashes: HashMap<PathBuf, String>,
+    summaries: SummaryCache,
+    embedded_chunks: usize,
+    progress: ProgressReporter,
+    cancel: CancellationToken,
+}
+
+/// What `Indexer::sync_with_state` changed.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IndexSync {
+    pub reindexed: usize,
+    pub removed: usize,
+    pub unchanged: usize,
 }
 
 impl Indexer {
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self, String> {
         let mut indexer = Self {
             configs: HashMap::new(),
             stores: HashMap::new(),
             analyzers: HashMap::new(),
             llm_enhancers: HashMap::new(),
+            indexed_files: HashSet::new(),
+            indexed_hashes: HashMap::new(),
+            summaries: SummaryCache::default(),
+            embedded_chunks: 0,
+            progress: ProgressReporter::default(),
+            cancel: CancellationToken::new(),
         };
 
         // Initialize code modality
@@ -43,7 +75,7 @@ impl Indexer {
         indexer.setup_audio_modality();
         indexer.setup_video_modality();
 
-        indexer
+        Ok(indexer)
     }
 
     fn setup_code_modality(&mut self) {
@@ -65,6 +97,153 @@ impl Indexer {
             .insert(ModalityType::Code, Box::new(CodeStore::new(code_analyzers)));
     }
 
+    /// Reuses LLM summaries cached at `path` for content that hasn't changed.
+    /// With `refresh` set the cache is emptied first so every file is
+    /// summarized again.
+    pub fn with_summary_cache(mut self, path: PathBuf, refresh: bool) -> Self {
+        self.summaries = SummaryCache::open(path);
+        if refresh {
+            self.summaries.clear();
+        }
+        self
+    }
+
+    /// Reports each file indexed by `sync_with_state` to `progress`.
+    pub fn set_progress(&mut self, progress: ProgressReporter) {
+        self.progress = progress;
+    }
+
+    /// Once `cancel` is cancelled, `sync_with_state` stops before the next
+    /// file with `IndexError::Cancelled`. Files indexed up to then keep
+    /// their hashes, so saving the index afterwards stays consistent.
+    pub fn set_cancellation(&mut self, cancel: CancellationToken) {
+        self.cancel = cancel;
+    }
+
+    pub fn save_summaries(&mut self) -> Result<(), String> {
+        self.summaries.save()
+    }
+
+    /// Paths of every file that currently has vectors in the index.
+    pub fn indexed_paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.indexed_files.iter()
+    }
+
+    /// Number of chunks embedded since the indexer was created.
+    pub fn chunk_count(&self) -> usize {
+        self.embedded_chunks
+    }
+
+    pub async fn remove_file(&mut self, path: &Path) -> Result<(), IndexError> {
+        for store in self.stores.values_mut() {
+            store.remove(path).await?;
+        }
+        self.indexed_files.remove(path);
+        self.indexed_hashes.remove(path);
+        Ok(())
+    }
+
+    /// Replaces the vectors of a modified file.
+    pub async fn update_file(&mut self, entry: &FileEntry) -> Result<(), IndexError> {
+        self.remove_file(&entry.path).await?;
+        self.index_file(entry).await
+    }
+
+    /// Brings the index in line with the analyzer's `state`. Files whose hash
+    /// is unchanged since they were embedded are skipped, modified files are
+    /// replaced and files missing from both `entries` and `state` are removed,
+    /// so a scoped analysis leaves files outside its scope alone. Entry paths
+    /// are relative to `root`; state paths are absolute.
+    pub async fn sync_with_state(
+        &mut self,
+        root: &Path,
+        state: &ProjectState,
+        entries: &[FileEntry],
+    ) -> Result<IndexSync, IndexError> {
+        let mut files = Vec::new();
+        collect_files(entries, &mut files);
+
+        let mut sync = IndexSync::default();
+        let mut seen = HashSet::new();
+        let mut changed = Vec::new();
+        for entry in files {
+            seen.insert(entry.path.clone());
+            let hash = state
+                .analyzed_files
+                .get(&root.join(&entry.path))
+                .map(|file_state| file_state.hash.clone());
+
+            let unchanged = match &hash {
+                Some(hash) => self.indexed_hashes.get(&entry.path) == Some(hash),
+                // Without a hash, a file already in the index can't be
+                // shown to have changed
+                None => self.indexed_files.contains(&entry.path),
+            };
+            if unchanged {
+                sync.unchanged += 1;
+            } else {
+                changed.push((entry, hash));
+            }
+        }
+
+        let total = changed.len();
+        for (entry, hash) in changed {
+            if self.cancel.is_cancelled() {
+                return Err(IndexError::Cancelled);
+            }
+            if self.indexed_files.contains(&entry.path) {
+                self.update_file(entry).await?;
+            } else {
+                self.index_file(entry).await?;
+            }
+            if let Some(hash) = hash {
+                self.indexed_hashes.insert(entry.path.clone(), hash);
+            }
+            sync.reindexed += 1;
+            self.progress.report(AnalysisProgress::Indexing {
+                path: entry.path.clone(),
+                done: sync.reindexed,
+                total,
+            });
+        }
+
+        let stale: Vec<PathBuf> = self
+            .indexed_files
+            .iter()
+            .filter(|path| {
+                !seen.contains(*path) && !state.analyzed_files.contains_key(&root.join(path))
+            })
+            .cloned()
+            .collect();
+        for path in stale {
+            self.remove_file(&path).await?;
+            sync.removed += 1;
+        }
+
+        self.progress.report(AnalysisProgress::Done);
+        Ok(sync)
+    }
+
+    /// Restores the file hashes written by `save_index_state` for a saved
+    /// index. Without them every file counts as modified on the next sync.
+    pub fn load_index_state(&mut self, path: &Path) -> Result<(), String> {
+        if !path.exists() {
+            return Ok(());
+        }
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        self.indexed_hashes = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid index state {}: {}", path.display(), e))?;
+        Ok(())
+    }
+
+    pub fn save_index_state(&self, path: &Path) -> Result<(), String> {
+        store::write_atomically(path, |writer| {
+            serde_json::to_writer(writer, &self.indexed_hashes).map_err(std::io::Error::from)
+        })
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
     pub async fn index_content(
         &mut self,
         content: &[u8],
@@ -76,11 +255,17 @@ impl Indexer {
             .get(&modality)
             .ok_or(IndexError::UnsupportedModality)?;
 
-        // 1. Base Analysis
-        let base_analysis = self.analyze_content(content, &modality).await?;
-
-        // 2. LLM Enhancement
-        let enhanced_analysis = self.enhance_analysis(base_analysis, &modality).await?;
+        // 1-2. Base analysis and LLM enhancement, skipped for cached content
+        let content_hash = SummaryCache::content_hash(content);
+        let enhanced_analysis = match self.summaries.get(&content_hash) {
+            Some(cached) => cached.clone(),
+            None => {
+                let base_analysis = self.analyze_content(content, &modality).await?;
+                let enhanced = self.enhance_analysis(base_analysis, &modality).await?;
+                self.summaries.insert(content_hash, enhanced.clone());
+                enhanced
+            }
+        };
 
         // 3. Generate Embeddings
         let embeddings = self
@@ -88,7 +273,10 @@ impl Indexer {
             .await?;
 
         // 4. Store Results
-        self.store_results(embeddings, metadata, &modality).await
+        let chunk_count = embeddings.len();
+        let result = self.store_results(embeddings, metadata, &modality).await?;
+        self.embedded_chunks += chunk_count;
+        Ok(result)
     }
 
     async fn analyze_content(
@@ -117,3 +305,14 @@ impl Indexer {
         enhancer.enhance(analysis).await
     }
 }
+
+/// Leaf file entries of a project structure tree.
+fn collect_files<'a>(entries: &'a [FileEntry], files: &mut Vec<&'a FileEntry>) {
+    for entry in entries {
+        if entry.file_type == "directory" {
+            collect_files(&entry.children, files);
+        } else {
+            files.push(entry);
+        }
+    }
+}
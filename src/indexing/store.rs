@@ -52,7 +52,12 @@ impl VectorStore {
             });
         }
 
-        results.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap());
+        // Embeddings iterate in HashMap order; the key makes ties stable
+        results.sort_by(|a, b| {
+            b.similarity
+                .total_cmp(&a.similarity)
+                .then_with(|| a.key.cmp(&b.key))
+        });
         Ok(results.into_iter().take(top_k).collect())
     }
 }
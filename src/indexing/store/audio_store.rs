@@ -1,13 +1,17 @@
+use super::common::{self, QueryEmbedder};
 use super::{StorageConfig, StoreError, VectorIndex, VectorStore};
 use crate::indexing::llm::EnhancedAudioAnalysis;
+use crate::llm::Model;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct AudioVectorStore {
     index: VectorIndex,
     config: StorageConfig,
+    query_embedder: Option<QueryEmbedder>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,9 +70,33 @@ impl AudioVectorStore {
                 search_k: 100,
             }),
             config,
+            query_embedder: None,
         }
     }
 
+    /// Embeds `semantic_description` queries with `model`.
+    pub fn with_query_model(mut self, model: Arc<dyn Model>) -> Self {
+        self.query_embedder = Some(QueryEmbedder::new(model));
+        self
+    }
+
+    async fn create_semantic_query_vector(
+        &self,
+        description: &str,
+    ) -> Result<Vec<f32>, StoreError> {
+        common::text_query_vector(
+            self.query_embedder.as_ref(),
+            description,
+            self.config.vector_dimension,
+        )
+        .await
+    }
+
+    fn normalize_vector(&self, vector: &mut Vec<f32>) -> Result<(), StoreError> {
+        common::normalize(vector);
+        Ok(())
+    }
+
     fn create_vector(&self, analysis: &EnhancedAudioAnalysis) -> Result<Vec<f32>, StoreError> {
         let mut vector = Vec::with_capacity(self.config.vector_dimension);
 
@@ -287,7 +315,7 @@ impl VectorStore for AudioVectorStore {
     async fn search(&self, query: Self::Query) -> Result<Vec<Self::Result>, StoreError> {
         let query_vector = match (&query.audio_features, &query.semantic_description) {
             (Some(features), _) => features.clone(),
-            (None, Some(description)) => self.create_semantic_query_vector(description)?,
+            (None, Some(description)) => self.create_semantic_query_vector(description).await?,
             (None, None) => return Err(StoreError::InvalidQuery("No query provided".into())),
         };
 
@@ -1,26 +1,63 @@
+use super::common::{self, IndexMetadata, QueryEmbedder, StoredVector};
 use super::{StorageConfig, StoreError, VectorIndex, VectorStore};
+use crate::indexing::base::code::CodeAnalysis;
 use crate::indexing::llm::EnhancedCodeAnalysis;
+use crate::llm::Model;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Layout of saved code stores. Version 0 files predate quantization and
+/// hold plain `f32` vectors; later versions hold `StoredVector`s.
+const STORE_FORMAT_VERSION: u32 = 1;
 
 #[derive(Debug)]
 pub struct CodeVectorStore {
     index: VectorIndex,
     config: StorageConfig,
+    query_embedder: Option<QueryEmbedder>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CodeQuery {
-    pub text: String,
+    pub code_features: Option<Vec<f32>>,
+    pub text: Option<String>,
     pub filters: CodeQueryFilters,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CodeQueryFilters {
     pub languages: Option<Vec<String>>,
     pub file_types: Option<Vec<String>>,
     pub relationship_types: Option<Vec<String>>,
+    /// Glob patterns such as `src/api/**`; a file must match at least one.
+    #[serde(default)]
+    pub path_glob: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoreData {
+    version: u32,
+    vectors: Vec<StoredVector>,
+    metadata: HashMap<usize, IndexMetadata>,
+    config: StorageConfig,
+}
+
+#[derive(Deserialize)]
+struct LegacyStoreData {
+    vectors: Vec<Vec<f32>>,
+    metadata: HashMap<usize, IndexMetadata>,
+    config: StorageConfig,
+}
+
+#[derive(Deserialize)]
+struct StoreVersion {
+    #[serde(default)]
+    version: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,11 +74,19 @@ impl CodeVectorStore {
                 num_trees: 10,
                 max_items_per_node: 100,
                 search_k: 50,
-            }),
+            })
+            .with_quantization(config.quantization),
             config,
+            query_embedder: None,
         }
     }
 
+    /// Embeds text queries with `model` instead of hashing their words.
+    pub fn with_query_model(mut self, model: Arc<dyn Model>) -> Self {
+        self.query_embedder = Some(QueryEmbedder::new(model));
+        self
+    }
+
     fn create_vector(&self, analysis: &EnhancedCodeAnalysis) -> Result<Vec<f32>, StoreError> {
         let mut vector = Vec::with_capacity(self.config.vector_dimension);
 
@@ -112,13 +157,39 @@ impl CodeVectorStore {
     }
 
     fn normalize_vector(&self, vector: &mut Vec<f32>) -> Result<(), StoreError> {
-        let magnitude = (vector.iter().map(|x| x * x).sum::<f32>()).sqrt();
-        if magnitude > 0.0 {
-            for x in vector.iter_mut() {
-                *x /= magnitude;
+        common::normalize(vector);
+        Ok(())
+    }
+
+    /// Prefer `code_features` when available; text is only as close to the
+    /// indexed vectors as the query model makes it.
+    async fn create_query_vector(&self, text: &str) -> Result<Vec<f32>, StoreError> {
+        common::text_query_vector(
+            self.query_embedder.as_ref(),
+            text,
+            self.config.vector_dimension,
+        )
+        .await
+    }
+
+    /// The query's features when given, otherwise a vector for its text.
+    async fn query_vector(&self, query: &CodeQuery) -> Result<Vec<f32>, StoreError> {
+        match (&query.code_features, &query.text) {
+            (Some(features), _) => {
+                if features.len() != self.config.vector_dimension {
+                    return Err(StoreError::InvalidQuery(format!(
+                        "Expected {} features, got {}",
+                        self.config.vector_dimension,
+                        features.len()
+                    )));
+                }
+                let mut features = features.clone();
+                self.normalize_vector(&mut features)?;
+                Ok(features)
             }
+            (None, Some(text)) if !text.trim().is_empty() => self.create_query_vector(text).await,
+            _ => Err(StoreError::InvalidQuery("No query provided".into())),
         }
-        Ok(())
     }
 
     fn apply_filters(
@@ -154,14 +225,37 @@ impl CodeVectorStore {
     ) -> Result<bool, StoreError> {
         // Check language filter
         if let Some(langs) = &filters.languages {
-            if !langs.contains(&metadata.attributes["language"]) {
+            let language = metadata.attributes.get("language");
+            if !langs
+                .iter()
+                .any(|lang| language.map_or(false, |l| l.eq_ignore_ascii_case(lang)))
+            {
                 return Ok(false);
             }
         }
 
         // Check file type filter
         if let Some(types) = &filters.file_types {
-            if !types.contains(&metadata.attributes["file_type"]) {
+            let file_type = metadata.attributes.get("file_type");
+            if !types
+                .iter()
+                .any(|ty| file_type.map_or(false, |t| t.eq_ignore_ascii_case(ty)))
+            {
+                return Ok(false);
+            }
+        }
+
+        // Check path globs
+        if let Some(globs) = &filters.path_glob {
+            let path = Path::new(&metadata.path);
+            let mut matched = false;
+            for glob in globs {
+                let pattern = glob::Pattern::new(glob).map_err(|e| {
+                    StoreError::InvalidQuery(format!("Invalid path glob '{}': {}", glob, e))
+                })?;
+                matched |= pattern.matches_path(path);
+            }
+            if !matched {
                 return Ok(false);
             }
         }
@@ -215,34 +309,62 @@ impl CodeVectorStore {
         &self,
         analysis: &EnhancedCodeAnalysis,
     ) -> Result<IndexMetadata, StoreError> {
-        let mut attributes = HashMap::new();
-        attributes.insert(
-            "language".to_string(),
-            analysis.base.functions[0].language.clone(),
-        );
-        attributes.insert(
+        let has_functions = !analysis.base.functions.is_empty();
+        let mut metadata = Self::file_metadata(&analysis.base);
+        metadata.attributes.insert(
             "pattern_count".to_string(),
-            analysis.patterns.len().to_string(),
+            if has_functions {
+                analysis.patterns.len()
+            } else {
+                0
+            }
+            .to_string(),
         );
-        attributes.insert(
+        metadata.attributes.insert(
             "architectural_style".to_string(),
-            analysis.architecture.architectural_style[0].to_string(),
+            analysis
+                .architecture
+                .architectural_style
+                .first()
+                .map(|style| style.to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
         );
+
+        Ok(metadata)
+    }
+
+    /// Metadata from the file-level analysis. Files of only constants or
+    /// config have no functions, so nothing here may depend on them.
+    fn file_metadata(base: &CodeAnalysis) -> IndexMetadata {
+        let mut attributes = HashMap::new();
+        attributes.insert(
+            "language".to_string(),
+            base.language
+                .clone()
+                .unwrap_or_else(|| "unknown".to_string()),
+        );
+        if let Some(extension) = base.file.extension() {
+            attributes.insert(
+                "file_type".to_string(),
+                extension.to_string_lossy().into_owned(),
+            );
+        }
         attributes.insert(
             "complexity_score".to_string(),
-            analysis.base.metrics.complexity.to_string(),
+            if base.functions.is_empty() {
+                0
+            } else {
+                base.metrics.complexity
+            }
+            .to_string(),
         );
 
-        Ok(IndexMetadata {
+        IndexMetadata {
             id: 0, // Will be set by index
-            path: analysis.base.functions[0]
-                .location
-                .file
-                .to_string_lossy()
-                .into_owned(),
+            path: base.file.to_string_lossy().into_owned(),
             modality: "code".to_string(),
             attributes,
-        })
+        }
     }
 }
 
@@ -261,10 +383,7 @@ impl VectorStore for CodeVectorStore {
 
     async fn search(&self, query: Self::Query) -> Result<Vec<Self::Result>, StoreError> {
         // Create query vector
-        let query_vector = match query.text {
-            Some(text) => self.create_query_vector(&text)?,
-            None => return Err(StoreError::InvalidQuery("No query text provided".into())),
-        };
+        let query_vector = self.query_vector(&query).await?;
 
         // Perform similarity search
         let results = self.index.search(&query_vector, self.config.max_results)?;
@@ -278,23 +397,67 @@ impl VectorStore for CodeVectorStore {
         Ok(search_results)
     }
 
+    async fn remove(&mut self, path: &Path) -> Result<(), StoreError> {
+        self.index.remove_path(&path.to_string_lossy());
+        Ok(())
+    }
+
+    async fn update(&mut self, item: Self::Item) -> Result<(), StoreError> {
+        let metadata = self.create_metadata(&item)?;
+        self.index.remove_path(&metadata.path);
+        self.add(item).await
+    }
+
     async fn save(&self, path: PathBuf) -> Result<(), StoreError> {
         let store_data = StoreData {
+            version: STORE_FORMAT_VERSION,
             vectors: self.index.vectors.clone(),
             metadata: self.index.metadata.clone(),
             config: self.config.clone(),
         };
 
-        let file = File::create(path)?;
-        serde_json::to_writer(file, &store_data).map_err(|e| StoreError::Storage(e.to_string()))?;
+        common::write_atomically(&path, |writer| {
+            serde_json::to_writer(writer, &store_data).map_err(io::Error::from)
+        })?;
 
         Ok(())
     }
 
     async fn load(&mut self, path: PathBuf) -> Result<(), StoreError> {
         let file = File::open(path)?;
-        let store_data: StoreData =
+        let raw: serde_json::Value =
             serde_json::from_reader(file).map_err(|e| StoreError::Storage(e.to_string()))?;
+        let version = StoreVersion::deserialize(&raw)
+            .map_err(|e| StoreError::Storage(e.to_string()))?
+            .version;
+
+        let store_data = match version {
+            0 => {
+                let legacy = LegacyStoreData::deserialize(&raw)
+                    .map_err(|e| StoreError::Storage(e.to_string()))?;
+                // Old full-precision files pick up the store's quantization
+                // setting as they are loaded
+                StoreData {
+                    version,
+                    vectors: legacy
+                        .vectors
+                        .into_iter()
+                        .map(|vector| StoredVector::new(vector, legacy.config.quantization))
+                        .collect(),
+                    metadata: legacy.metadata,
+                    config: legacy.config,
+                }
+            }
+            STORE_FORMAT_VERSION => {
+                StoreData::deserialize(&raw).map_err(|e| StoreError::Storage(e.to_string()))?
+            }
+            _ => {
+                return Err(StoreError::Storage(format!(
+                    "Unsupported code store format version {} (expected at most {})",
+                    version, STORE_FORMAT_VERSION
+                )))
+            }
+        };
 
         self.index.vectors = store_data.vectors;
         self.index.metadata = store_data.metadata;
@@ -303,3 +466,169 @@ impl VectorStore for CodeVectorStore {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::common::{IndexType, Quantization};
+    use super::*;
+    use crate::analyzers::RustAnalyzer;
+    use crate::indexing::base::code::{
+        AnalysisLevel, CodeAnalyzerConfig, CodeBaseAnalyzer, CodeMetrics,
+    };
+    use crate::indexing::base::BaseAnalyzer;
+
+    const DIMENSION: usize = 16;
+
+    fn store() -> CodeVectorStore {
+        CodeVectorStore::new(StorageConfig {
+            vector_dimension: DIMENSION,
+            max_items: 100,
+            index_type: IndexType::Flat,
+            similarity_threshold: 0.0,
+            quantization: Quantization::None,
+        })
+    }
+
+    /// A store holding one Rust file per (path, text) pair, embedded by
+    /// hashing the text's words.
+    async fn store_with(files: &[(&str, &str)]) -> CodeVectorStore {
+        let mut store = store();
+        for (path, text) in files {
+            let vector = common::text_query_vector(None, text, DIMENSION)
+                .await
+                .unwrap();
+            let metadata = IndexMetadata {
+                id: 0,
+                path: path.to_string(),
+                modality: "code".to_string(),
+                attributes: HashMap::from([("language".to_string(), "rust".to_string())]),
+            };
+            store.index.add(vector, metadata).unwrap();
+        }
+        store
+    }
+
+    fn query(text: Option<&str>, code_features: Option<Vec<f32>>) -> CodeQuery {
+        CodeQuery {
+            code_features,
+            text: text.map(str::to_string),
+            filters: CodeQueryFilters {
+                languages: Some(vec!["rust".to_string()]),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn constants_only_file_keeps_its_path_and_language() {
+        let base = CodeAnalysis {
+            file: PathBuf::from("src/config.rs"),
+            language: Some("rust".to_string()),
+            functions: Vec::new(),
+            modules: Vec::new(),
+            dependencies: Vec::new(),
+            metrics: CodeMetrics {
+                loc: 3,
+                complexity: 4,
+                dependency_count: 0,
+                modularity_score: 1.0,
+            },
+        };
+
+        let metadata = CodeVectorStore::file_metadata(&base);
+        assert_eq!(metadata.path, "src/config.rs");
+        assert_eq!(metadata.attributes["language"], "rust");
+        assert_eq!(metadata.attributes["file_type"], "rs");
+        assert_eq!(metadata.attributes["complexity_score"], "0");
+    }
+
+    #[test]
+    fn analyzed_file_is_stored_under_its_real_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("src/parser.rs");
+        let source = "pub fn parse(input: &str) -> usize {\n    input.len()\n}\n";
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, source).unwrap();
+
+        let config = CodeAnalyzerConfig {
+            path: path.clone(),
+            max_depth: 1,
+            include_tests: false,
+            analysis_level: AnalysisLevel::Basic,
+        };
+        let analyzer = CodeBaseAnalyzer::new(vec![Box::new(RustAnalyzer::new())], config.clone());
+        let base = analyzer.analyze(source.as_bytes(), &config).unwrap();
+
+        let mut store = store();
+        store
+            .index
+            .add(vec![1.0; DIMENSION], CodeVectorStore::file_metadata(&base))
+            .unwrap();
+        let results = store.index.search(&[1.0; DIMENSION], 1).unwrap();
+        let stored = store
+            .apply_filters(results, &query(None, None).filters)
+            .unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].metadata.path, path.to_string_lossy());
+    }
+
+    #[tokio::test]
+    async fn query_without_text_or_features_is_rejected() {
+        let store = store();
+        for empty in [query(None, None), query(Some("  "), None)] {
+            assert!(matches!(
+                store.query_vector(&empty).await,
+                Err(StoreError::InvalidQuery(_))
+            ));
+        }
+        assert!(matches!(
+            store.query_vector(&query(None, Some(vec![1.0; 3]))).await,
+            Err(StoreError::InvalidQuery(_))
+        ));
+
+        let features = store
+            .query_vector(&query(None, Some(vec![2.0; DIMENSION])))
+            .await
+            .unwrap();
+        let magnitude = features.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((magnitude - 1.0).abs() < 1e-5);
+    }
+
+    #[tokio::test]
+    async fn text_query_ranks_the_matching_file_first() {
+        let store = store_with(&[
+            ("render.rs", "render widget frame"),
+            ("config.rs", "parse config file"),
+        ])
+        .await;
+
+        let query = query(Some("parse the config"), None);
+        let vector = store.query_vector(&query).await.unwrap();
+        let results = store.index.search(&vector, 2).unwrap();
+        let filtered = store.apply_filters(results, &query.filters).unwrap();
+        assert_eq!(filtered[0].metadata.path, "config.rs");
+    }
+
+    #[tokio::test]
+    async fn path_glob_excludes_files_outside_it() {
+        let store = store_with(&[
+            ("src/api/handler.rs", "handle request"),
+            ("src/api/v2/routes.rs", "route request"),
+            ("src/db/pool.rs", "pool request"),
+        ])
+        .await;
+
+        let mut query = query(Some("request"), None);
+        query.filters.path_glob = Some(vec!["src/api/**".to_string()]);
+        let vector = store.query_vector(&query).await.unwrap();
+        let results = store.index.search(&vector, 3).unwrap();
+        let mut paths: Vec<String> = store
+            .apply_filters(results, &query.filters)
+            .unwrap()
+            .into_iter()
+            .map(|result| result.metadata.path)
+            .collect();
+        paths.sort();
+        assert_eq!(paths, vec!["src/api/handler.rs", "src/api/v2/routes.rs"]);
+    }
+}
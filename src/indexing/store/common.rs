@@ -1,5 +1,12 @@
+use crate::llm::Model;
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Debug, thiserror::Error)]
 pub enum StoreError {
@@ -9,6 +16,8 @@ pub enum StoreError {
     Index(String),
     #[error("Vector operation error: {0}")]
     VectorOp(String),
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -19,6 +28,111 @@ pub struct StorageConfig {
     pub max_items: usize,
     pub index_type: IndexType,
     pub similarity_threshold: f32,
+    #[serde(default)]
+    pub quantization: Quantization,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            vector_dimension: 384,
+            max_items: 100_000,
+            index_type: IndexType::Flat,
+            similarity_threshold: 0.0,
+            quantization: Quantization::None,
+        }
+    }
+}
+
+/// Writes `path` through a temporary sibling that is renamed into place, so
+/// an interrupted save leaves the previous file intact.
+pub(crate) fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let result = File::create(&tmp_path)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// How stored vectors are kept in memory. Queries always stay full precision.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Quantization {
+    #[default]
+    None,
+    /// Symmetric per-vector int8, a quarter of the f32 footprint.
+    Int8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum StoredVector {
+    Full(Vec<f32>),
+    Int8 { values: Vec<i8>, scale: f32 },
+}
+
+impl StoredVector {
+    pub fn new(vector: Vec<f32>, quantization: Quantization) -> Self {
+        match quantization {
+            Quantization::None => StoredVector::Full(vector),
+            Quantization::Int8 => {
+                let max_abs = vector.iter().fold(0.0f32, |max, x| max.max(x.abs()));
+                let scale = if max_abs == 0.0 { 1.0 } else { max_abs / 127.0 };
+                StoredVector::Int8 {
+                    values: vector
+                        .iter()
+                        .map(|x| (x / scale).round().clamp(-127.0, 127.0) as i8)
+                        .collect(),
+                    scale,
+                }
+            }
+        }
+    }
+
+    pub fn dequantize(&self) -> Vec<f32> {
+        match self {
+            StoredVector::Full(vector) => vector.clone(),
+            StoredVector::Int8 { values, scale } => {
+                values.iter().map(|v| *v as f32 * scale).collect()
+            }
+        }
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        match self {
+            StoredVector::Full(vector) => vector.len() * std::mem::size_of::<f32>(),
+            StoredVector::Int8 { values, .. } => values.len() + std::mem::size_of::<f32>(),
+        }
+    }
+
+    fn cosine_similarity(&self, query: &[f32]) -> f32 {
+        match self {
+            StoredVector::Full(vector) => cosine_similarity(query, vector),
+            // The scale cancels out of the cosine, so compare against the raw values
+            StoredVector::Int8 { values, .. } => {
+                let dot_product: f32 = query.iter().zip(values).map(|(x, v)| x * *v as f32).sum();
+                let norm_a: f32 = query.iter().map(|x| x * x).sum::<f32>().sqrt();
+                let norm_b: f32 = values
+                    .iter()
+                    .map(|v| (*v as f32).powi(2))
+                    .sum::<f32>()
+                    .sqrt();
+                dot_product / (norm_a * norm_b)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,9 +144,10 @@ pub enum IndexType {
 
 #[derive(Debug)]
 pub struct VectorIndex {
-    vectors: Vec<Vec<f32>>,
+    vectors: Vec<StoredVector>,
     metadata: HashMap<usize, IndexMetadata>,
     config: IndexConfig,
+    quantization: Quantization,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,12 +171,48 @@ impl VectorIndex {
             vectors: Vec::new(),
             metadata: HashMap::new(),
             config,
+            quantization: Quantization::None,
         }
     }
 
+    pub fn with_quantization(mut self, quantization: Quantization) -> Self {
+        self.quantization = quantization;
+        self
+    }
+
+    /// Bytes held by the stored vectors.
+    pub fn memory_bytes(&self) -> usize {
+        self.vectors.iter().map(StoredVector::memory_bytes).sum()
+    }
+
+    /// Number of live (not removed) vectors.
+    pub fn len(&self) -> usize {
+        self.metadata.len()
+    }
+
+    /// Soft-deletes every vector whose metadata path matches, returning how
+    /// many were removed. Slots stay allocated so ids remain stable; a slot
+    /// without metadata is never returned by `search`.
+    pub fn remove_path(&mut self, path: &str) -> usize {
+        let ids: Vec<usize> = self
+            .metadata
+            .iter()
+            .filter(|(_, metadata)| metadata.path == path)
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in &ids {
+            self.metadata.remove(id);
+            self.vectors[*id] = StoredVector::Full(Vec::new());
+        }
+
+        ids.len()
+    }
+
     pub fn add(&mut self, vector: Vec<f32>, metadata: IndexMetadata) -> Result<(), StoreError> {
         let id = self.vectors.len();
-        self.vectors.push(vector);
+        self.vectors
+            .push(StoredVector::new(vector, self.quantization));
         self.metadata.insert(id, metadata);
         Ok(())
     }
@@ -71,17 +222,203 @@ impl VectorIndex {
             .vectors
             .iter()
             .enumerate()
-            .map(|(i, v)| (i, cosine_similarity(query, v)))
+            .filter(|(i, _)| self.metadata.contains_key(i))
+            .map(|(i, v)| (i, v.cosine_similarity(query)))
             .collect();
 
-        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        // Equal scores fall back to path, then id, so ties order the same way
+        // on every run
+        scores.sort_by(|a, b| {
+            b.1.total_cmp(&a.1)
+                .then_with(|| self.metadata[&a.0].path.cmp(&self.metadata[&b.0].path))
+                .then(a.0.cmp(&b.0))
+        });
         Ok(scores.into_iter().take(k).collect())
     }
 }
 
+/// Embeds text queries with a model so stores can be searched by
+/// description.
+#[derive(Clone)]
+pub struct QueryEmbedder {
+    model: Arc<dyn Model>,
+}
+
+impl std::fmt::Debug for QueryEmbedder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryEmbedder").finish_non_exhaustive()
+    }
+}
+
+impl QueryEmbedder {
+    pub fn new(model: Arc<dyn Model>) -> Self {
+        Self { model }
+    }
+
+    /// The model's embedding of `text`, folded or zero-padded to `dimension`
+    /// and normalized like stored vectors.
+    pub async fn embed(&self, text: &str, dimension: usize) -> Result<Vec<f32>, StoreError> {
+        if dimension == 0 {
+            return Err(StoreError::InvalidQuery("Vector dimension is zero".into()));
+        }
+
+        let embedding = self
+            .model
+            .embed_text(text)
+            .await
+            .map_err(|e| StoreError::VectorOp(format!("Failed to embed query: {}", e)))?;
+
+        let mut vector = vec![0.0; dimension];
+        for (i, value) in embedding.into_iter().enumerate() {
+            vector[i % dimension] += value;
+        }
+        normalize(&mut vector);
+        Ok(vector)
+    }
+}
+
+/// Text query vector for `dimension`: the embedder's when there is one,
+/// otherwise the query's words hashed into slots. Hashed vectors only
+/// roughly match vectors built from analyses.
+pub async fn text_query_vector(
+    embedder: Option<&QueryEmbedder>,
+    text: &str,
+    dimension: usize,
+) -> Result<Vec<f32>, StoreError> {
+    if let Some(embedder) = embedder {
+        return embedder.embed(text, dimension).await;
+    }
+    if dimension == 0 {
+        return Err(StoreError::InvalidQuery("Vector dimension is zero".into()));
+    }
+
+    let mut vector = vec![0.0; dimension];
+    for token in text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|t| !t.is_empty())
+    {
+        let mut hasher = DefaultHasher::new();
+        token.to_lowercase().hash(&mut hasher);
+        vector[hasher.finish() as usize % dimension] += 1.0;
+    }
+
+    normalize(&mut vector);
+    Ok(vector)
+}
+
+/// Scales `vector` to unit length, leaving a zero vector as it is.
+pub fn normalize(vector: &mut [f32]) {
+    let magnitude = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if magnitude > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= magnitude);
+    }
+}
+
 fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
     let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
     let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
     dot_product / (norm_a * norm_b)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockModel;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashSet;
+
+    fn index(quantization: Quantization, vectors: &[Vec<f32>]) -> VectorIndex {
+        let mut index = VectorIndex::new(IndexConfig {
+            num_trees: 10,
+            max_items_per_node: 100,
+            search_k: 50,
+        })
+        .with_quantization(quantization);
+        for (id, vector) in vectors.iter().enumerate() {
+            let metadata = IndexMetadata {
+                id,
+                path: format!("file_{}.rs", id),
+                modality: "code".to_string(),
+                attributes: HashMap::new(),
+            };
+            index.add(vector.clone(), metadata).unwrap();
+        }
+        index
+    }
+
+    #[test]
+    fn int8_search_keeps_recall_with_less_memory() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut random_vector =
+            || -> Vec<f32> { (0..64).map(|_| rng.gen_range(-1.0..1.0)).collect() };
+        let vectors: Vec<Vec<f32>> = (0..500).map(|_| random_vector()).collect();
+        let queries: Vec<Vec<f32>> = (0..20).map(|_| random_vector()).collect();
+
+        let full = index(Quantization::None, &vectors);
+        let quantized = index(Quantization::Int8, &vectors);
+        assert!(quantized.memory_bytes() * 3 < full.memory_bytes());
+
+        let k = 10;
+        let mut found = 0;
+        for query in &queries {
+            let expected: HashSet<usize> = full
+                .search(query, k)
+                .unwrap()
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            found += quantized
+                .search(query, k)
+                .unwrap()
+                .into_iter()
+                .filter(|(id, _)| expected.contains(id))
+                .count();
+        }
+        let recall = found as f32 / (k * queries.len()) as f32;
+        assert!(recall >= 0.9, "recall {}", recall);
+    }
+
+    #[test]
+    fn tied_scores_order_by_path_then_id() {
+        let vectors = vec![vec![0.5, 0.5, 0.5, 0.5]; 12];
+        let index = index(Quantization::None, &vectors);
+
+        // Paths sort as strings, so file_10 lands before file_2
+        let mut expected: Vec<usize> = (0..12).collect();
+        expected.sort_by_key(|id| format!("file_{}.rs", id));
+        for _ in 0..5 {
+            let ids: Vec<usize> = index
+                .search(&vectors[0], 12)
+                .unwrap()
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            assert_eq!(ids, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn searching_by_indexed_text_finds_that_item() {
+        let texts = [
+            "parse the configuration file",
+            "render a frame of the widget tree",
+            "open a pooled database connection",
+        ];
+        let model = QueryEmbedder::new(Arc::new(MockModel::new().with_dimension(24)));
+
+        for embedder in [None, Some(&model)] {
+            let mut vectors = Vec::new();
+            for text in texts {
+                vectors.push(text_query_vector(embedder, text, 32).await.unwrap());
+            }
+            let index = index(Quantization::None, &vectors);
+
+            let query = text_query_vector(embedder, texts[1], 32).await.unwrap();
+            let (id, similarity) = index.search(&query, 1).unwrap()[0];
+            assert_eq!(id, 1);
+            assert!((similarity - 1.0).abs() < 1e-5, "similarity {}", similarity);
+        }
+    }
+}
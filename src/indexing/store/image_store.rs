@@ -1,13 +1,17 @@
+use super::common::{self, QueryEmbedder};
 use super::{StorageConfig, StoreError, VectorIndex, VectorStore};
 use crate::indexing::llm::EnhancedImageAnalysis;
+use crate::llm::Model;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct ImageVectorStore {
     index: VectorIndex,
     config: StorageConfig,
+    query_embedder: Option<QueryEmbedder>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,9 +44,33 @@ impl ImageVectorStore {
                 search_k: 50,
             }),
             config,
+            query_embedder: None,
         }
     }
 
+    /// Embeds `semantic_description` queries with `model`.
+    pub fn with_query_model(mut self, model: Arc<dyn Model>) -> Self {
+        self.query_embedder = Some(QueryEmbedder::new(model));
+        self
+    }
+
+    async fn create_semantic_query_vector(
+        &self,
+        description: &str,
+    ) -> Result<Vec<f32>, StoreError> {
+        common::text_query_vector(
+            self.query_embedder.as_ref(),
+            description,
+            self.config.vector_dimension,
+        )
+        .await
+    }
+
+    fn normalize_vector(&self, vector: &mut Vec<f32>) -> Result<(), StoreError> {
+        common::normalize(vector);
+        Ok(())
+    }
+
     fn create_vector(&self, analysis: &EnhancedImageAnalysis) -> Result<Vec<f32>, StoreError> {
         let mut vector = Vec::with_capacity(self.config.vector_dimension);
 
@@ -255,7 +283,7 @@ impl VectorStore for ImageVectorStore {
         let query_vector = if let Some(features) = query.visual_features {
             features
         } else if let Some(description) = query.semantic_description {
-            self.create_semantic_query_vector(&description)?
+            self.create_semantic_query_vector(&description).await?
         } else {
             return Err(StoreError::InvalidQuery(
                 "No query features provided".into(),
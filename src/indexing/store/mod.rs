@@ -4,21 +4,23 @@ mod common;
 mod image_store;
 mod video_store;
 
-pub use audio_store::AudioVectorStore;
-pub use code_store::CodeVectorStore;
-use common::{StorageConfig, StoreError, VectorIndex};
-pub use image_store::ImageVectorStore;
-pub use video_store::VideoVectorStore;
+pub use audio_store::{AudioSearchResult, AudioVectorStore};
+pub use code_store::{CodeQuery, CodeQueryFilters, CodeSearchResult, CodeVectorStore};
+pub use common::{QueryEmbedder, StoreError};
+pub(crate) use common::write_atomically;
+use common::{StorageConfig, VectorIndex};
+pub use image_store::{ImageSearchResult, ImageVectorStore};
+pub use video_store::{VideoSearchResult, VideoVectorStore};
 
 use crate::indexing::embeddings::{EmbeddingGenerator, EmbeddingMetadata};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 #[async_trait]
 pub trait VectorStore: Send + Sync {
-    type Item;
+    type Item: Send;
     type Config;
     type Query;
     type Result;
@@ -27,6 +29,22 @@ pub trait VectorStore: Send + Sync {
     async fn search(&self, query: Self::Query) -> Result<Vec<Self::Result>, StoreError>;
     async fn save(&self, path: PathBuf) -> Result<(), StoreError>;
     async fn load(&mut self, path: PathBuf) -> Result<(), StoreError>;
+
+    /// Removes every vector indexed for `path`.
+    async fn remove(&mut self, path: &Path) -> Result<(), StoreError> {
+        Err(StoreError::Storage(format!(
+            "Removing {} is not supported by this store",
+            path.display()
+        )))
+    }
+
+    /// Replaces the vectors for the item's path with the item.
+    async fn update(&mut self, item: Self::Item) -> Result<(), StoreError> {
+        drop(item);
+        Err(StoreError::Storage(
+            "Updating items is not supported by this store".to_string(),
+        ))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +55,20 @@ pub struct StoreMetadata {
     pub modality: String,
 }
 
+pub struct ItemEmbedder<T: EmbeddingGenerator> {
+    generator: Arc<T>,
+    config: StorageConfig,
+}
+
+impl<T: EmbeddingGenerator> ItemEmbedder<T> {
+    pub async fn embed(&self, item: &T::Input) -> Result<Vec<f32>, StoreError> {
+        self.generator
+            .generate(item, &self.config.embedding_config)
+            .await
+            .map_err(|e| StoreError::Generation(e.to_string()))
+    }
+}
+
 pub struct StoreWithEmbeddings<T: EmbeddingGenerator> {
     store: VectorIndex,
     embedding_generator: Arc<T>,
@@ -60,20 +92,34 @@ impl<T: EmbeddingGenerator> StoreWithEmbeddings<T> {
     }
 
     pub async fn add_item(&mut self, item: T::Input) -> Result<(), StoreError> {
-        // Generate embedding
-        let embedding = self
-            .embedding_generator
-            .generate(&item, &self.config.embedding_config)
-            .await
-            .map_err(|e| StoreError::Generation(e.to_string()))?;
+        let embedding = self.embedder().embed(&item).await?;
+        self.insert(item, embedding)
+    }
 
-        // Create metadata
-        let metadata = self.create_item_metadata(&item)?;
+    /// The store's generator, detached so items can be embedded without
+    /// holding a lock on the store.
+    pub fn embedder(&self) -> ItemEmbedder<T> {
+        ItemEmbedder {
+            generator: self.embedding_generator.clone(),
+            config: self.config.clone(),
+        }
+    }
 
-        // Add to store
+    /// Adds `item` with an embedding made by this store's `embedder`.
+    pub fn insert(&mut self, item: T::Input, embedding: Vec<f32>) -> Result<(), StoreError> {
+        let metadata = self.create_item_metadata(&item)?;
         self.store.add_vector(embedding, metadata)?;
-        self.metadata.item_count += 1;
+        self.metadata.item_count = self.store.len();
+        Ok(())
+    }
+
+    pub fn item_count(&self) -> usize {
+        self.metadata.item_count
+    }
 
+    pub fn remove_item(&mut self, path: &Path) -> Result<(), StoreError> {
+        self.store.remove_path(&path.to_string_lossy());
+        self.metadata.item_count = self.store.len();
         Ok(())
     }
 
@@ -0,0 +1,156 @@
+use super::llm::EnhancedAnalysis;
+use super::store::write_atomically;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// LLM-enhanced analyses keyed by the md5 of the content they describe, so
+/// unchanged files skip the enhancer on later runs.
+#[derive(Debug)]
+pub struct SummaryCache<T = EnhancedAnalysis> {
+    path: Option<PathBuf>,
+    entries: HashMap<String, T>,
+    dirty: bool,
+}
+
+#[derive(Deserialize)]
+struct PersistedSummaries<T> {
+    entries: HashMap<String, T>,
+}
+
+impl<T> Default for SummaryCache<T> {
+    fn default() -> Self {
+        Self {
+            path: None,
+            entries: HashMap::new(),
+            dirty: false,
+        }
+    }
+}
+
+impl SummaryCache {
+    pub fn content_hash(content: &[u8]) -> String {
+        format!("{:x}", md5::compute(content))
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> SummaryCache<T> {
+    /// Opens the cache at `path`. A missing or unreadable file starts empty.
+    pub fn open(path: PathBuf) -> Self {
+        let entries = fs::File::open(&path)
+            .ok()
+            .and_then(|file| {
+                serde_json::from_reader::<_, PersistedSummaries<T>>(BufReader::new(file)).ok()
+            })
+            .map(|persisted| persisted.entries)
+            .unwrap_or_default();
+
+        Self {
+            path: Some(path),
+            entries,
+            dirty: false,
+        }
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&T> {
+        self.entries.get(hash)
+    }
+
+    pub fn insert(&mut self, hash: String, analysis: T) {
+        self.entries.insert(hash, analysis);
+        self.dirty = true;
+    }
+
+    /// Drops every cached summary so the next run regenerates them.
+    pub fn clear(&mut self) {
+        self.dirty |= !self.entries.is_empty();
+        self.entries.clear();
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        let path = match &self.path {
+            Some(path) if self.dirty => path,
+            _ => return Ok(()),
+        };
+
+        write_summaries(path, &self.entries)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+fn write_summaries<T: Serialize>(path: &Path, entries: &HashMap<String, T>) -> Result<(), String> {
+    #[derive(Serialize)]
+    struct Borrowed<'a, T> {
+        entries: &'a HashMap<String, T>,
+    }
+
+    write_atomically(path, |writer| {
+        serde_json::to_writer(writer, &Borrowed { entries }).map_err(std::io::Error::from)
+    })
+    .map_err(|e| format!("Failed to write summary cache {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Summarizes `content` the way `Indexer::index_content` does, counting
+    /// how often the summarizer had to run.
+    fn summarize(cache: &mut SummaryCache<String>, content: &str, calls: &mut usize) -> String {
+        let hash = SummaryCache::content_hash(content.as_bytes());
+        if let Some(cached) = cache.get(&hash) {
+            return cached.clone();
+        }
+        *calls += 1;
+        let summary = format!("summary of {}", content);
+        cache.insert(hash, summary.clone());
+        summary
+    }
+
+    #[test]
+    fn unchanged_content_reuses_the_saved_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".deeptracking-summaries.json");
+        let mut calls = 0;
+
+        let mut cache = SummaryCache::open(path.clone());
+        summarize(&mut cache, "fn a() {}", &mut calls);
+        cache.save().unwrap();
+        assert_eq!(calls, 1);
+
+        let mut cache = SummaryCache::open(path.clone());
+        assert_eq!(
+            summarize(&mut cache, "fn a() {}", &mut calls),
+            "summary of fn a() {}"
+        );
+        assert_eq!(calls, 1);
+        summarize(&mut cache, "fn a() { changed() }", &mut calls);
+        assert_eq!(calls, 2);
+
+        cache.clear();
+        summarize(&mut cache, "fn a() {}", &mut calls);
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn failed_save_keeps_the_previous_summaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".deeptracking-summaries.json");
+        let mut calls = 0;
+
+        let mut cache = SummaryCache::open(path.clone());
+        summarize(&mut cache, "fn a() {}", &mut calls);
+        cache.save().unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+
+        // The temporary file can't be created where a directory is in the way
+        fs::create_dir(dir.path().join(".deeptracking-summaries.json.tmp")).unwrap();
+        summarize(&mut cache, "fn b() {}", &mut calls);
+        assert!(cache.save().is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), saved);
+    }
+}
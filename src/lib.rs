@@ -1,6 +1,28 @@
 pub mod analyzers;
+#[cfg(feature = "python")]
+pub mod bridge;
 pub mod cli;
 pub mod graph;
 pub mod indexing;
 pub mod llm;
+pub mod progress;
 pub mod query;
+mod tracker;
+
+pub use analyzers::manager::AnalyzerManager;
+pub use analyzers::{AnalyzerError, Dependency, DependencyMetadata, DependencyType, TrackerError};
+pub use graph::{DependencyGraph, GraphError};
+pub use llm::CodeTokenizer;
+pub use progress::{AnalysisProgress, ProgressReporter};
+pub use tracker::CodeTracker;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Python module exposing the LlamaIndex bridge.
+#[cfg(feature = "python")]
+#[pymodule]
+fn deeptracking_llamaindex(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<bridge::PyLlamaIndexBridge>()?;
+    Ok(())
+}
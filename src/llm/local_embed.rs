@@ -0,0 +1,126 @@
+use super::model::{GenerationConfig, Model, ModelError, ModelResponse};
+use async_trait::async_trait;
+use candle_core::{Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+use std::fs;
+use std::path::Path;
+use tokenizers::{Tokenizer, TruncationParams};
+
+/// Files a model directory must contain, as exported by Hugging Face.
+const MODEL_FILES: [&str; 3] = ["config.json", "tokenizer.json", "model.safetensors"];
+
+/// Sentence embeddings from a BERT-style encoder run on the CPU, so an index
+/// can be built without an LLM.
+///
+/// The model is a directory holding `config.json`, `tokenizer.json` and
+/// `model.safetensors`, such as `sentence-transformers/all-MiniLM-L6-v2`
+/// (384 dimensions). An embedding is the mean of the last hidden states over
+/// the text's tokens, normalized to unit length, so its dimension is the
+/// config's `hidden_size`. Text past `max_position_embeddings` tokens is
+/// truncated. The model only embeds; `generate` fails.
+pub struct LocalEmbeddingModel {
+    model: BertModel,
+    tokenizer: Tokenizer,
+    dimension: usize,
+}
+
+impl LocalEmbeddingModel {
+    /// Whether `path` is a directory `load` can read.
+    pub fn is_model_dir(path: &Path) -> bool {
+        MODEL_FILES.iter().all(|file| path.join(file).is_file())
+    }
+
+    /// Loads the model in `dir`, failing if it produces vectors of a
+    /// different length than `expected_dimension`.
+    pub fn load(dir: &Path, expected_dimension: Option<usize>) -> Result<Self, String> {
+        let config_path = dir.join("config.json");
+        let config: Config = fs::read_to_string(&config_path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+            .map_err(|e| format!("Failed to read {}: {}", config_path.display(), e))?;
+
+        if let Some(expected) = expected_dimension {
+            if config.hidden_size != expected {
+                return Err(format!(
+                    "Embedding model {} produces {}-dimensional vectors, but {} are configured",
+                    dir.display(),
+                    config.hidden_size,
+                    expected
+                ));
+            }
+        }
+
+        let mut tokenizer = Tokenizer::from_file(dir.join("tokenizer.json"))
+            .map_err(|e| format!("Failed to load tokenizer from {}: {}", dir.display(), e))?;
+        tokenizer.with_padding(None);
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: config.max_position_embeddings,
+                ..Default::default()
+            }))
+            .map_err(|e| format!("Failed to configure tokenizer: {}", e))?;
+
+        let weights = dir.join("model.safetensors");
+        // Safety: the weights file is only read, and must not be modified
+        // while the model is loaded
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[&weights], DTYPE, &Device::Cpu) }
+            .map_err(|e| format!("Failed to map {}: {}", weights.display(), e))?;
+        let model = BertModel::load(vb, &config)
+            .map_err(|e| format!("Failed to load embedding model: {}", e))?;
+
+        Ok(Self {
+            model,
+            tokenizer,
+            dimension: config.hidden_size,
+        })
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn embed(&self, text: &str) -> Result<Vec<f32>, candle_core::Error> {
+        let encoding = self
+            .tokenizer
+            .encode(text, true)
+            .map_err(candle_core::Error::msg)?;
+        let device = &self.model.device;
+        let input_ids = Tensor::new(encoding.get_ids(), device)?.unsqueeze(0)?;
+        let type_ids = Tensor::new(encoding.get_type_ids(), device)?.unsqueeze(0)?;
+        let mask = Tensor::new(encoding.get_attention_mask(), device)?.unsqueeze(0)?;
+
+        // (1, tokens, hidden) -> mean over unmasked tokens -> unit length
+        let hidden = self.model.forward(&input_ids, &type_ids, Some(&mask))?;
+        let mask = mask.to_dtype(DTYPE)?.unsqueeze(2)?;
+        let mean = hidden
+            .broadcast_mul(&mask)?
+            .sum(1)?
+            .broadcast_div(&mask.sum(1)?)?;
+        let norm = mean.sqr()?.sum_keepdim(1)?.sqrt()?;
+        mean.broadcast_div(&norm)?.squeeze(0)?.to_vec1::<f32>()
+    }
+}
+
+#[async_trait]
+impl Model for LocalEmbeddingModel {
+    async fn generate(&self, prompt: &str) -> Result<ModelResponse, ModelError> {
+        self.generate_with_config(prompt, GenerationConfig::default())
+            .await
+    }
+
+    async fn generate_with_config(
+        &self,
+        _prompt: &str,
+        _config: GenerationConfig,
+    ) -> Result<ModelResponse, ModelError> {
+        Err(ModelError::InvalidRequest(
+            "Local embedding models cannot generate text".to_string(),
+        ))
+    }
+
+    async fn embed_text(&self, text: &str) -> Result<Vec<f32>, ModelError> {
+        self.embed(text)
+            .map_err(|e| ModelError::Other(format!("Embedding failed: {}", e)))
+    }
+}
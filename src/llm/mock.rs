@@ -0,0 +1,124 @@
+use super::model::{GenerationConfig, Model, ModelError, ModelResponse};
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// A `Model` with canned responses and deterministic embeddings, for testing
+/// code that needs a model without loading one.
+///
+/// `generate` returns the queued responses in order, then the default
+/// response, after failing any calls set up with `with_transient_failures`.
+/// `embed_text` hashes each word of the text into a fixed number of
+/// dimensions, so texts sharing words get similar vectors and the same text
+/// always gets the same one.
+pub struct MockModel {
+    responses: Mutex<VecDeque<String>>,
+    default_response: String,
+    dimension: usize,
+    prompts: Mutex<Vec<String>>,
+    transient_failures: Mutex<usize>,
+}
+
+impl Default for MockModel {
+    fn default() -> Self {
+        Self {
+            responses: Mutex::new(VecDeque::new()),
+            default_response: String::new(),
+            dimension: 64,
+            prompts: Mutex::new(Vec::new()),
+            transient_failures: Mutex::new(0),
+        }
+    }
+}
+
+impl MockModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues `response` after any already queued.
+    pub fn with_response(self, response: impl Into<String>) -> Self {
+        self.responses.lock().unwrap().push_back(response.into());
+        self
+    }
+
+    /// Returned once the queued responses run out.
+    pub fn with_default_response(mut self, response: impl Into<String>) -> Self {
+        self.default_response = response.into();
+        self
+    }
+
+    /// The next `count` calls to `generate` fail with
+    /// `ModelError::Transient`. Their prompts are still recorded.
+    pub fn with_transient_failures(self, count: usize) -> Self {
+        *self.transient_failures.lock().unwrap() += count;
+        self
+    }
+
+    pub fn with_dimension(mut self, dimension: usize) -> Self {
+        self.dimension = dimension.max(1);
+        self
+    }
+
+    /// Every prompt passed to `generate` so far, oldest first.
+    pub fn prompts(&self) -> Vec<String> {
+        self.prompts.lock().unwrap().clone()
+    }
+
+    fn embed(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimension];
+        for word in text.split(|c: char| !c.is_alphanumeric() && c != '_') {
+            if word.is_empty() {
+                continue;
+            }
+            let digest = md5::compute(word.to_lowercase().as_bytes()).0;
+            let bucket = u64::from_le_bytes(digest[..8].try_into().unwrap());
+            let sign = if digest[8] & 1 == 0 { 1.0 } else { -1.0 };
+            vector[(bucket % self.dimension as u64) as usize] += sign;
+        }
+
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        vector
+    }
+}
+
+#[async_trait]
+impl Model for MockModel {
+    async fn generate(&self, prompt: &str) -> Result<ModelResponse, ModelError> {
+        self.prompts.lock().unwrap().push(prompt.to_string());
+        {
+            let mut failures = self.transient_failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(ModelError::Transient("mock failure".to_string()));
+            }
+        }
+        let text = self
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| self.default_response.clone());
+
+        Ok(ModelResponse {
+            tokens_used: text.split_whitespace().count(),
+            text,
+            metadata: None,
+        })
+    }
+
+    async fn generate_with_config(
+        &self,
+        prompt: &str,
+        _config: GenerationConfig,
+    ) -> Result<ModelResponse, ModelError> {
+        self.generate(prompt).await
+    }
+
+    async fn embed_text(&self, text: &str) -> Result<Vec<f32>, ModelError> {
+        Ok(self.embed(text))
+    }
+}
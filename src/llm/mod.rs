@@ -1,7 +1,18 @@
+#[cfg(feature = "local-embeddings")]
+mod local_embed;
+#[cfg(any(test, feature = "test-utils"))]
+mod mock;
 mod model;
 mod prompts;
 mod tokenizer;
 
-pub use model::{GenerationConfig, Llama, Model, ModelResponse};
+#[cfg(feature = "local-embeddings")]
+pub use local_embed::LocalEmbeddingModel;
+#[cfg(any(test, feature = "test-utils"))]
+pub use mock::MockModel;
+pub use model::{
+    load_model, with_retry, GenerationConfig, Llama, Model, ModelError, ModelResponse, RetryConfig,
+    RetryingModel,
+};
 pub use prompts::{PromptTemplate, SystemPrompts};
 pub use tokenizer::CodeTokenizer;
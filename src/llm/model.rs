@@ -1,6 +1,10 @@
 use async_trait::async_trait;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GenerationConfig {
@@ -8,6 +12,69 @@ pub struct GenerationConfig {
     pub top_p: f32,
     pub top_k: i32,
     pub max_tokens: usize,
+    /// Generation stops before any of these strings.
+    #[serde(default)]
+    pub stop_sequences: Vec<String>,
+    /// Retry policy for transient failures. `None` fails on the first error.
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            temperature: 0.7,
+            top_p: 0.95,
+            top_k: 40,
+            max_tokens: 2048,
+            stop_sequences: Vec::new(),
+            retry: None,
+        }
+    }
+}
+
+impl GenerationConfig {
+    /// Greedy decoding, so the same prompt gets the same answer.
+    pub fn deterministic() -> Self {
+        Self {
+            temperature: 0.0,
+            top_p: 1.0,
+            top_k: 1,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryConfig {
+    pub max_retries: usize,
+    /// Delay before the first retry; doubled for each one after it.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Backoff before retry number `attempt` (from 0): the exponential delay
+    /// capped at `max_delay`, with its upper half randomized so concurrent
+    /// callers don't retry in lockstep.
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.min(u32::MAX as usize) as u32))
+            .min(self.max_delay);
+        let half = exponential / 2;
+        half + half.mul_f64(rand::thread_rng().gen::<f64>())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,15 +84,152 @@ pub struct ModelResponse {
     pub metadata: Option<serde_json::Value>,
 }
 
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ModelError {
+    #[error("Transient model error: {0}")]
+    Transient(String),
+    #[error("Rate limited (retry after {retry_after:?})")]
+    RateLimited { retry_after: Option<Duration> },
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Model error: {0}")]
+    Other(String),
+}
+
+impl ModelError {
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ModelError::Transient(_) | ModelError::RateLimited { .. }
+        )
+    }
+}
+
+impl From<ModelError> for String {
+    fn from(error: ModelError) -> Self {
+        error.to_string()
+    }
+}
+
 #[async_trait]
 pub trait Model: Send + Sync {
-    async fn generate(&self, prompt: &str) -> Result<ModelResponse, String>;
+    /// Generates with the model's own defaults.
+    async fn generate(&self, prompt: &str) -> Result<ModelResponse, ModelError> {
+        self.generate_with_config(prompt, GenerationConfig::default())
+            .await
+    }
+    async fn generate_with_config(
+        &self,
+        prompt: &str,
+        config: GenerationConfig,
+    ) -> Result<ModelResponse, ModelError>;
+    async fn embed_text(&self, text: &str) -> Result<Vec<f32>, ModelError>;
+}
+
+/// Runs `operation`, retrying with jittered exponential backoff while it
+/// fails with a retryable error. Rate-limit responses wait for the
+/// server-provided delay when one is given.
+pub async fn with_retry<T, F, Fut>(retry: &RetryConfig, mut operation: F) -> Result<T, ModelError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ModelError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(error) if error.is_retryable() && attempt < retry.max_retries => {
+                let delay = match &error {
+                    ModelError::RateLimited {
+                        retry_after: Some(retry_after),
+                    } => (*retry_after).min(retry.max_delay),
+                    _ => retry.delay(attempt),
+                };
+                log::warn!(
+                    "Model call failed ({}), retry {}/{} in {:?}",
+                    error,
+                    attempt + 1,
+                    retry.max_retries,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Wraps another model so every call is retried on transient errors. A
+/// `GenerationConfig` passed to `generate_with_config` may carry its own
+/// policy, which takes precedence for that call.
+pub struct RetryingModel {
+    inner: Arc<dyn Model>,
+    retry: RetryConfig,
+}
+
+impl RetryingModel {
+    pub fn new(inner: Arc<dyn Model>, retry: RetryConfig) -> Self {
+        Self { inner, retry }
+    }
+
+    /// Wraps `model` if `config` opts into retries, otherwise returns it as is.
+    pub fn from_config(model: Arc<dyn Model>, config: &GenerationConfig) -> Arc<dyn Model> {
+        match &config.retry {
+            Some(retry) => Arc::new(Self::new(model, retry.clone())),
+            None => model,
+        }
+    }
+}
+
+#[async_trait]
+impl Model for RetryingModel {
+    async fn generate(&self, prompt: &str) -> Result<ModelResponse, ModelError> {
+        with_retry(&self.retry, || self.inner.generate(prompt)).await
+    }
+
     async fn generate_with_config(
         &self,
         prompt: &str,
         config: GenerationConfig,
-    ) -> Result<ModelResponse, String>;
-    async fn embed_text(&self, text: &str) -> Result<Vec<f32>, String>;
+    ) -> Result<ModelResponse, ModelError> {
+        let retry = config.retry.clone().unwrap_or_else(|| self.retry.clone());
+        with_retry(&retry, || {
+            self.inner.generate_with_config(prompt, config.clone())
+        })
+        .await
+    }
+
+    async fn embed_text(&self, text: &str) -> Result<Vec<f32>, ModelError> {
+        with_retry(&self.retry, || self.inner.embed_text(text)).await
+    }
+}
+
+/// Loads the model at `path`: a local embedding model directory (see
+/// `LocalEmbeddingModel`, with the `local-embeddings` feature) or a Llama
+/// checkpoint. A local embedding model whose vectors aren't
+/// `expected_dimension` long (the configured `EmbeddingConfig::dimension`)
+/// is rejected.
+pub fn load_model(
+    path: &Path,
+    expected_dimension: Option<usize>,
+) -> Result<Arc<dyn Model>, String> {
+    #[cfg(feature = "local-embeddings")]
+    if super::local_embed::LocalEmbeddingModel::is_model_dir(path) {
+        return Ok(Arc::new(super::local_embed::LocalEmbeddingModel::load(
+            path,
+            expected_dimension,
+        )?));
+    }
+    #[cfg(not(feature = "local-embeddings"))]
+    let _ = expected_dimension;
+    if path.is_dir() {
+        return Err(format!(
+            "{} is a directory; embedding model directories need the local-embeddings feature",
+            path.display()
+        ));
+    }
+    Ok(Arc::new(Llama::new(path.to_path_buf())?))
 }
 
 pub struct Llama {
@@ -37,19 +241,23 @@ impl Llama {
     pub fn new(model_path: PathBuf) -> Result<Self, String> {
         Ok(Self {
             model_path,
-            config: GenerationConfig {
-                temperature: 0.7,
-                top_p: 0.95,
-                top_k: 40,
-                max_tokens: 2048,
-            },
+            config: GenerationConfig::default(),
         })
     }
+
+    pub fn with_config(mut self, config: GenerationConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &GenerationConfig {
+        &self.config
+    }
 }
 
 #[async_trait]
 impl Model for Llama {
-    async fn generate(&self, prompt: &str) -> Result<ModelResponse, String> {
+    async fn generate(&self, prompt: &str) -> Result<ModelResponse, ModelError> {
         self.generate_with_config(prompt, self.config.clone()).await
     }
 
@@ -57,15 +265,107 @@ impl Model for Llama {
         &self,
         prompt: &str,
         config: GenerationConfig,
-    ) -> Result<ModelResponse, String> {
+    ) -> Result<ModelResponse, ModelError> {
         // Implementation for generating text using Llama model
         // This will be implemented when we add the actual Llama integration
         todo!()
     }
 
-    async fn embed_text(&self, text: &str) -> Result<Vec<f32>, String> {
+    async fn embed_text(&self, text: &str) -> Result<Vec<f32>, ModelError> {
         // Implementation for generating embeddings
         // This will be implemented when we add the actual Llama integration
         todo!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn no_delay(max_retries: usize) -> RetryConfig {
+        RetryConfig {
+            max_retries,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        }
+    }
+
+    /// Calls `with_retry` with an operation failing with `error` on every
+    /// attempt, returning how many attempts were made.
+    async fn attempts_until_giving_up(error: ModelError) -> usize {
+        let attempts = AtomicUsize::new(0);
+        let result: Result<(), ModelError> = with_retry(&no_delay(3), || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            let error = error.clone();
+            async move { Err(error) }
+        })
+        .await;
+        assert!(result.is_err());
+        attempts.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn with_retry_retries_transient_errors_only() {
+        assert_eq!(
+            attempts_until_giving_up(ModelError::Transient("reset".into())).await,
+            4
+        );
+        assert_eq!(
+            attempts_until_giving_up(ModelError::RateLimited { retry_after: None }).await,
+            4
+        );
+        assert_eq!(
+            attempts_until_giving_up(ModelError::InvalidRequest("bad".into())).await,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn with_retry_returns_the_first_success() {
+        let attempts = AtomicUsize::new(0);
+        let result = with_retry(&no_delay(3), || {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt < 2 {
+                    Err(ModelError::Transient("busy".into()))
+                } else {
+                    Ok(attempt)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+    }
+
+    #[cfg(feature = "local-embeddings")]
+    #[test]
+    fn local_model_with_another_dimension_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = serde_json::json!({
+            "vocab_size": 30522,
+            "hidden_size": 384,
+            "num_hidden_layers": 6,
+            "num_attention_heads": 12,
+            "intermediate_size": 1536,
+            "hidden_act": "gelu",
+            "hidden_dropout_prob": 0.1,
+            "max_position_embeddings": 512,
+            "type_vocab_size": 2,
+            "initializer_range": 0.02,
+            "layer_norm_eps": 1e-12,
+            "pad_token_id": 0,
+        });
+        std::fs::write(dir.path().join("config.json"), config.to_string()).unwrap();
+        // Never read: the dimension is checked first
+        std::fs::write(dir.path().join("tokenizer.json"), "{}").unwrap();
+        std::fs::write(dir.path().join("model.safetensors"), "").unwrap();
+
+        let err = load_model(dir.path(), Some(768)).err().unwrap();
+        assert!(
+            err.contains("produces 384-dimensional vectors, but 768"),
+            "{}",
+            err
+        );
+    }
+}
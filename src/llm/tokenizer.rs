@@ -0,0 +1,118 @@
+use std::ops::Range;
+use std::sync::Arc;
+use tokenizers::Tokenizer;
+
+/// Counts and splits source text in model tokens. With a model vocabulary
+/// attached the counts are exact; without one they are estimated. Code is
+/// punctuation and indentation heavy, so the estimate works per symbol
+/// rather than per word.
+#[derive(Clone, Default)]
+pub struct CodeTokenizer {
+    vocab: Option<Arc<Tokenizer>>,
+}
+
+/// Characters a typical BPE vocabulary packs into one identifier token.
+const CHARS_PER_WORD_TOKEN: usize = 4;
+
+impl CodeTokenizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses `tokenizer`'s vocabulary instead of the estimate.
+    pub fn with_tokenizer(mut self, tokenizer: Arc<Tokenizer>) -> Self {
+        self.vocab = Some(tokenizer);
+        self
+    }
+
+    pub fn count_tokens(&self, text: &str) -> usize {
+        self.token_spans(text).len()
+    }
+
+    /// The longest prefix of `text` that fits in `max` tokens. Text that
+    /// already fits is returned whole.
+    pub fn truncate_to_tokens(&self, text: &str, max: usize) -> String {
+        let spans = self.token_spans(text);
+        match max {
+            _ if spans.len() <= max => text.to_string(),
+            0 => String::new(),
+            _ => text[..spans[max - 1].end].to_string(),
+        }
+    }
+
+    /// Byte range of every token in `text`, in order.
+    pub fn token_spans(&self, text: &str) -> Vec<Range<usize>> {
+        if let Some(vocab) = &self.vocab {
+            if let Ok(encoding) = vocab.encode(text, false) {
+                return encoding
+                    .get_offsets()
+                    .iter()
+                    .map(|&(start, end)| start..end)
+                    .collect();
+            }
+        }
+        estimate_spans(text)
+    }
+}
+
+impl std::fmt::Debug for CodeTokenizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CodeTokenizer")
+            .field("vocab", &self.vocab.is_some())
+            .finish()
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Identifiers and numbers take one token per few characters; every
+/// punctuation character, newline and run of indentation takes one.
+/// Other whitespace is folded into the following token.
+fn estimate_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    // Start and length in characters of the word piece being read
+    let mut word: Option<(usize, usize)> = None;
+    let mut indent_start = Some(0);
+
+    for (i, c) in text.char_indices() {
+        let end = i + c.len_utf8();
+
+        // Indentation of two or more columns is one token of its own
+        if !matches!(c, ' ' | '\t' | '\n') {
+            if let Some(start) = indent_start.take() {
+                if i - start > 1 {
+                    spans.push(start..i);
+                }
+            }
+        }
+
+        if is_word_char(c) {
+            word = match word {
+                Some((start, len)) if len < CHARS_PER_WORD_TOKEN => Some((start, len + 1)),
+                Some((start, _)) => {
+                    spans.push(start..i);
+                    Some((i, 1))
+                }
+                None => Some((i, 1)),
+            };
+            continue;
+        }
+
+        if let Some((start, _)) = word.take() {
+            spans.push(start..i);
+        }
+        if c == '\n' {
+            spans.push(i..end);
+            indent_start = Some(end);
+        } else if !c.is_whitespace() {
+            spans.push(i..end);
+        }
+    }
+
+    if let Some((start, _)) = word {
+        spans.push(start..text.len());
+    }
+    spans
+}
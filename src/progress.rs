@@ -0,0 +1,63 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// A step of a long-running analysis or indexing pass.
+#[derive(Debug, Clone)]
+pub enum AnalysisProgress {
+    /// Files found so far while walking the project.
+    Walking { count: usize },
+    /// A changed file is being handed to its analyzer.
+    Analyzing { path: PathBuf },
+    /// `path` was indexed, the `done`th of `total` files needing it.
+    Indexing {
+        path: PathBuf,
+        done: usize,
+        total: usize,
+    },
+    /// The pass finished.
+    Done,
+}
+
+impl fmt::Display for AnalysisProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalysisProgress::Walking { count } => write!(f, "Found {} files...", count),
+            AnalysisProgress::Analyzing { path } => write!(f, "Analyzing {}...", path.display()),
+            AnalysisProgress::Indexing { path, done, total } => {
+                write!(f, "Indexed {}/{}: {}", done, total, path.display())
+            }
+            AnalysisProgress::Done => write!(f, "Done"),
+        }
+    }
+}
+
+/// Hands progress events to a frontend's callback. The default reporter
+/// drops them. Parallel analysis reports from rayon workers, so the
+/// callback must be thread safe.
+#[derive(Clone, Default)]
+pub struct ProgressReporter {
+    callback: Option<Arc<dyn Fn(AnalysisProgress) + Send + Sync>>,
+}
+
+impl ProgressReporter {
+    pub fn new(callback: impl Fn(AnalysisProgress) + Send + Sync + 'static) -> Self {
+        Self {
+            callback: Some(Arc::new(callback)),
+        }
+    }
+
+    pub fn report(&self, event: AnalysisProgress) {
+        if let Some(callback) = &self.callback {
+            callback(event);
+        }
+    }
+}
+
+impl fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressReporter")
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
+}
@@ -1,5 +1,9 @@
 mod engine;
+mod related;
+mod relevance;
 mod response;
 
-pub use engine::{QueryContext, QueryEngine};
-pub use response::{CodeContext, QueryResponse, QueryResult};
+pub use engine::{QueryContext, QueryDryRun, QueryEngine, QuerySettings};
+pub use related::{co_changed_files, Recommender, RelatedFile, RelatedReason, RelatedWeights};
+pub use relevance::{RelevanceScorer, RelevanceWeights};
+pub use response::{CodeContext, ContextGroup, QueryResponse, QueryResult};
@@ -0,0 +1,286 @@
+use crate::analyzers::DependencyType;
+use crate::graph::{DependencyGraph, GraphError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Farthest graph distance, in edges, at which files still count as related.
+const MAX_GRAPH_DISTANCE: usize = 3;
+
+const ALL_EDGES: [DependencyType; 7] = [
+    DependencyType::Import,
+    DependencyType::FunctionCall,
+    DependencyType::Inheritance,
+    DependencyType::Usage,
+    DependencyType::FunctionDefinition,
+    DependencyType::TypeUsage,
+    DependencyType::TypeDefinition,
+];
+
+/// Why a file was recommended.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RelatedReason {
+    /// Reachable from the file through `distance` dependency edges, the
+    /// last of type `via`.
+    Dependency {
+        distance: usize,
+        via: DependencyType,
+    },
+    /// Depends on the file directly.
+    Dependent,
+    SharedDependency {
+        dependencies: Vec<PathBuf>,
+    },
+    SimilarContent {
+        similarity: f32,
+    },
+    /// Changed in the same commit as the file this many times.
+    CoChange {
+        commits: usize,
+    },
+}
+
+impl fmt::Display for RelatedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelatedReason::Dependency { distance: 1, via } => {
+                write!(f, "direct dependency ({:?})", via)
+            }
+            RelatedReason::Dependency { distance, via } => {
+                write!(f, "dependency {} hops away ({:?})", distance, via)
+            }
+            RelatedReason::Dependent => write!(f, "depends on it"),
+            RelatedReason::SharedDependency { dependencies } => {
+                write!(f, "shares {} dependencies", dependencies.len())
+            }
+            RelatedReason::SimilarContent { similarity } => {
+                write!(f, "similar content ({:.2})", similarity)
+            }
+            RelatedReason::CoChange { commits } => {
+                write!(f, "changed together in {} commits", commits)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RelatedFile {
+    pub path: PathBuf,
+    pub score: f32,
+    pub reasons: Vec<RelatedReason>,
+}
+
+/// How much each signal contributes to a recommendation. With weights
+/// summing to 1.0 scores stay within 0.0..=1.0.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelatedWeights {
+    pub graph: f32,
+    pub shared_dependencies: f32,
+    pub similarity: f32,
+    pub co_change: f32,
+}
+
+impl Default for RelatedWeights {
+    fn default() -> Self {
+        Self {
+            graph: 0.4,
+            shared_dependencies: 0.2,
+            similarity: 0.3,
+            co_change: 0.1,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Signals {
+    graph: f32,
+    shared_dependencies: f32,
+    similarity: f32,
+    co_change: f32,
+    reasons: Vec<RelatedReason>,
+}
+
+/// Recommends files related to a given one, combining closeness in the
+/// dependency graph with embedding similarity and commit history supplied
+/// by the caller.
+pub struct Recommender<'a> {
+    graph: &'a DependencyGraph,
+    similarities: HashMap<PathBuf, f32>,
+    co_changes: HashMap<PathBuf, usize>,
+    weights: RelatedWeights,
+}
+
+impl<'a> Recommender<'a> {
+    pub fn new(graph: &'a DependencyGraph) -> Self {
+        Self {
+            graph,
+            similarities: HashMap::new(),
+            co_changes: HashMap::new(),
+            weights: RelatedWeights::default(),
+        }
+    }
+
+    /// Embedding similarity of other files to the file being asked about.
+    /// A path listed more than once keeps its best score.
+    pub fn with_similarities(
+        mut self,
+        similarities: impl IntoIterator<Item = (PathBuf, f32)>,
+    ) -> Self {
+        for (path, similarity) in similarities {
+            let best = self.similarities.entry(path).or_insert(similarity);
+            *best = best.max(similarity);
+        }
+        self
+    }
+
+    /// Commits each other file shared with the file being asked about, as
+    /// returned by `co_changed_files`.
+    pub fn with_co_changes(mut self, co_changes: HashMap<PathBuf, usize>) -> Self {
+        self.co_changes = co_changes;
+        self
+    }
+
+    pub fn with_weights(mut self, weights: RelatedWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// The `top_k` files most related to `file`, best first. Paths are
+    /// graph keys, so `file` must be absolute like the graph's nodes.
+    pub fn related_files(&self, file: &Path, top_k: usize) -> Result<Vec<RelatedFile>, GraphError> {
+        let file = file.to_path_buf();
+        let mut candidates: HashMap<PathBuf, Signals> = HashMap::new();
+
+        for relationship in self.graph.get_indirect_relationships(&file)? {
+            let distance = relationship.depth + 1;
+            if distance > MAX_GRAPH_DISTANCE {
+                continue;
+            }
+            let signals = candidates
+                .entry(PathBuf::from(relationship.path))
+                .or_default();
+            signals.graph = signals.graph.max(1.0 / distance as f32);
+            signals.reasons.push(RelatedReason::Dependency {
+                distance,
+                via: relationship.relationship_type,
+            });
+        }
+
+        for dependent in self.graph.get_dependents(&file, &ALL_EDGES)? {
+            let signals = candidates.entry(dependent).or_default();
+            if !matches!(signals.reasons.last(), Some(RelatedReason::Dependent)) {
+                signals.graph = 1.0;
+                signals.reasons.push(RelatedReason::Dependent);
+            }
+        }
+
+        let mut dependencies: Vec<PathBuf> = self
+            .graph
+            .get_dependencies(&file)?
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        dependencies.sort();
+        dependencies.dedup();
+
+        let mut shared: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+        for dependency in &dependencies {
+            for other in self.graph.get_dependents(dependency, &ALL_EDGES)? {
+                let entry = shared.entry(other).or_default();
+                if entry.last() != Some(dependency) {
+                    entry.push(dependency.clone());
+                }
+            }
+        }
+        for (path, shared_dependencies) in shared {
+            let signals = candidates.entry(path).or_default();
+            signals.shared_dependencies =
+                shared_dependencies.len() as f32 / dependencies.len() as f32;
+            signals.reasons.push(RelatedReason::SharedDependency {
+                dependencies: shared_dependencies,
+            });
+        }
+
+        for (path, similarity) in &self.similarities {
+            if *similarity > 0.0 {
+                let signals = candidates.entry(path.clone()).or_default();
+                signals.similarity = similarity.min(1.0);
+                signals.reasons.push(RelatedReason::SimilarContent {
+                    similarity: *similarity,
+                });
+            }
+        }
+
+        let most_commits = self.co_changes.values().copied().max().unwrap_or(0);
+        for (path, commits) in &self.co_changes {
+            if *commits > 0 {
+                let signals = candidates.entry(path.clone()).or_default();
+                signals.co_change = *commits as f32 / most_commits as f32;
+                signals
+                    .reasons
+                    .push(RelatedReason::CoChange { commits: *commits });
+            }
+        }
+
+        // Scoped nodes such as `function:foo` and unresolved module paths
+        // have no extension; only recommend files
+        candidates.remove(&file);
+        let mut related: Vec<RelatedFile> = candidates
+            .into_iter()
+            .filter(|(path, _)| path.extension().is_some())
+            .map(|(path, signals)| RelatedFile {
+                score: self.weights.graph * signals.graph
+                    + self.weights.shared_dependencies * signals.shared_dependencies
+                    + self.weights.similarity * signals.similarity
+                    + self.weights.co_change * signals.co_change,
+                path,
+                reasons: signals.reasons,
+            })
+            .collect();
+
+        related.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        related.truncate(top_k);
+        Ok(related)
+    }
+}
+
+/// How often each other file changed in the same commit as `file` over the
+/// last `max_commits` commits touching it. Empty when `root` is not a git
+/// checkout or git is unavailable.
+pub fn co_changed_files(root: &Path, file: &Path, max_commits: usize) -> HashMap<PathBuf, usize> {
+    let mut counts = HashMap::new();
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["log", "--full-diff", "--name-only", "--format=%x00"])
+        .arg(format!("-n{}", max_commits))
+        .arg("--")
+        .arg(file)
+        .output();
+
+    let output = match output {
+        Ok(output) if output.status.success() => output,
+        _ => return counts,
+    };
+
+    let file = file.strip_prefix(root).unwrap_or(file);
+    for commit in String::from_utf8_lossy(&output.stdout).split('\0') {
+        for name in commit
+            .lines()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            if Path::new(name) != file {
+                *counts.entry(root.join(name)).or_insert(0) += 1;
+            }
+        }
+    }
+    counts
+}
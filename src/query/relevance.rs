@@ -0,0 +1,106 @@
+use super::response::CodeContext;
+use crate::analyzers::manager::ProjectState;
+use crate::analyzers::DependencyType;
+use crate::graph::DependencyGraph;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Edges that make the target file a dependency of the source.
+const DEPENDENT_EDGES: [DependencyType; 5] = [
+    DependencyType::Import,
+    DependencyType::FunctionCall,
+    DependencyType::Inheritance,
+    DependencyType::Usage,
+    DependencyType::TypeUsage,
+];
+
+/// How much each signal contributes to a context's relevance. With weights
+/// summing to 1.0 scores stay within 0.0..=1.0.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelevanceWeights {
+    pub similarity: f32,
+    pub centrality: f32,
+    pub recency: f32,
+    /// Number of dependents at which the centrality boost reaches half.
+    pub centrality_midpoint: f32,
+    /// Age in days at which the recency boost halves.
+    pub recency_half_life_days: f32,
+}
+
+impl Default for RelevanceWeights {
+    fn default() -> Self {
+        Self {
+            similarity: 0.7,
+            centrality: 0.2,
+            recency: 0.1,
+            centrality_midpoint: 5.0,
+            recency_half_life_days: 30.0,
+        }
+    }
+}
+
+/// Ranks query results by embedding similarity, boosted for files many
+/// others depend on and for files changed recently.
+pub struct RelevanceScorer<'a> {
+    graph: &'a DependencyGraph,
+    state: &'a ProjectState,
+    /// Context paths are relative to this; graph and state paths are not.
+    root: PathBuf,
+    weights: RelevanceWeights,
+}
+
+impl<'a> RelevanceScorer<'a> {
+    pub fn new(graph: &'a DependencyGraph, state: &'a ProjectState, root: PathBuf) -> Self {
+        Self {
+            graph,
+            state,
+            root,
+            weights: RelevanceWeights::default(),
+        }
+    }
+
+    pub fn with_weights(mut self, weights: RelevanceWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// `similarity_score` is the context's embedding similarity to the
+    /// query; files missing from the graph or state get no boost.
+    pub fn score_relevance(&self, context: &CodeContext) -> f32 {
+        let file = self.root.join(&context.file_path);
+
+        let dependents = self
+            .graph
+            .get_dependents(&file, &DEPENDENT_EDGES)
+            .map(|dependents| dependents.len())
+            .unwrap_or(0) as f32;
+        let centrality =
+            dependents / (dependents + self.weights.centrality_midpoint.max(f32::EPSILON));
+
+        let recency = self
+            .state
+            .analyzed_files
+            .get(&file)
+            .map(|state| {
+                let age_days =
+                    (Utc::now() - state.last_modified).num_seconds().max(0) as f32 / 86_400.0;
+                0.5f32.powf(age_days / self.weights.recency_half_life_days.max(f32::EPSILON))
+            })
+            .unwrap_or(0.0);
+
+        self.weights.similarity * context.similarity_score
+            + self.weights.centrality * centrality
+            + self.weights.recency * recency
+    }
+
+    /// Sorts `contexts` most relevant first. Ties keep their search order.
+    pub fn rank(&self, contexts: &mut Vec<CodeContext>) {
+        let mut scored: Vec<(f32, CodeContext)> = contexts
+            .drain(..)
+            .map(|context| (self.score_relevance(&context), context))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        contexts.extend(scored.into_iter().map(|(_, context)| context));
+    }
+}
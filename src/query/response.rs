@@ -1,6 +1,7 @@
+use super::engine::EnhancedSearchResult;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct QueryResponse {
@@ -27,6 +28,15 @@ pub struct ResponseMetadata {
     pub relationship_depth: usize,
 }
 
+/// Code contexts sharing a directory, e.g. everything under `src/auth`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ContextGroup {
+    pub directory: String,
+    pub contexts: Vec<CodeContext>,
+    pub max_similarity: f32,
+    pub mean_similarity: f32,
+}
+
 impl From<EnhancedSearchResult> for CodeContext {
     fn from(result: EnhancedSearchResult) -> Self {
         CodeContext {
@@ -35,7 +45,7 @@ impl From<EnhancedSearchResult> for CodeContext {
                 .result
                 .metadata
                 .as_ref()
-                .map(|m| m.content_hash.clone())
+                .map(|m| m.content.clone())
                 .unwrap_or_default(),
             language: result
                 .result
@@ -55,6 +65,7 @@ impl QueryResponse {
         code_contexts: Vec<CodeContext>,
         execution_time_ms: u64,
     ) -> Self {
+        let num_results = code_contexts.len();
         Self {
             query,
             response,
@@ -62,40 +73,149 @@ impl QueryResponse {
             metadata: ResponseMetadata {
                 timestamp: chrono::Utc::now(),
                 execution_time_ms,
-                num_results: code_contexts.len(),
+                num_results,
                 relationship_depth: 2, // Default value, could be configurable
             },
         }
     }
 
     pub fn format_markdown(&self) -> String {
+        let mut markdown = self.format_markdown_header();
+
+        // Add code contexts
+        markdown.push_str("# Relevant Code Sections\n\n");
+        for context in &self.code_contexts {
+            Self::push_context_markdown(&mut markdown, context, "##");
+        }
+
+        markdown.push_str(&self.format_markdown_metadata());
+        markdown
+    }
+
+    /// Like `format_markdown`, but with contexts grouped by directory so it
+    /// is clear which subsystems were involved.
+    pub fn format_markdown_grouped(&self) -> String {
+        let mut markdown = self.format_markdown_header();
+
+        markdown.push_str("# Relevant Code Sections\n\n");
+        for group in self.group_by_directory() {
+            markdown.push_str(&format!("## {}\n\n", group.directory));
+            markdown.push_str(&format!(
+                "{} sections, best match {:.2}, average {:.2}\n\n",
+                group.contexts.len(),
+                group.max_similarity,
+                group.mean_similarity
+            ));
+            for context in &group.contexts {
+                Self::push_context_markdown(&mut markdown, context, "###");
+            }
+        }
+
+        markdown.push_str(&self.format_markdown_metadata());
+        markdown
+    }
+
+    pub fn format_json(&self, group_by_directory: bool) -> Result<String, String> {
+        let json = if group_by_directory {
+            serde_json::to_string_pretty(&serde_json::json!({
+                "query": self.query,
+                "response": self.response,
+                "groups": self.group_by_directory(),
+                "metadata": self.metadata,
+            }))
+        } else {
+            serde_json::to_string_pretty(self)
+        };
+
+        json.map_err(|e| format!("Failed to serialize response: {}", e))
+    }
+
+    /// Groups contexts by their first two directory levels, most relevant
+    /// group first.
+    pub fn group_by_directory(&self) -> Vec<ContextGroup> {
+        let mut groups: Vec<ContextGroup> = Vec::new();
+
+        for context in &self.code_contexts {
+            let directory = Self::group_directory(&context.file_path);
+            match groups.iter_mut().find(|group| group.directory == directory) {
+                Some(group) => group.contexts.push(context.clone()),
+                None => groups.push(ContextGroup {
+                    directory,
+                    contexts: vec![context.clone()],
+                    max_similarity: 0.0,
+                    mean_similarity: 0.0,
+                }),
+            }
+        }
+
+        for group in &mut groups {
+            let scores = group.contexts.iter().map(|c| c.similarity_score);
+            group.max_similarity = scores.clone().fold(f32::MIN, f32::max);
+            group.mean_similarity = scores.sum::<f32>() / group.contexts.len() as f32;
+        }
+
+        groups.sort_by(|a, b| {
+            b.max_similarity
+                .partial_cmp(&a.max_similarity)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        groups
+    }
+
+    fn group_directory(file_path: &Path) -> String {
+        let directories: Vec<String> = file_path
+            .parent()
+            .map(|parent| {
+                parent
+                    .components()
+                    .filter_map(|component| match component {
+                        Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                        _ => None,
+                    })
+                    .take(2)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if directories.is_empty() {
+            ".".to_string()
+        } else {
+            directories.join("/")
+        }
+    }
+
+    fn format_markdown_header(&self) -> String {
         let mut markdown = String::new();
 
         // Add query and response
         markdown.push_str(&format!("# Query\n\n{}\n\n", self.query));
         markdown.push_str(&format!("# Response\n\n{}\n\n", self.response));
 
-        // Add code contexts
-        markdown.push_str("# Relevant Code Sections\n\n");
-        for context in &self.code_contexts {
-            markdown.push_str(&format!("## {}\n\n", context.file_path.display()));
-            markdown.push_str("```");
-            if let Some(lang) = &context.language {
-                markdown.push_str(lang);
+        markdown
+    }
+
+    fn push_context_markdown(markdown: &mut String, context: &CodeContext, heading: &str) {
+        markdown.push_str(&format!("{} {}\n\n", heading, context.file_path.display()));
+        markdown.push_str("```");
+        if let Some(lang) = &context.language {
+            markdown.push_str(lang);
+        }
+        markdown.push_str("\n");
+        markdown.push_str(&context.content);
+        markdown.push_str("\n```\n\n");
+
+        // Add relationships
+        if !context.relationships.is_empty() {
+            markdown.push_str(&format!("{}# Relationships\n\n", heading));
+            for (rel_type, targets) in &context.relationships {
+                markdown.push_str(&format!("- {}: {}\n", rel_type, targets.join(", ")));
             }
             markdown.push_str("\n");
-            markdown.push_str(&context.content);
-            markdown.push_str("\n```\n\n");
-
-            // Add relationships
-            if !context.relationships.is_empty() {
-                markdown.push_str("### Relationships\n\n");
-                for (rel_type, targets) in &context.relationships {
-                    markdown.push_str(&format!("- {}: {}\n", rel_type, targets.join(", ")));
-                }
-                markdown.push_str("\n");
-            }
         }
+    }
+
+    fn format_markdown_metadata(&self) -> String {
+        let mut markdown = String::new();
 
         // Add metadata
         markdown.push_str("# Metadata\n\n");
@@ -109,3 +229,51 @@ impl QueryResponse {
         markdown
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(file_path: &str, similarity_score: f32) -> CodeContext {
+        CodeContext {
+            file_path: PathBuf::from(file_path),
+            content: String::new(),
+            language: Some("rust".to_string()),
+            relationships: HashMap::new(),
+            similarity_score,
+        }
+    }
+
+    #[test]
+    fn grouped_markdown_puts_each_directory_under_its_own_header() {
+        let response = QueryResponse::new(
+            "how do logins reach the database".to_string(),
+            "answer".to_string(),
+            vec![
+                context("src/auth/login.rs", 0.9),
+                context("src/db/pool.rs", 0.7),
+                context("src/auth/session.rs", 0.5),
+            ],
+            10,
+        );
+
+        let markdown = response.format_markdown_grouped();
+        let auth = markdown.find("## src/auth\n").unwrap();
+        let db = markdown.find("## src/db\n").unwrap();
+        assert!(auth < db);
+        assert!(markdown[auth..db].contains("### src/auth/login.rs"));
+        assert!(markdown[auth..db].contains("### src/auth/session.rs"));
+        assert!(markdown[db..].contains("### src/db/pool.rs"));
+        assert!(markdown[auth..db].contains("2 sections, best match 0.90, average 0.70"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&response.format_json(true).unwrap()).unwrap();
+        let directories: Vec<&str> = json["groups"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|group| group["directory"].as_str().unwrap())
+            .collect();
+        assert_eq!(directories, vec!["src/auth", "src/db"]);
+    }
+}
@@ -0,0 +1,63 @@
+use crate::analyzers::manager::AnalyzerManager;
+use crate::analyzers::TrackerError;
+use crate::graph::DependencyGraph;
+use std::path::{Path, PathBuf};
+
+/// Static analysis of a project without any model or index: analyzes the
+/// sources under a root and builds the dependency graph from them.
+///
+/// ```no_run
+/// use deeptracking_llamaindex::CodeTracker;
+/// use std::path::PathBuf;
+///
+/// let mut tracker = CodeTracker::new("path/to/project")?;
+/// tracker.analyze()?;
+/// let deps = tracker.get_dependencies(&PathBuf::from("path/to/project/src/lib.rs"))?;
+/// # Ok::<(), deeptracking_llamaindex::TrackerError>(())
+/// ```
+#[derive(Debug)]
+pub struct CodeTracker {
+    root: PathBuf,
+    analyzer: AnalyzerManager,
+    graph: DependencyGraph,
+}
+
+impl CodeTracker {
+    pub fn new(root: impl AsRef<Path>) -> Result<Self, TrackerError> {
+        let root = root.as_ref().to_path_buf();
+        Ok(Self {
+            analyzer: AnalyzerManager::new(&root, None).map_err(TrackerError::Analysis)?,
+            root,
+            graph: DependencyGraph::new(),
+        })
+    }
+
+    /// Analyzes every source file under the root and rebuilds the graph,
+    /// with imports resolved to the project files they name.
+    pub fn analyze(&mut self) -> Result<&DependencyGraph, TrackerError> {
+        self.analyzer
+            .analyze_project(&self.root)
+            .map_err(TrackerError::Analysis)?;
+
+        self.graph = DependencyGraph::new();
+        self.graph
+            .add_dependencies(self.analyzer.resolved_dependencies())
+            .map_err(|e| TrackerError::Graph(e.to_string()))?;
+        self.graph.resolve_calls();
+        Ok(&self.graph)
+    }
+
+    pub fn get_dependencies(&self, file: &PathBuf) -> Result<Vec<String>, TrackerError> {
+        self.graph
+            .get_dependencies(file)
+            .map_err(|e| TrackerError::Graph(e.to_string()))
+    }
+
+    pub fn graph(&self) -> &DependencyGraph {
+        &self.graph
+    }
+
+    pub fn analyzer(&self) -> &AnalyzerManager {
+        &self.analyzer
+    }
+}
@@ -0,0 +1,45 @@
+use std::fs;
+use std::process::{Command, Output};
+
+/// Runs the CLI in a small project with stdout and stderr captured, so
+/// neither is a terminal. Colors are forced through the environment to show
+/// the CLI's own override wins.
+fn run_cli(args: &[&str]) -> Output {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("src")).unwrap();
+    fs::write(dir.path().join("src/lib.rs"), "pub fn helper() {}\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_deeptracking-llamaindex"))
+        .args(args)
+        .current_dir(dir.path())
+        .env("CLICOLOR_FORCE", "1")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+fn assert_no_escape_codes(output: &Output) {
+    for stream in [&output.stdout, &output.stderr] {
+        let text = String::from_utf8_lossy(stream);
+        assert!(!text.contains('\u{1b}'), "escape code in {:?}", text);
+    }
+}
+
+#[test]
+fn quiet_output_has_no_escape_codes() {
+    let output = run_cli(&["--quiet", "analyze", "--output", "report.txt"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Report written to"));
+    assert_no_escape_codes(&output);
+}
+
+#[test]
+fn output_that_is_not_a_terminal_has_no_escape_codes() {
+    let output = run_cli(&["analyze", "--output", "report.txt"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Report written to"));
+    assert_no_escape_codes(&output);
+}
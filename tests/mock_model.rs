@@ -0,0 +1,35 @@
+//! Runs the query pipeline end to end with `MockModel` standing in for a
+//! real model. Needs `--features test-utils`.
+
+use deeptracking_llamaindex::indexing::CodeVectorStore;
+use deeptracking_llamaindex::llm::MockModel;
+use deeptracking_llamaindex::query::{QueryEngine, QuerySettings};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+#[tokio::test]
+async fn query_answers_with_the_mock_model() {
+    let model = Arc::new(
+        MockModel::new()
+            .with_response("Needs the location of the parser.")
+            .with_response("Parsing happens in src/parser.rs."),
+    );
+    let store = Arc::new(RwLock::new(CodeVectorStore::new(Default::default())));
+    let engine = QueryEngine::new(
+        store,
+        model.clone(),
+        QuerySettings {
+            include_relationships: false,
+            ..QuerySettings::default()
+        },
+    );
+
+    let response = engine.query("where is parsing done?").await.unwrap();
+    assert_eq!(response.query, "where is parsing done?");
+    assert_eq!(response.response, "Parsing happens in src/parser.rs.");
+
+    // One call to read the intent, one for the answer
+    let prompts = model.prompts();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts.iter().all(|p| p.contains("where is parsing done?")));
+}